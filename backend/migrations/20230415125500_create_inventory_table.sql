@@ -1,4 +1,4 @@
--- migration.sql
+-- 20230415125500_create_inventory_table.sql
 
 CREATE TABLE inventory (
     id SERIAL PRIMARY KEY,
@@ -1,3 +1,4 @@
+-- 20230415130000_create_vehicle_table.sql
 -- Vehicles
 CREATE TABLE vehicles (
     id SERIAL PRIMARY KEY,
@@ -0,0 +1,5 @@
+-- 20250601090000_add_player_combat_stats.sql
+
+ALTER TABLE players
+    ADD COLUMN health INT NOT NULL DEFAULT 100,
+    ADD COLUMN max_health INT NOT NULL DEFAULT 100;
@@ -0,0 +1,5 @@
+-- 20250602090000_add_player_mana.sql
+
+ALTER TABLE players
+    ADD COLUMN mana INT NOT NULL DEFAULT 50,
+    ADD COLUMN max_mana INT NOT NULL DEFAULT 50;
@@ -0,0 +1,10 @@
+-- 20250602091000_create_player_skills_table.sql
+
+CREATE TABLE player_skills (
+    id SERIAL PRIMARY KEY,
+    player_id INT NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    skill_id INT NOT NULL REFERENCES skills(id) ON DELETE CASCADE,
+    level INT NOT NULL DEFAULT 1,
+    acquired_at TIMESTAMP DEFAULT NOW(),
+    UNIQUE (player_id, skill_id)
+);
@@ -0,0 +1,10 @@
+-- 20250603090000_add_skill_status_effects.sql
+
+ALTER TABLE skills
+    ADD COLUMN status_effect VARCHAR(50),
+    ADD COLUMN effect_duration INT;
+
+UPDATE skills SET status_effect = 'AttackUp', effect_duration = 3 WHERE name = 'Battle Cry';
+UPDATE skills SET status_effect = 'AccuracyDown', effect_duration = 3 WHERE name = 'Smokescreen';
+UPDATE skills SET status_effect = 'Regeneration', effect_duration = 5 WHERE name = 'Regeneration';
+UPDATE skills SET status_effect = 'Stun', effect_duration = 1 WHERE name = 'Charm';
@@ -0,0 +1,15 @@
+-- 20250603091000_create_player_status_effects_table.sql
+
+-- Effects that outlive a combat encounter. Turn-based effects only exist in memory.
+CREATE TABLE player_status_effects (
+    id SERIAL PRIMARY KEY,
+    player_id INT NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    effect_id VARCHAR(100) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    kind VARCHAR(50) NOT NULL,
+    magnitude INT NOT NULL,
+    stacks INT NOT NULL DEFAULT 1,
+    source VARCHAR(255) NOT NULL,
+    expires_at BIGINT, -- unix seconds, NULL for permanent effects
+    UNIQUE (player_id, effect_id)
+);
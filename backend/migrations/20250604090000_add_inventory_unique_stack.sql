@@ -0,0 +1,32 @@
+-- 20250604090000_add_inventory_unique_stack.sql
+
+-- Copies with nothing of their own (no durability, no curse) share one stack row per player
+-- and item. Every other copy keeps its own row, so wear and curses are never merged away.
+UPDATE inventory SET is_cursed = FALSE WHERE is_cursed IS NULL;
+
+ALTER TABLE inventory
+    ALTER COLUMN is_cursed SET NOT NULL,
+    ADD COLUMN stacked BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE inventory SET stacked = TRUE WHERE durability IS NULL AND NOT is_cursed;
+
+-- Merge duplicate stacks
+UPDATE inventory i
+SET quantity = merged.total
+FROM (
+    SELECT MIN(id) AS keep_id, SUM(quantity) AS total
+    FROM inventory
+    WHERE stacked
+    GROUP BY player_id, item_id
+) merged
+WHERE i.id = merged.keep_id;
+
+DELETE FROM inventory i
+USING inventory j
+WHERE i.stacked
+  AND j.stacked
+  AND i.player_id = j.player_id
+  AND i.item_id = j.item_id
+  AND i.id > j.id;
+
+CREATE UNIQUE INDEX inventory_player_item_stack_key ON inventory (player_id, item_id) WHERE stacked;
@@ -0,0 +1,9 @@
+-- 20250605090000_create_player_equipment_table.sql
+
+CREATE TABLE player_equipment (
+    player_id INT NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    slot VARCHAR(50) NOT NULL,
+    inventory_id INT NOT NULL UNIQUE REFERENCES inventory(id) ON DELETE CASCADE,
+    equipped_at TIMESTAMP DEFAULT NOW(),
+    PRIMARY KEY (player_id, slot)
+);
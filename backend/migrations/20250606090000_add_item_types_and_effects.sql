@@ -0,0 +1,24 @@
+-- 20250606090000_add_item_types_and_effects.sql
+
+-- Potions were stored under their own type before consumables existed
+UPDATE items SET item_type = 'Consumable' WHERE item_type = 'Potion';
+
+ALTER TABLE items
+    ALTER COLUMN item_type SET NOT NULL,
+    ADD CONSTRAINT items_item_type_check CHECK (item_type IN (
+        'Weapon', 'Armor', 'Consumable', 'Accessory', 'MountAccessory', 'TechKey', 'MagicItem', 'Tool'
+    )),
+    ADD COLUMN effects JSONB NOT NULL DEFAULT '[]';
+
+UPDATE items SET effects = '[{"effect": "heal", "amount": 25}]' WHERE name = 'Healing Potion';
+UPDATE items SET effects = '[{"effect": "summon_minion", "minion_type": "Skeleton", "health": 20, "power": 8}]'
+    WHERE name = 'Necromancer Skull';
+UPDATE items SET effects = '[{"effect": "unlock_portal", "portal_id": "portal_lab"}]' WHERE name = 'Battlemech Chip';
+
+-- Portals a player has opened up by using an item, e.g. a tech key
+CREATE TABLE player_unlocked_portals (
+    player_id INT NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    portal_id VARCHAR(255) NOT NULL,
+    unlocked_at TIMESTAMP DEFAULT NOW(),
+    PRIMARY KEY (player_id, portal_id)
+);
@@ -0,0 +1,4 @@
+-- 20250607090000_add_item_content_id.sql
+
+-- Stable ID of the content file an item was loaded from; NULL for items created any other way
+ALTER TABLE items ADD COLUMN content_id VARCHAR(255) UNIQUE;
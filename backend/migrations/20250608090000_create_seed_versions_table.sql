@@ -0,0 +1,61 @@
+-- 20250608090000_create_seed_versions_table.sql
+
+-- Which version of each seed set has been applied
+CREATE TABLE seed_versions (
+    name VARCHAR(100) PRIMARY KEY,
+    version INT NOT NULL,
+    applied_at TIMESTAMP DEFAULT NOW()
+);
+
+-- Every debug startup used to insert the seed skills again. Merge the copies into the first
+-- one, keeping the highest level when a player learned several copies.
+CREATE TEMP TABLE skill_merge AS
+SELECT id AS old_id, MIN(id) OVER (PARTITION BY name) AS keep_id FROM skills;
+
+DELETE FROM player_skills ps
+USING skill_merge m, player_skills other, skill_merge om
+WHERE ps.skill_id = m.old_id
+  AND other.player_id = ps.player_id
+  AND other.skill_id = om.old_id
+  AND om.keep_id = m.keep_id
+  AND (other.level, other.id) > (ps.level, ps.id);
+
+UPDATE player_skills ps
+SET skill_id = m.keep_id
+FROM skill_merge m
+WHERE ps.skill_id = m.old_id AND m.old_id <> m.keep_id;
+
+DELETE FROM skills s
+USING skill_merge m
+WHERE s.id = m.old_id AND m.old_id <> m.keep_id;
+
+DROP TABLE skill_merge;
+
+ALTER TABLE skills ADD CONSTRAINT skills_name_key UNIQUE (name);
+
+-- Same for the seed items: drop copies nobody holds, and key the first copy on a content ID so
+-- the seeds can upsert it
+DELETE FROM items i
+USING items first
+WHERE i.name = first.name
+  AND i.id > first.id
+  AND i.content_id IS NULL
+  AND NOT EXISTS (SELECT 1 FROM inventory inv WHERE inv.item_id = i.id);
+
+UPDATE items i
+SET content_id = seeded.content_id
+FROM (VALUES
+    ('Iron Sword', 'iron_sword'),
+    ('Healing Potion', 'healing_potion'),
+    ('Staff of Fire', 'staff_of_fire'),
+    ('Cursed Ring', 'cursed_ring'),
+    ('Leather Armor', 'leather_armor'),
+    ('Pegasus Saddle', 'pegasus_saddle'),
+    ('Battlemech Chip', 'battlemech_chip'),
+    ('Elven Cloak', 'elven_cloak'),
+    ('Necromancer Skull', 'necromancer_skull'),
+    ('Explorer''s Compass', 'explorers_compass')
+) AS seeded(name, content_id)
+WHERE i.name = seeded.name
+  AND i.content_id IS NULL
+  AND i.id = (SELECT MIN(id) FROM items WHERE name = seeded.name);
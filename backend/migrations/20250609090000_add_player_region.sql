@@ -0,0 +1,7 @@
+-- 20250609090000_add_player_region.sql
+
+-- The region each player is standing in; everyone starts out in the nexus
+ALTER TABLE players
+    ADD COLUMN region_id VARCHAR(255) NOT NULL DEFAULT 'nexus';
+
+CREATE INDEX idx_players_region_id ON players (region_id);
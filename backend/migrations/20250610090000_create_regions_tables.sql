@@ -0,0 +1,38 @@
+-- 20250610090000_create_regions_tables.sql
+
+-- The live world. Regions come from content files, the region generator or an import, and
+-- `origin` says which: content regions are replaced on every content sync, the others are
+-- kept until deleted.
+CREATE TABLE regions (
+    id VARCHAR(255) PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    description TEXT NOT NULL,
+    environment VARCHAR(50) NOT NULL,
+    anchor_point VARCHAR(255),
+    generated JSONB,
+    layout JSONB,
+    origin VARCHAR(50) NOT NULL,
+    -- Bumped whenever the region or its portals change
+    version INT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP DEFAULT NOW(),
+    updated_at TIMESTAMP DEFAULT NOW()
+);
+
+-- Portals out of a region. `leads_to` isn't a foreign key so a region can be removed without
+-- first removing every way into it; the map reports those as broken links.
+CREATE TABLE portals (
+    id VARCHAR(255) PRIMARY KEY,
+    region_id VARCHAR(255) NOT NULL REFERENCES regions (id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    leads_to VARCHAR(255) NOT NULL,
+    required_level INT NOT NULL DEFAULT 0,
+    -- Set on portals a generated region added to the regions it connects to, so saving a
+    -- region only replaces its own portals and removing the generated region removes these too
+    added_by VARCHAR(255) REFERENCES regions (id) ON DELETE CASCADE,
+    -- Order within the region
+    position INT NOT NULL,
+    created_at TIMESTAMP DEFAULT NOW()
+);
+
+CREATE INDEX idx_portals_region_id ON portals (region_id);
+CREATE INDEX idx_portals_leads_to ON portals (leads_to);
@@ -0,0 +1,4 @@
+-- 20250611090000_add_portal_required_item.sql
+
+-- Content ID of an item travelers have to carry through the portal
+ALTER TABLE portals ADD COLUMN required_item VARCHAR(255);
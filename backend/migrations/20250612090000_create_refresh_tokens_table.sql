@@ -0,0 +1,13 @@
+-- 20250612090000_create_refresh_tokens_table.sql
+
+-- Refresh tokens handed out at login. Each one is good for a single refresh: using it revokes
+-- it and issues the next, and logging out revokes it early.
+CREATE TABLE refresh_tokens (
+    id UUID PRIMARY KEY,
+    player_id INT NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_refresh_tokens_player_id ON refresh_tokens (player_id);
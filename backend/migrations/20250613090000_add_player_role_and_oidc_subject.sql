@@ -0,0 +1,9 @@
+-- 20250613090000_add_player_role_and_oidc_subject.sql
+
+-- `role` is what local logins are granted; tokens from the identity provider bring their own
+-- roles. Players who sign in through it are linked by the token's subject and may have no
+-- password at all.
+ALTER TABLE players
+    ADD COLUMN role VARCHAR(50) NOT NULL DEFAULT 'player',
+    ADD COLUMN oidc_subject VARCHAR(255) UNIQUE,
+    ALTER COLUMN password_hash DROP NOT NULL;
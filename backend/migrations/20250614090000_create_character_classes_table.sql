@@ -0,0 +1,14 @@
+-- 20250614090000_create_character_classes_table.sql
+
+-- Starting artifacts are content IDs from `content/artifacts`, which the database doesn't know
+-- about, so they are checked when a class is saved rather than by a foreign key
+CREATE TABLE character_classes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL UNIQUE,
+    description TEXT NOT NULL DEFAULT '',
+    base_health INT NOT NULL DEFAULT 100,
+    base_mana INT NOT NULL DEFAULT 50,
+    starting_artifacts TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP DEFAULT NOW(),
+    updated_at TIMESTAMP DEFAULT NOW()
+);
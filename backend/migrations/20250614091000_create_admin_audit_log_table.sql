@@ -0,0 +1,17 @@
+-- 20250614091000_create_admin_audit_log_table.sql
+
+-- One row per change made through the admin API. `before` is NULL for creations and `after`
+-- for deletions. Entries outlive the player who made them.
+CREATE TABLE admin_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    player_id INT REFERENCES players(id) ON DELETE SET NULL,
+    action VARCHAR(50) NOT NULL,
+    entity VARCHAR(50) NOT NULL,
+    entity_id VARCHAR(255) NOT NULL,
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX admin_audit_log_entity_idx ON admin_audit_log (entity, entity_id);
+CREATE INDEX admin_audit_log_created_at_idx ON admin_audit_log (created_at DESC);
@@ -0,0 +1,8 @@
+-- 20250615090000_add_refresh_token_revoked_reason.sql
+
+-- Why a refresh token stopped working: used for a refresh ('rotated'), given back at logout
+-- ('logout') or revoked with the rest of the player's tokens after a rotated one came back
+-- ('reuse'). Only a rotated token coming back again means it was copied.
+ALTER TABLE refresh_tokens ADD COLUMN revoked_reason VARCHAR(20);
+
+UPDATE refresh_tokens SET revoked_reason = 'rotated' WHERE revoked_at IS NOT NULL;
@@ -0,0 +1,15 @@
+-- 20250616090000_create_region_presence_table.sql
+
+-- Who each backend replica has connected in which region, rewritten by the replica on every
+-- heartbeat. Replicas that start later read it to catch up, and rows a crashed replica stopped
+-- refreshing are ignored and then cleared.
+CREATE TABLE region_presence (
+    replica_id UUID NOT NULL,
+    player_id INT NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    username VARCHAR(255) NOT NULL,
+    region_id VARCHAR(255) NOT NULL,
+    seen_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (replica_id, player_id)
+);
+
+CREATE INDEX idx_region_presence_seen_at ON region_presence (seen_at);
@@ -0,0 +1,7 @@
+-- 20250617090000_add_player_room.sql
+
+-- The room a player stands in within their region's layout. NULL means the layout's start,
+-- and is what regions without rooms keep.
+ALTER TABLE players
+    ADD COLUMN room_x INT,
+    ADD COLUMN room_y INT;
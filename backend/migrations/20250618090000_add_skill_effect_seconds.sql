@@ -0,0 +1,6 @@
+-- 20250618090000_add_skill_effect_seconds.sql
+
+-- How long a skill's status effect lasts in real time. Set instead of effect_duration for
+-- effects that outlive the encounter they were cast in.
+ALTER TABLE skills
+    ADD COLUMN effect_seconds INT;
@@ -0,0 +1,8 @@
+-- 20250618100000_create_removed_regions_table.sql
+
+-- Content regions an admin deleted. Content syncs skip their files, just as they skip the
+-- regions admins changed, which are stored with origin 'admin'.
+CREATE TABLE removed_regions (
+    id VARCHAR(255) PRIMARY KEY,
+    removed_at TIMESTAMP DEFAULT NOW()
+);
@@ -0,0 +1,340 @@
+use axum::extract::{Extension, Json, Path, Query};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{middleware, Router};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::auth::{require_role, CurrentPlayer};
+use crate::api::content::{get_reload_history, reload_content};
+use crate::db::audit::{list_audit_entries, AuditFilter};
+use crate::db::character_classes::{get_character_class, list_character_classes};
+use crate::db::skills::{get_skill, list_skills};
+use crate::db::DbPool;
+use crate::engine::admin::{self, AdminError, NewRegion};
+use crate::engine::content::SharedContent;
+use crate::models::{Artifact, CharacterClass, ItemDefinition, Portal, Region, Role, Skill};
+
+/// Most audit entries returned at once
+const MAX_AUDIT_ENTRIES: i64 = 200;
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            AdminError::NotFound(_) => StatusCode::NOT_FOUND,
+            AdminError::Conflict(_) => StatusCode::CONFLICT,
+            AdminError::Invalid(problems) => {
+                return (StatusCode::UNPROCESSABLE_ENTITY, Json(Problems { problems })).into_response();
+            }
+            AdminError::Content(_) | AdminError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Why a write was turned away, one line per problem
+#[derive(Serialize)]
+struct Problems {
+    problems: Vec<String>,
+}
+
+/// Everything under `/admin`. Only admins get in; the `authenticate` layer has to wrap it.
+pub fn router() -> Router {
+    Router::new()
+        .route("/items", get(list_items).post(create_item))  // Item definitions in content/items
+        .route("/items/:item_id", get(get_item).put(update_item).delete(delete_item))
+        .route("/artifacts", get(list_artifacts).post(create_artifact))  // Artifacts in content/artifacts
+        .route("/artifacts/:artifact_id", get(get_artifact).put(update_artifact).delete(delete_artifact))
+        .route("/regions", get(list_regions).post(create_region))  // Every region on the map
+        .route("/regions/:region_id", get(get_region).put(update_region).delete(delete_region))
+        .route("/regions/:region_id/portals", post(create_portal))  // Portals out of a region
+        .route("/regions/:region_id/portals/:portal_id", put(update_portal).delete(delete_portal))
+        .route("/skills", get(get_skills).post(create_skill))  // Skills players can learn
+        .route("/skills/:skill_id", get(get_one_skill).put(update_skill).delete(delete_skill))
+        .route("/classes", get(get_classes).post(create_class))  // Character classes
+        .route("/classes/:class_id", get(get_class).put(update_class).delete(delete_class))
+        .route("/audit", get(get_audit_log))  // Changes made through this API, newest first
+        .route("/content/reloads", get(get_reload_history))  // Content reloads, newest first
+        .route("/content/reload", post(reload_content))  // Reload content now
+        .route_layer(middleware::from_fn_with_state(Role::Admin, require_role))
+}
+
+fn created<T: Serialize>(result: Result<T, AdminError>) -> Response {
+    match result {
+        Ok(value) => (StatusCode::CREATED, Json(value)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+fn updated<T: Serialize>(result: Result<T, AdminError>) -> Response {
+    match result {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+fn deleted(result: Result<(), AdminError>) -> Response {
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn list_items(Extension(content): Extension<SharedContent>) -> Response {
+    Json(content.snapshot().items.clone()).into_response()
+}
+
+pub async fn get_item(Extension(content): Extension<SharedContent>, Path(item_id): Path<String>) -> Response {
+    updated(admin::get_item(&content, &item_id))
+}
+
+/// Add an item definition. It is written to `content/items/<id>.toml` and kept only if the
+/// content reloads with it.
+pub async fn create_item(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(item): Json<ItemDefinition>,
+) -> Response {
+    created(admin::create_item(&pool, &content, player_id, item).await)
+}
+
+/// Replace an item definition, keeping the format of the file it came from
+pub async fn update_item(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(item_id): Path<String>,
+    Json(item): Json<ItemDefinition>,
+) -> Response {
+    updated(admin::update_item(&pool, &content, player_id, &item_id, item).await)
+}
+
+pub async fn delete_item(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(item_id): Path<String>,
+) -> Response {
+    deleted(admin::delete_item(&pool, &content, player_id, &item_id).await)
+}
+
+pub async fn list_artifacts(Extension(content): Extension<SharedContent>) -> Response {
+    Json(content.snapshot().artifacts.clone()).into_response()
+}
+
+pub async fn get_artifact(Extension(content): Extension<SharedContent>, Path(artifact_id): Path<String>) -> Response {
+    updated(admin::get_artifact(&content, &artifact_id))
+}
+
+pub async fn create_artifact(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(artifact): Json<Artifact>,
+) -> Response {
+    created(admin::create_artifact(&pool, &content, player_id, artifact).await)
+}
+
+pub async fn update_artifact(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(artifact_id): Path<String>,
+    Json(artifact): Json<Artifact>,
+) -> Response {
+    updated(admin::update_artifact(&pool, &content, player_id, &artifact_id, artifact).await)
+}
+
+pub async fn delete_artifact(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(artifact_id): Path<String>,
+) -> Response {
+    deleted(admin::delete_artifact(&pool, &content, player_id, &artifact_id).await)
+}
+
+/// Every region on the map, content or not, by ID
+pub async fn list_regions(Extension(content): Extension<SharedContent>) -> Response {
+    let map = content.map();
+    let mut regions: Vec<&Region> = map.regions.values().collect();
+    regions.sort_by(|a, b| a.id.cmp(&b.id));
+    Json(regions).into_response()
+}
+
+pub async fn get_region(Extension(content): Extension<SharedContent>, Path(region_id): Path<String>) -> Response {
+    updated(admin::get_region(&content, &region_id))
+}
+
+/// Add a region along with the portals leading into it
+pub async fn create_region(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(new): Json<NewRegion>,
+) -> Response {
+    created(admin::create_region(&pool, &content, player_id, new).await)
+}
+
+pub async fn update_region(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(region_id): Path<String>,
+    Json(region): Json<Region>,
+) -> Response {
+    updated(admin::update_region(&pool, &content, player_id, &region_id, region).await)
+}
+
+pub async fn delete_region(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(region_id): Path<String>,
+) -> Response {
+    deleted(admin::delete_region(&pool, &content, player_id, &region_id).await)
+}
+
+pub async fn create_portal(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(region_id): Path<String>,
+    Json(portal): Json<Portal>,
+) -> Response {
+    created(admin::create_portal(&pool, &content, player_id, &region_id, portal).await)
+}
+
+pub async fn update_portal(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path((region_id, portal_id)): Path<(String, String)>,
+    Json(portal): Json<Portal>,
+) -> Response {
+    updated(admin::update_portal(&pool, &content, player_id, &region_id, &portal_id, portal).await)
+}
+
+pub async fn delete_portal(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path((region_id, portal_id)): Path<(String, String)>,
+) -> Response {
+    deleted(admin::delete_portal(&pool, &content, player_id, &region_id, &portal_id).await)
+}
+
+pub async fn get_skills(Extension(pool): Extension<DbPool>) -> Response {
+    match list_skills(&pool).await {
+        Ok(skills) => Json(skills).into_response(),
+        Err(e) => AdminError::from(e).into_response(),
+    }
+}
+
+pub async fn get_one_skill(Extension(pool): Extension<DbPool>, Path(skill_id): Path<i32>) -> Response {
+    match get_skill(pool.as_ref(), skill_id).await {
+        Ok(Some(skill)) => Json(skill).into_response(),
+        Ok(None) => AdminError::NotFound(format!("skill {}", skill_id)).into_response(),
+        Err(e) => AdminError::from(e).into_response(),
+    }
+}
+
+/// Add a skill; any `id` in the body is ignored
+pub async fn create_skill(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(skill): Json<Skill>,
+) -> Response {
+    created(admin::create_skill(&pool, player_id, skill).await)
+}
+
+pub async fn update_skill(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(skill_id): Path<i32>,
+    Json(skill): Json<Skill>,
+) -> Response {
+    updated(admin::update_skill(&pool, player_id, skill_id, skill).await)
+}
+
+pub async fn delete_skill(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(skill_id): Path<i32>,
+) -> Response {
+    deleted(admin::delete_skill(&pool, player_id, skill_id).await)
+}
+
+pub async fn get_classes(Extension(pool): Extension<DbPool>) -> Response {
+    match list_character_classes(&pool).await {
+        Ok(classes) => Json(classes).into_response(),
+        Err(e) => AdminError::from(e).into_response(),
+    }
+}
+
+pub async fn get_class(Extension(pool): Extension<DbPool>, Path(class_id): Path<Uuid>) -> Response {
+    match get_character_class(pool.as_ref(), class_id).await {
+        Ok(Some(class)) => Json(class).into_response(),
+        Ok(None) => AdminError::NotFound(format!("character class {}", class_id)).into_response(),
+        Err(e) => AdminError::from(e).into_response(),
+    }
+}
+
+/// Add a character class; any `id` in the body is ignored
+pub async fn create_class(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(class): Json<CharacterClass>,
+) -> Response {
+    created(admin::create_character_class(&pool, &content, player_id, class).await)
+}
+
+pub async fn update_class(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(class_id): Path<Uuid>,
+    Json(class): Json<CharacterClass>,
+) -> Response {
+    updated(admin::update_character_class(&pool, &content, player_id, class_id, class).await)
+}
+
+pub async fn delete_class(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(class_id): Path<Uuid>,
+) -> Response {
+    deleted(admin::delete_character_class(&pool, player_id, class_id).await)
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub entity: Option<String>,
+    pub entity_id: Option<String>,
+    pub player_id: Option<i32>,
+    /// Only entries older than this entry ID
+    pub before: Option<i64>,
+    #[serde(default = "default_audit_limit")]
+    pub limit: i64,
+}
+
+fn default_audit_limit() -> i64 {
+    50
+}
+
+/// Who changed what, newest first
+pub async fn get_audit_log(Extension(pool): Extension<DbPool>, Query(query): Query<AuditQuery>) -> Response {
+    let filter = AuditFilter {
+        entity: query.entity,
+        entity_id: query.entity_id,
+        player_id: query.player_id,
+        before_id: query.before,
+    };
+    match list_audit_entries(&pool, &filter, query.limit.clamp(1, MAX_AUDIT_ENTRIES)).await {
+        Ok(entries) => Json(entries).into_response(),
+        Err(e) => AdminError::from(e).into_response(),
+    }
+}
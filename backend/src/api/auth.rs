@@ -1,13 +1,133 @@
 // auth.rs
 use axum::{
-    extract::{Form, Json},
-    http::StatusCode,
-    response::IntoResponse,
+    async_trait,
+    extract::{Extension, FromRequestParts, Json, Request, State},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
 use std::sync::Arc;
 
+use crate::db::DbPool;
+use crate::engine::auth::{self, AuthError, Principal, SharedAuth};
+use crate::models::Role;
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        error_response(&self)
+    }
+}
+
+fn error_response(e: &AuthError) -> Response {
+    let status = match e {
+        AuthError::InvalidUsername | AuthError::InvalidPassword | AuthError::InvalidEmail => StatusCode::BAD_REQUEST,
+        AuthError::UsernameTaken | AuthError::EmailTaken => StatusCode::CONFLICT,
+        AuthError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+        AuthError::MissingToken | AuthError::InvalidToken | AuthError::TokenExpired => {
+            return (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], e.to_string()).into_response();
+        }
+        AuthError::MissingRole(_) => StatusCode::FORBIDDEN,
+        AuthError::KeysUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        AuthError::Hashing(_) | AuthError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string()).into_response()
+}
+
+/// Why a request has no `Principal`: it carried no bearer token, or `authenticate` turned the
+/// one it carried away. Only routes that need to know who is asking answer with it.
+#[derive(Debug, Clone)]
+pub struct Unauthenticated(Arc<AuthError>);
+
+impl Unauthenticated {
+    pub fn error(&self) -> &AuthError {
+        &self.0
+    }
+}
+
+impl IntoResponse for Unauthenticated {
+    fn into_response(self) -> Response {
+        error_response(&self.0)
+    }
+}
+
+/// Resolve the bearer token of every request that carries one, ours or the identity
+/// provider's, and pass the `Principal` on to the handler. A token that doesn't check out only
+/// fails routes that ask for the `Principal`, so public routes such as `/auth/refresh` still
+/// work for clients that send a stale token along.
+pub async fn authenticate(
+    Extension(pool): Extension<DbPool>,
+    Extension(auth): Extension<SharedAuth>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(value) = request.headers().get(header::AUTHORIZATION) else {
+        return next.run(request).await;
+    };
+    let token = value
+        .to_str()
+        .ok()
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, token)| token.trim().to_string());
+    let result = match token {
+        Some(token) => auth.authenticate(&pool, &token).await,
+        None => Err(AuthError::InvalidToken),
+    };
+    match result {
+        Ok(principal) => {
+            request.extensions_mut().insert(principal);
+        }
+        Err(e) => {
+            request.extensions_mut().insert(Unauthenticated(Arc::new(e)));
+        }
+    }
+    next.run(request).await
+}
+
+/// Turn away requests from anyone without `role`. Layer it with
+/// `middleware::from_fn_with_state(role, require_role)` inside `authenticate`.
+pub async fn require_role(State(role): State<Role>, principal: Principal, request: Request, next: Next) -> Response {
+    if !principal.has_role(role) {
+        return AuthError::MissingRole(role).into_response();
+    }
+    next.run(request).await
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Principal {
+    type Rejection = Unauthenticated;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(principal) = parts.extensions.get::<Principal>() {
+            return Ok(principal.clone());
+        }
+        let rejected = parts.extensions.get::<Unauthenticated>().cloned();
+        Err(rejected.unwrap_or_else(|| Unauthenticated(Arc::new(AuthError::MissingToken))))
+    }
+}
+
+/// The player the request's bearer token belongs to. Player routes take this instead of a
+/// player ID from the path.
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentPlayer(pub i32);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CurrentPlayer {
+    type Rejection = Unauthenticated;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Principal::from_request_parts(parts, state).await.map(|principal| CurrentPlayer(principal.player_id))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegisterRequest {
+    pub username: String,
+    pub password: String,
+    pub email: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct LoginRequest {
     pub username: String,
@@ -15,29 +135,54 @@ pub struct LoginRequest {
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct AuthResponse {
-    pub token: String,
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Create a player and log them in
+pub async fn register(
+    Extension(pool): Extension<DbPool>,
+    Extension(auth): Extension<SharedAuth>,
+    Json(payload): Json<RegisterRequest>,
+) -> Response {
+    match auth::register(&pool, &auth, &payload.username, payload.email.as_deref(), &payload.password).await {
+        Ok(tokens) => (StatusCode::CREATED, Json(tokens)).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
+/// Trade a username and password for an access token and a refresh token
 pub async fn login(
-    pool: Arc<PgPool>,
-    Form(payload): Form<LoginRequest>,
-) -> impl IntoResponse {
-    // Authenticate and generate JWT (or session)
-    // This is just a placeholder for actual auth logic
-    let user = sqlx::query!("SELECT * FROM players WHERE username = $1", payload.username)
-        .fetch_optional(&*pool)
-        .await
-        .unwrap();
-
-    match user {
-        Some(_user) => {
-            // Generate a token (mock token here)
-            let token = "mock-token";
-            Json(AuthResponse {
-                token: token.to_string(),
-            })
-        }
-        None => StatusCode::UNAUTHORIZED.into_response(),
+    Extension(pool): Extension<DbPool>,
+    Extension(auth): Extension<SharedAuth>,
+    Json(payload): Json<LoginRequest>,
+) -> Response {
+    match auth::login(&pool, &auth, &payload.username, &payload.password).await {
+        Ok(tokens) => Json(tokens).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Trade a refresh token for a new pair; the old refresh token stops working
+pub async fn refresh(
+    Extension(pool): Extension<DbPool>,
+    Extension(auth): Extension<SharedAuth>,
+    Json(payload): Json<RefreshRequest>,
+) -> Response {
+    match auth::refresh(&pool, &auth, &payload.refresh_token).await {
+        Ok(tokens) => Json(tokens).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Revoke a refresh token
+pub async fn logout(
+    Extension(pool): Extension<DbPool>,
+    Extension(auth): Extension<SharedAuth>,
+    Json(payload): Json<RefreshRequest>,
+) -> Response {
+    match auth::logout(&pool, &auth, &payload.refresh_token).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => e.into_response(),
     }
 }
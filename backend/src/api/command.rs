@@ -0,0 +1,51 @@
+use axum::{Json, extract::Extension};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::api::auth::CurrentPlayer;
+use crate::db::DbPool;
+use crate::engine::combat::CombatSessions;
+use crate::engine::content::SharedContent;
+use crate::engine::game_sessions::SharedGameSessions;
+use crate::engine::interpreter::{execute, CommandContext, CommandError};
+use crate::engine::presence::SharedPresence;
+
+impl IntoResponse for CommandError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            CommandError::PlayerNotFound(_) => StatusCode::NOT_FOUND,
+            CommandError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CommandRequest {
+    pub input: String,
+}
+
+/// Run a line typed into the terminal. Commands the game turns down still answer 200, with
+/// `ok: false` and the reason in `text`. Players connected over `/ws` in the same region hear
+/// about it like they would from another connected player.
+pub async fn run_command(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    Extension(sessions): Extension<CombatSessions>,
+    Extension(game_sessions): Extension<SharedGameSessions>,
+    Extension(presence): Extension<SharedPresence>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(request): Json<CommandRequest>,
+) -> Response {
+    let ctx = CommandContext { pool: &pool, content: &content, sessions: &sessions, presence: &presence };
+    match execute(&ctx, player_id, &request.input).await {
+        Ok(response) => {
+            if let Err(e) = game_sessions.announce(&pool, player_id, &response).await {
+                eprintln!("⚠️ Failed to announce a command to the region: {}", e);
+            }
+            Json(response).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
@@ -0,0 +1,24 @@
+use axum::{Json, extract::Extension};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use crate::db::DbPool;
+use crate::engine::content::{ReloadOutcome, ReloadTrigger, SharedContent};
+
+/// Content reloads since the server started, newest first
+pub async fn get_reload_history(Extension(content): Extension<SharedContent>) -> impl IntoResponse {
+    Json(content.history())
+}
+
+/// Reload content now instead of waiting for the watcher. A rejected reload answers 422 with
+/// the problems found; the running world is kept.
+pub async fn reload_content(
+    Extension(content): Extension<SharedContent>,
+    Extension(pool): Extension<DbPool>,
+) -> Response {
+    let record = content.reload(pool.as_ref(), ReloadTrigger::Manual).await;
+    let status = match record.outcome {
+        ReloadOutcome::Applied { .. } => StatusCode::OK,
+        ReloadOutcome::Rejected { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+    };
+    (status, Json(record)).into_response()
+}
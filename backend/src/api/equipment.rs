@@ -0,0 +1,86 @@
+use axum::{Json, extract::Extension};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::api::auth::CurrentPlayer;
+use crate::db::DbPool;
+use crate::engine::equipment::{equip_item, get_equipment, lift_curse, unequip_item, EquipError};
+use crate::engine::inventory_logic::InventoryError;
+use crate::models::EquipmentSlot;
+
+#[derive(Deserialize)]
+pub struct EquipRequest {
+    pub item_id: i32,
+    pub slot: Option<EquipmentSlot>,
+}
+
+#[derive(Deserialize)]
+pub struct SlotRequest {
+    pub slot: EquipmentSlot,
+}
+
+/// A game master lifting the curse on another player's equipped item
+#[derive(Deserialize)]
+pub struct LiftCurseRequest {
+    pub player_id: i32,
+    pub slot: EquipmentSlot,
+}
+
+impl IntoResponse for EquipError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            EquipError::NotInInventory(_) | EquipError::SlotEmpty(_) => StatusCode::NOT_FOUND,
+            EquipError::NotEquippable { .. } | EquipError::WrongSlot { .. } | EquipError::ItemBroken(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            EquipError::Cursed(_) | EquipError::NotCursed(_) => StatusCode::CONFLICT,
+            EquipError::Inventory(InventoryError::PlayerNotFound(_)) => StatusCode::NOT_FOUND,
+            EquipError::Inventory(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub async fn get_player_equipment(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+) -> Response {
+    match get_equipment(pool.as_ref(), player_id).await {
+        Ok(equipment) => Json(equipment).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+pub async fn equip(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(request): Json<EquipRequest>,
+) -> Response {
+    match equip_item(&pool, player_id, request.item_id, request.slot).await {
+        Ok(equipment) => Json(equipment).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn unequip(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(request): Json<SlotRequest>,
+) -> Response {
+    match unequip_item(&pool, player_id, request.slot).await {
+        Ok(equipment) => Json(equipment).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn remove_curse(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(game_master_id): CurrentPlayer,
+    Json(request): Json<LiftCurseRequest>,
+) -> Response {
+    match lift_curse(&pool, game_master_id, request.player_id, request.slot).await {
+        Ok(equipment) => Json(equipment).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
@@ -1,19 +1,73 @@
-use axum::{Json, extract::Path};
-use crate::models::{Player, Item, Quest};
-use crate::engine::game_logic::{handle_combat, complete_quest};
+use axum::{Json, extract::{Extension, Path}};
 use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use uuid::Uuid;
 
-pub async fn start_combat(Path(player_id): Path<i32>, monster_health: i32) -> impl axum::response::IntoResponse {
-    let mut player = get_player_from_db(player_id).await;
-    let remaining_health = handle_combat(&mut player, monster_health);
-    update_player_in_db(&player).await;
-    Json(remaining_health)
+use crate::api::auth::CurrentPlayer;
+use crate::db::DbPool;
+use crate::db::skills::get_player_skill;
+use crate::engine::combat::{CombatAction, CombatSessions};
+use crate::engine::encounters::{advance_encounter, start_encounter, EncounterError};
+use crate::engine::skills::SkillError;
+
+impl IntoResponse for EncounterError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            EncounterError::PlayerNotFound(_) | EncounterError::EncounterNotFound(_) => StatusCode::NOT_FOUND,
+            EncounterError::PlayerDefeated | EncounterError::Action(_) => StatusCode::BAD_REQUEST,
+            EncounterError::AlreadyFighting(_) => StatusCode::CONFLICT,
+            EncounterError::NotInEncounter => StatusCode::FORBIDDEN,
+            EncounterError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Start a new encounter against a wild monster and play the first round
+pub async fn start_combat(
+    Extension(pool): Extension<DbPool>,
+    Extension(sessions): Extension<CombatSessions>,
+    CurrentPlayer(player_id): CurrentPlayer,
+) -> impl IntoResponse {
+    match start_encounter(&pool, &sessions, player_id).await {
+        Ok(encounter) => Json(encounter).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
-pub async fn complete_quest_route(Path(player_id): Path<i32>, quest_id: i32) -> impl axum::response::IntoResponse {
-    let mut player = get_player_from_db(player_id).await;
-    let mut quest = get_quest_from_db(quest_id).await;
-    complete_quest(&mut player, &mut quest);
-    update_player_in_db(&player).await;
-    Json(quest)
+/// The player's chosen action for the next round. Omitting it performs a basic attack.
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ActionRequest {
+    Attack { target: Option<String> },
+    UseSkill { skill_id: i32, target: Option<String> },
+}
+
+/// Advance an ongoing encounter by one round
+pub async fn advance_combat(
+    Extension(pool): Extension<DbPool>,
+    Extension(sessions): Extension<CombatSessions>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(encounter_id): Path<Uuid>,
+    request: Option<Json<ActionRequest>>,
+) -> impl IntoResponse {
+    let action = match request.map(|Json(request)| request) {
+        Some(ActionRequest::UseSkill { skill_id, target }) => {
+            match get_player_skill(&pool, player_id, skill_id).await {
+                Ok(Some(skill)) => Some(CombatAction::UseSkill { skill, target }),
+                Ok(None) => {
+                    return (StatusCode::BAD_REQUEST, SkillError::UnknownSkill.to_string()).into_response()
+                }
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }
+        Some(ActionRequest::Attack { target }) => Some(CombatAction::Attack { target }),
+        None => None,
+    };
+
+    match advance_encounter(&pool, &sessions, player_id, encounter_id, action).await {
+        Ok(encounter) => Json(encounter).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
@@ -1,17 +1,109 @@
-use axum::{Json, extract::Path};
-use crate::engine::inventory_logic::{add_item_to_inventory, remove_item_from_inventory};
-use crate::models::{Player, Item};
+use axum::{Json, extract::Extension};
+use crate::api::auth::CurrentPlayer;
+use crate::db::DbPool;
+use crate::db::items::get_item;
+use crate::engine::inventory_logic::{
+    add_item_to_inventory, get_inventory_for_player, remove_item_from_inventory, use_item_from_inventory,
+    InventoryError,
+};
+use crate::models::ItemUseError;
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct InventoryChange {
+    pub item_id: i32,
+    pub quantity: i32,
+}
+
+impl IntoResponse for InventoryError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            InventoryError::InvalidQuantity(_) => StatusCode::BAD_REQUEST,
+            InventoryError::NotEnoughQuantity { .. } | InventoryError::CursedItemEquipped(_) => StatusCode::CONFLICT,
+            InventoryError::PlayerNotFound(_) => StatusCode::NOT_FOUND,
+            InventoryError::Unusable(ItemUseError::UnknownSkill(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            InventoryError::Unusable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            InventoryError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub async fn get_inventory(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+) -> impl IntoResponse {
+    match get_inventory_for_player(pool.as_ref(), player_id).await {
+        Ok(inventory) => Json(inventory).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Items for a player, handed out by a game master
+#[derive(Deserialize)]
+pub struct GrantRequest {
+    pub player_id: i32,
+    pub item_id: i32,
+    pub quantity: i32,
+}
+
+pub async fn add_item(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(game_master_id): CurrentPlayer,
+    Json(grant): Json<GrantRequest>,
+) -> Response {
+    let item = match get_item(&pool, grant.item_id).await {
+        Ok(Some(item)) => item,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
 
-pub async fn add_item(Path(player_id): Path<i32>, item: Item, quantity: i32) -> impl IntoResponse {
     // Add the item to the player's inventory
-    add_item_to_inventory(&pool, player_id, item, quantity).await;
-    StatusCode::OK
+    match add_item_to_inventory(&pool, game_master_id, grant.player_id, &item, grant.quantity).await {
+        Ok(rows) => Json(rows).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
-pub async fn remove_item(Path(player_id): Path<i32>, item: Item, quantity: i32) -> impl IntoResponse {
+pub async fn remove_item(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(change): Json<InventoryChange>,
+) -> Response {
+    let item = match get_item(&pool, change.item_id).await {
+        Ok(Some(item)) => item,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
     // Remove the item from the player's inventory
-    remove_item_from_inventory(&pool, player_id, item, quantity).await;
-    StatusCode::OK
+    match remove_item_from_inventory(&pool, player_id, &item, change.quantity).await {
+        Ok(rows) => Json(rows).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UseRequest {
+    pub item_id: i32,
+}
+
+/// Use an item from the player's inventory, running its effects
+pub async fn use_item(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(request): Json<UseRequest>,
+) -> Response {
+    let item = match get_item(&pool, request.item_id).await {
+        Ok(Some(item)) => item,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    match use_item_from_inventory(&pool, player_id, &item).await {
+        Ok(outcome) => Json(outcome).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
@@ -0,0 +1,123 @@
+use axum::{Json, extract::{Extension, Path, Query}};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+use crate::api::auth::CurrentPlayer;
+use crate::engine::content::SharedContent;
+use crate::engine::map_graph::{RouteError, NEXUS_ID};
+use crate::engine::presence::SharedPresence;
+use crate::engine::rooms::{region_layout, RoomError};
+use crate::models::{Entrance, Position};
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            RouteError::UnknownRegion(_) => StatusCode::NOT_FOUND,
+            RouteError::Unreachable { .. } | RouteError::LevelTooLow { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+impl IntoResponse for RoomError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            RoomError::PlayerNotFound(_) | RoomError::UnknownRegion(_) | RoomError::NoLayout(_) => StatusCode::NOT_FOUND,
+            RoomError::Move(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            RoomError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RouteQuery {
+    #[serde(default = "nexus")]
+    pub from: String,
+    pub to: String,
+    pub level: i32,
+}
+
+#[derive(Deserialize)]
+pub struct ReachableQuery {
+    #[serde(default = "nexus")]
+    pub from: String,
+    pub level: i32,
+}
+
+fn nexus() -> String {
+    NEXUS_ID.to_string()
+}
+
+#[derive(Serialize)]
+pub struct ReachableRegions {
+    pub from: String,
+    pub level: i32,
+    pub regions: Vec<String>,
+}
+
+/// The fewest-hop route between two regions for a player of the given level
+pub async fn get_route(Extension(content): Extension<SharedContent>, Query(query): Query<RouteQuery>) -> Response {
+    let map = content.map();
+    match map.find_path(&query.from, &query.to, query.level) {
+        Ok(route) => Json(route).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Regions a player of the given level can get to, from the nexus unless `from` says otherwise
+pub async fn get_reachable(
+    Extension(content): Extension<SharedContent>,
+    Query(query): Query<ReachableQuery>,
+) -> Response {
+    let map = content.map();
+    if map.get_region(&query.from).is_none() {
+        return RouteError::UnknownRegion(query.from).into_response();
+    }
+    let regions = map.reachable_from(&query.from, query.level);
+    Json(ReachableRegions { from: query.from, level: query.level, regions }).into_response()
+}
+
+/// Regions no portal leads into
+pub async fn get_orphans(Extension(content): Extension<SharedContent>) -> impl IntoResponse {
+    let map = content.map();
+    Json(map.orphan_regions())
+}
+
+/// Players connected in a region, by name. Only signed-in players get to see who is online.
+pub async fn get_region_players(
+    _player: CurrentPlayer,
+    Extension(content): Extension<SharedContent>,
+    Extension(presence): Extension<SharedPresence>,
+    Path(region_id): Path<String>,
+) -> Response {
+    if content.map().get_region(&region_id).is_none() {
+        return RouteError::UnknownRegion(region_id).into_response();
+    }
+    Json(presence.who(&region_id)).into_response()
+}
+
+#[derive(Serialize)]
+pub struct LayoutView {
+    pub region_id: String,
+    pub start: Position,
+    pub entrances: Vec<Entrance>,
+    /// The layout with entrances marked `*`
+    pub map: Vec<String>,
+}
+
+/// The rooms inside a region
+pub async fn get_layout(Extension(content): Extension<SharedContent>, Path(region_id): Path<String>) -> Response {
+    let map = content.map();
+    match region_layout(&map, &region_id) {
+        Ok((region, layout)) => Json(LayoutView {
+            region_id: region.id.clone(),
+            start: layout.start,
+            entrances: layout.entrances.clone(),
+            map: layout.render(None),
+        })
+        .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
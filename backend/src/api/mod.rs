@@ -1,4 +1,12 @@
-mod api;
-
+pub mod admin;
 pub mod player;
 pub mod auth;
+pub mod game;
+pub mod inventory;
+pub mod equipment;
+pub mod skills;
+pub mod map;
+pub mod content;
+pub mod travel;
+pub mod command;
+pub mod ws;
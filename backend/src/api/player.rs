@@ -1,50 +1,82 @@
 // player.rs
 use axum::{
-    extract::{Path, Query},
+    extract::{Extension, Query},
     http::StatusCode,
-    response::{IntoResponse, Json},
+    response::{IntoResponse, Json, Response},
 };
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
-use std::sync::Arc;
+use sqlx::FromRow;
 
-#[derive(Serialize, Deserialize)]
+use crate::api::auth::CurrentPlayer;
+use crate::db::DbPool;
+use crate::engine::equipment::get_equipment;
+use crate::models::Equipment;
+
+#[derive(Serialize, Deserialize, FromRow)]
 pub struct Player {
     pub id: i32,
     pub username: String,
-    pub email: String,
+    pub email: Option<String>,
+}
+
+/// A player's profile together with what they have equipped
+#[derive(Serialize)]
+pub struct PlayerView {
+    #[serde(flatten)]
+    pub profile: Player,
+    pub equipment: Equipment,
+    pub attack_bonus: i32,
+    pub defense_bonus: i32,
 }
 
 pub async fn get_player(
-    pool: Arc<PgPool>,
-    Path(player_id): Path<i32>,
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
 ) -> impl IntoResponse {
     // Query player from the database
-    let player = sqlx::query_as!(
-        Player,
-        "SELECT id, username, email FROM players WHERE id = $1",
-        player_id
-    )
-    .fetch_optional(&*pool)
-    .await
-    .unwrap();
+    let player = sqlx::query_as::<_, Player>("SELECT id, username, email FROM players WHERE id = $1")
+        .bind(player_id)
+        .fetch_optional(&*pool)
+        .await;
 
-    match player {
-        Some(p) => Json(p),
-        None => StatusCode::NOT_FOUND.into_response(),
-    }
+    let profile = match player {
+        Ok(Some(profile)) => profile,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let equipment = match get_equipment(pool.as_ref(), player_id).await {
+        Ok(equipment) => equipment,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    Json(PlayerView {
+        attack_bonus: equipment.attack_bonus(),
+        defense_bonus: equipment.defense_bonus(),
+        profile,
+        equipment,
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct PlayersQuery {
+    /// Only players whose username contains this
+    pub username: Option<String>,
 }
 
-pub async fn get_players(pool: Arc<PgPool>, Query(params): Query<HashMap<String, String>>) -> impl IntoResponse {
-    // Fetch multiple players from database with optional filters
-    let players = sqlx::query_as!(
-        Player,
-        "SELECT id, username, email FROM players WHERE username LIKE $1",
-        params.get("username").unwrap_or(&"".to_string())
+/// Every player, or those matching `username`. Mounted behind the admin role, since it lists
+/// email addresses.
+pub async fn get_players(Extension(pool): Extension<DbPool>, Query(query): Query<PlayersQuery>) -> Response {
+    let pattern = format!("%{}%", query.username.unwrap_or_default());
+    let players = sqlx::query_as::<_, Player>(
+        "SELECT id, username, email FROM players WHERE username LIKE $1 ORDER BY username",
     )
+    .bind(pattern)
     .fetch_all(&*pool)
-    .await
-    .unwrap();
+    .await;
 
-    Json(players)
+    match players {
+        Ok(players) => Json(players).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
 }
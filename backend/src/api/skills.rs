@@ -0,0 +1,31 @@
+use axum::{Json, extract::{Extension, Path}};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+
+use crate::api::auth::CurrentPlayer;
+use crate::db::DbPool;
+use crate::db::skills::{get_player_skills, learn_skill};
+
+/// List the skills a player has learned
+pub async fn list_player_skills(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+) -> impl IntoResponse {
+    match get_player_skills(&pool, player_id).await {
+        Ok(skills) => Json(skills).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Teach a player a skill from the skills table
+pub async fn learn_player_skill(
+    Extension(pool): Extension<DbPool>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Path(skill_id): Path<i32>,
+) -> impl IntoResponse {
+    match learn_skill(pool.as_ref(), player_id, skill_id).await {
+        Ok(()) => StatusCode::OK,
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,99 @@
+use axum::{Json, extract::Extension};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::api::auth::CurrentPlayer;
+use crate::db::players::get_player;
+use crate::db::DbPool;
+use crate::engine::content::SharedContent;
+use crate::engine::presence::SharedPresence;
+use crate::engine::rooms::{player_room, walk};
+use crate::engine::travel::{get_location, travel, TravelError};
+use crate::models::Direction;
+
+impl IntoResponse for TravelError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            TravelError::PlayerNotFound(_) | TravelError::NoSuchPortal { .. } => StatusCode::NOT_FOUND,
+            TravelError::Lost(_) => StatusCode::CONFLICT,
+            TravelError::Blocked(_) => StatusCode::FORBIDDEN,
+            TravelError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TravelRequest {
+    pub portal_id: String,
+}
+
+/// The player's region and its portals, each with the reason it's closed to them if it is
+pub async fn get_player_location(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+) -> Response {
+    match get_location(&pool, &content.map(), player_id).await {
+        Ok(location) => Json(location).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Take a portal out of the player's region. Everyone in both regions sees the player go.
+pub async fn travel_through_portal(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    Extension(presence): Extension<SharedPresence>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(request): Json<TravelRequest>,
+) -> Response {
+    let arrival = match travel(&pool, &content.map(), player_id, &request.portal_id).await {
+        Ok(arrival) => arrival,
+        Err(e) => return e.into_response(),
+    };
+
+    // The player has already moved, so a failed announcement only leaves the regions stale
+    // until their next `look`
+    match get_player(pool.as_ref(), player_id).await {
+        Ok(Some(player)) => presence.travel(player_id, &player.username, &arrival.from, &arrival.location.region_id),
+        Ok(None) => {}
+        Err(e) => eprintln!("⚠️ Failed to announce travel to the region: {}", e),
+    }
+    Json(arrival).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct MoveRequest {
+    pub direction: String,
+}
+
+/// Exits and portals of the room the player stands in
+pub async fn get_room(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+) -> Response {
+    match player_room(&pool, &content.map(), player_id).await {
+        Ok(room) => Json(room).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Walk north, south, east or west from the player's room
+pub async fn move_room(
+    Extension(pool): Extension<DbPool>,
+    Extension(content): Extension<SharedContent>,
+    CurrentPlayer(player_id): CurrentPlayer,
+    Json(request): Json<MoveRequest>,
+) -> Response {
+    let direction = match Direction::try_from(request.direction) {
+        Ok(direction) => direction,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match walk(&pool, &content.map(), player_id, direction).await {
+        Ok(room) => Json(room).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
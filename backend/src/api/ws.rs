@@ -0,0 +1,236 @@
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Extension, Query};
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::db::players::{get_player, get_player_region};
+use crate::db::DbPool;
+use crate::api::auth::Unauthenticated;
+use crate::engine::auth::{AuthError, Principal, SharedAuth};
+use crate::engine::combat::CombatSessions;
+use crate::engine::content::SharedContent;
+use crate::engine::game_sessions::{Attachment, GameEvent, SharedGameSessions};
+use crate::engine::interpreter::{execute, CommandContext, CommandError};
+use crate::engine::presence::SharedPresence;
+
+/// How often the server pings an idle connection
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+/// A connection nothing has come in on for this long is closed; the session can still be resumed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// A client that takes longer than this to accept one message is dropped
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest message a client may send
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+#[derive(Deserialize)]
+pub struct SocketParams {
+    /// Access token, for clients that can't set the `Authorization` header on a WebSocket,
+    /// such as browsers
+    pub token: Option<String>,
+    /// Resume token from an earlier connection's `welcome`
+    pub resume: Option<String>,
+    /// Sequence number of the last event the client got; later ones are sent again
+    pub last_seq: Option<u64>,
+}
+
+/// What a client sends besides plain command lines
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Command { input: String },
+    /// For clients that can't send WebSocket pings
+    Ping,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    /// The first message on every connection
+    Welcome {
+        session_id: Uuid,
+        resume_token: &'a str,
+        /// False when a new session was started, including when the one asked for had expired
+        resumed: bool,
+        /// Sequence number of the newest event so far
+        last_seq: u64,
+    },
+    Event { seq: u64, event: &'a GameEvent },
+    /// Events that were dropped before the client got to them
+    Lagged { missed: u64 },
+    Pong,
+    Error { message: String },
+}
+
+/// Upgrade to a game session. The client sends command lines, either as plain text or as
+/// `{"type": "command", "input": ...}`, and receives their responses along with everything else
+/// happening around the player as numbered events. After a disconnect, connecting again with
+/// `resume` and `last_seq` picks the session up where it left off.
+#[allow(clippy::too_many_arguments)]
+pub async fn game_socket(
+    ws: WebSocketUpgrade,
+    Extension(pool): Extension<DbPool>,
+    Extension(auth): Extension<SharedAuth>,
+    Extension(content): Extension<SharedContent>,
+    Extension(combat): Extension<CombatSessions>,
+    Extension(sessions): Extension<SharedGameSessions>,
+    Extension(presence): Extension<SharedPresence>,
+    principal: Result<Principal, Unauthenticated>,
+    Query(params): Query<SocketParams>,
+) -> Response {
+    let principal = match (principal, &params.token) {
+        (Ok(principal), _) => principal,
+        (Err(rejected), Some(token)) if matches!(rejected.error(), AuthError::MissingToken) => {
+            match auth.authenticate(&pool, token).await {
+                Ok(principal) => principal,
+                Err(e) => return e.into_response(),
+            }
+        }
+        (Err(rejected), _) => return rejected.into_response(),
+    };
+    let player_id = principal.player_id;
+    let player = match get_player(pool.as_ref(), player_id).await {
+        Ok(Some(player)) => player,
+        Ok(None) => return CommandError::PlayerNotFound(player_id).into_response(),
+        Err(e) => return AuthError::Database(e).into_response(),
+    };
+    let region = match get_player_region(pool.as_ref(), player_id).await {
+        Ok(region) => region,
+        Err(e) => return AuthError::Database(e).into_response(),
+    };
+
+    ws.max_message_size(MAX_MESSAGE_SIZE).on_upgrade(move |socket| async move {
+        let resumed = params.resume.as_deref().and_then(|token| sessions.resume(player_id, token));
+        let (attachment, cursor) = match resumed {
+            Some(attachment) => (attachment, params.last_seq.unwrap_or(0)),
+            None => {
+                let attachment = sessions.open(player_id, &player.username, region.as_deref());
+                let cursor = sessions.last_seq(&attachment);
+                (attachment, cursor)
+            }
+        };
+        let connection =
+            Connection { pool, content, combat, presence, sessions: sessions.clone(), player_id, attachment };
+        connection.run(socket, cursor).await;
+        sessions.detach(&connection.attachment);
+    })
+}
+
+struct Connection {
+    pool: DbPool,
+    content: SharedContent,
+    combat: CombatSessions,
+    presence: SharedPresence,
+    sessions: SharedGameSessions,
+    player_id: i32,
+    attachment: Attachment,
+}
+
+/// Why a connection stopped
+enum Closed {
+    /// The client went away, stopped answering or couldn't keep up
+    Gone,
+    /// Another connection resumed the session
+    Replaced,
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage<'_>) -> Result<(), Closed> {
+    let text = serde_json::to_string(message).map_err(|_| Closed::Gone)?;
+    match tokio::time::timeout(SEND_TIMEOUT, socket.send(Message::Text(text))).await {
+        Ok(Ok(())) => Ok(()),
+        _ => Err(Closed::Gone),
+    }
+}
+
+impl Connection {
+    /// Stream the session's events after `cursor` and run the commands that come in, until the
+    /// client leaves or another connection takes over
+    async fn run(&self, mut socket: WebSocket, mut cursor: u64) {
+        let welcome = ServerMessage::Welcome {
+            session_id: self.attachment.session_id,
+            resume_token: &self.attachment.resume_token,
+            resumed: self.attachment.resumed,
+            last_seq: self.sessions.last_seq(&self.attachment),
+        };
+        if send(&mut socket, &welcome).await.is_err() {
+            return;
+        }
+
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        let mut last_heard = Instant::now();
+        let closed = loop {
+            // Anything already queued goes out first, so replays come before new events
+            if let Err(closed) = self.flush(&mut socket, &mut cursor).await {
+                break closed;
+            }
+            tokio::select! {
+                _ = self.attachment.changed() => {}
+                _ = ping.tick() => {
+                    if last_heard.elapsed() > IDLE_TIMEOUT {
+                        break Closed::Gone;
+                    }
+                    let pinged = tokio::time::timeout(SEND_TIMEOUT, socket.send(Message::Ping(Vec::new()))).await;
+                    if !matches!(pinged, Ok(Ok(()))) {
+                        break Closed::Gone;
+                    }
+                }
+                message = socket.recv() => {
+                    let Some(Ok(message)) = message else {
+                        break Closed::Gone;
+                    };
+                    last_heard = Instant::now();
+                    if let Err(closed) = self.handle(&mut socket, message).await {
+                        break closed;
+                    }
+                }
+            }
+        };
+        if let Closed::Replaced = closed {
+            let _ = send(&mut socket, &ServerMessage::Error { message: "Resumed on another connection".to_string() }).await;
+            let _ = socket.send(Message::Close(None)).await;
+        }
+    }
+
+    /// Send every event after `cursor`, telling the client first if some were dropped
+    async fn flush(&self, socket: &mut WebSocket, cursor: &mut u64) -> Result<(), Closed> {
+        let pending = self.sessions.pending(&self.attachment, *cursor).ok_or(Closed::Replaced)?;
+        if pending.missed > 0 {
+            send(socket, &ServerMessage::Lagged { missed: pending.missed }).await?;
+        }
+        for (seq, event) in &pending.events {
+            send(socket, &ServerMessage::Event { seq: *seq, event }).await?;
+            *cursor = *seq;
+        }
+        Ok(())
+    }
+
+    async fn handle(&self, socket: &mut WebSocket, message: Message) -> Result<(), Closed> {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => return Err(Closed::Gone),
+            // Pings are answered by the WebSocket itself; pongs only count as being heard from
+            _ => return Ok(()),
+        };
+        let input = if text.trim_start().starts_with('{') {
+            match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(ClientMessage::Command { input }) => input,
+                Ok(ClientMessage::Ping) => return send(socket, &ServerMessage::Pong).await,
+                Err(e) => return send(socket, &ServerMessage::Error { message: e.to_string() }).await,
+            }
+        } else {
+            text
+        };
+
+        let ctx = CommandContext { pool: &self.pool, content: &self.content, sessions: &self.combat, presence: &self.presence };
+        let response = match execute(&ctx, self.player_id, &input).await {
+            Ok(response) => response,
+            Err(e) => return send(socket, &ServerMessage::Error { message: e.to_string() }).await,
+        };
+        if let Err(e) = self.sessions.announce(&self.pool, self.player_id, &response).await {
+            eprintln!("⚠️ Failed to announce a command to the region: {}", e);
+        }
+        self.sessions.publish(self.attachment.session_id, GameEvent::Response(response));
+        Ok(())
+    }
+}
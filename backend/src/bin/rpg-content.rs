@@ -0,0 +1,35 @@
+// src/bin/rpg-content.rs
+// Validates content packs so bad content can be blocked before it ships:
+//
+//     rpg-content check [content_dir]
+
+use rpg_framework::loader::check::check_content;
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let content_dir = match args.as_slice() {
+        [command] if command == "check" => PathBuf::from("content"),
+        [command, dir] if command == "check" => PathBuf::from(dir),
+        _ => {
+            eprintln!("usage: rpg-content check [content_dir]");
+            return ExitCode::from(2);
+        }
+    };
+
+    let diagnostics = check_content(&content_dir);
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+    println!("{}: {} error(s), {} warning(s)", content_dir.display(), errors, warnings);
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
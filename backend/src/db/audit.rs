@@ -0,0 +1,81 @@
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{FromRow, PgExecutor, PgPool};
+
+/// One change made through the admin API
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// `None` once the player who made the change is deleted
+    pub player_id: Option<i32>,
+    pub action: String,
+    pub entity: String,
+    pub entity_id: String,
+    pub before: Option<Json<Value>>,
+    pub after: Option<Json<Value>>,
+    /// Unix time in seconds
+    pub at: i64,
+}
+
+/// Which entries `list_audit_entries` returns
+#[derive(Debug, Default, Clone)]
+pub struct AuditFilter {
+    pub entity: Option<String>,
+    pub entity_id: Option<String>,
+    pub player_id: Option<i32>,
+    /// Only entries older than this one, for paging back through the log
+    pub before_id: Option<i64>,
+}
+
+/// Record that a player changed something. `before` is `None` for creations, `after` for
+/// deletions.
+pub async fn record_audit_entry<'e, E: PgExecutor<'e>>(
+    executor: E,
+    player_id: i32,
+    action: &str,
+    entity: &str,
+    entity_id: &str,
+    before: Option<&Value>,
+    after: Option<&Value>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO admin_audit_log (player_id, action, entity, entity_id, before, after)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(player_id)
+    .bind(action)
+    .bind(entity)
+    .bind(entity_id)
+    .bind(before.map(Json))
+    .bind(after.map(Json))
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Up to `limit` matching entries, newest first
+pub async fn list_audit_entries(pool: &PgPool, filter: &AuditFilter, limit: i64) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    sqlx::query_as::<_, AuditEntry>(
+        r#"
+        SELECT id, player_id, action, entity, entity_id, before, after,
+               EXTRACT(EPOCH FROM created_at)::BIGINT AS at
+        FROM admin_audit_log
+        WHERE ($1::VARCHAR IS NULL OR entity = $1)
+          AND ($2::VARCHAR IS NULL OR entity_id = $2)
+          AND ($3::INT IS NULL OR player_id = $3)
+          AND ($4::BIGINT IS NULL OR id < $4)
+        ORDER BY id DESC
+        LIMIT $5
+        "#,
+    )
+    .bind(&filter.entity)
+    .bind(&filter.entity_id)
+    .bind(filter.player_id)
+    .bind(filter.before_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
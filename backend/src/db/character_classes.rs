@@ -0,0 +1,78 @@
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+use crate::models::CharacterClass;
+
+const CLASS_COLUMNS: &str = "id, name, description, base_health, base_mana, starting_artifacts";
+
+/// Every character class, by name
+pub async fn list_character_classes(pool: &PgPool) -> Result<Vec<CharacterClass>, sqlx::Error> {
+    sqlx::query_as::<_, CharacterClass>(&format!("SELECT {} FROM character_classes ORDER BY name", CLASS_COLUMNS))
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_character_class<'e, E: PgExecutor<'e>>(
+    executor: E,
+    class_id: Uuid,
+) -> Result<Option<CharacterClass>, sqlx::Error> {
+    sqlx::query_as::<_, CharacterClass>(&format!("SELECT {} FROM character_classes WHERE id = $1", CLASS_COLUMNS))
+        .bind(class_id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Add a class with a new ID. Fails with a unique violation if the name is taken.
+pub async fn create_character_class<'e, E: PgExecutor<'e>>(
+    executor: E,
+    class: &CharacterClass,
+) -> Result<CharacterClass, sqlx::Error> {
+    sqlx::query_as::<_, CharacterClass>(&format!(
+        r#"
+        INSERT INTO character_classes (name, description, base_health, base_mana, starting_artifacts)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING {}
+        "#,
+        CLASS_COLUMNS
+    ))
+    .bind(&class.name)
+    .bind(&class.description)
+    .bind(class.base_health)
+    .bind(class.base_mana)
+    .bind(&class.starting_artifacts)
+    .fetch_one(executor)
+    .await
+}
+
+/// Overwrite the class with ID `class.id`. Returns `None` if there is none.
+pub async fn update_character_class<'e, E: PgExecutor<'e>>(
+    executor: E,
+    class: &CharacterClass,
+) -> Result<Option<CharacterClass>, sqlx::Error> {
+    sqlx::query_as::<_, CharacterClass>(&format!(
+        r#"
+        UPDATE character_classes
+        SET name = $2, description = $3, base_health = $4, base_mana = $5, starting_artifacts = $6,
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING {}
+        "#,
+        CLASS_COLUMNS
+    ))
+    .bind(class.id)
+    .bind(&class.name)
+    .bind(&class.description)
+    .bind(class.base_health)
+    .bind(class.base_mana)
+    .bind(&class.starting_artifacts)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Returns whether there was such a class
+pub async fn delete_character_class<'e, E: PgExecutor<'e>>(executor: E, class_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM character_classes WHERE id = $1")
+        .bind(class_id)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -37,15 +37,15 @@ pub async fn check_db_health(pool: &DbPool) -> bool {
 /// Optional: Seed the database with initial data for development/testing.
 pub async fn seed_data(pool: &DbPool) -> Result<(), sqlx::Error> {
     // Example: insert a default region or player class
-    sqlx::query!(
+    sqlx::query(
         r#"
         INSERT INTO character_classes (name, description)
         VALUES ($1, $2)
         ON CONFLICT (name) DO NOTHING
         "#,
-        "Adventurer",
-        "A brave soul starting their journey."
     )
+    .bind("Adventurer")
+    .bind("A brave soul starting their journey.")
     .execute(pool.as_ref())
     .await?;
 
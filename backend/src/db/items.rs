@@ -0,0 +1,124 @@
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use crate::models::{Item, ItemDefinition};
+
+/// How a content sync changed the `items` table
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct ItemSyncReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// Fetch an item template by ID
+pub async fn get_item(pool: &PgPool, item_id: i32) -> Result<Option<Item>, sqlx::Error> {
+    sqlx::query_as::<_, Item>(
+        r#"
+        SELECT id, name, COALESCE(description, '') AS description, item_type,
+               COALESCE(value, 0) AS value, COALESCE(power, 0) AS power, durability,
+               COALESCE(is_magical, FALSE) AS is_magical, COALESCE(is_cursed, FALSE) AS is_cursed,
+               effects
+        FROM items
+        WHERE id = $1
+        "#,
+    )
+    .bind(item_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// The stored definitions of the items with the given content IDs
+pub async fn get_item_definitions(pool: &PgPool, content_ids: &[String]) -> Result<Vec<ItemDefinition>, sqlx::Error> {
+    sqlx::query_as::<_, ItemDefinition>(
+        r#"
+        SELECT content_id AS id, name, COALESCE(description, '') AS description, item_type,
+               COALESCE(value, 0) AS value, COALESCE(power, 0) AS power, durability,
+               COALESCE(is_magical, FALSE) AS is_magical, COALESCE(is_cursed, FALSE) AS is_cursed,
+               effects
+        FROM items
+        WHERE content_id = ANY($1)
+        "#,
+    )
+    .bind(content_ids)
+    .fetch_all(pool)
+    .await
+}
+
+/// Content IDs of the items a player is carrying at least one of
+pub async fn get_carried_content_ids<'e, E: PgExecutor<'e>>(executor: E, player_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT DISTINCT i.content_id
+        FROM inventory inv
+        JOIN items i ON i.id = inv.item_id
+        WHERE inv.player_id = $1 AND inv.quantity > 0 AND i.content_id IS NOT NULL
+        ORDER BY i.content_id
+        "#,
+    )
+    .bind(player_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// Content ID of every item
+pub async fn get_item_content_ids<'e, E: PgExecutor<'e>>(executor: E) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT content_id FROM items WHERE content_id IS NOT NULL")
+        .fetch_all(executor)
+        .await
+}
+
+/// Upsert item definitions by content ID. Rows that already match their definition are left
+/// alone, so syncing the same content twice changes nothing.
+pub async fn sync_item_definitions(
+    conn: &mut PgConnection,
+    items: &[ItemDefinition],
+) -> Result<ItemSyncReport, sqlx::Error> {
+    let mut report = ItemSyncReport::default();
+
+    for item in items {
+        let inserted: Option<bool> = sqlx::query_scalar(
+            r#"
+            INSERT INTO items (content_id, name, description, item_type, value, power, durability,
+                               is_magical, is_cursed, effects)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (content_id) DO UPDATE
+            SET name = EXCLUDED.name,
+                description = EXCLUDED.description,
+                item_type = EXCLUDED.item_type,
+                value = EXCLUDED.value,
+                power = EXCLUDED.power,
+                durability = EXCLUDED.durability,
+                is_magical = EXCLUDED.is_magical,
+                is_cursed = EXCLUDED.is_cursed,
+                effects = EXCLUDED.effects,
+                updated_at = NOW()
+            WHERE (items.name, items.description, items.item_type, items.value, items.power,
+                   items.durability, items.is_magical, items.is_cursed, items.effects)
+                IS DISTINCT FROM
+                  (EXCLUDED.name, EXCLUDED.description, EXCLUDED.item_type, EXCLUDED.value, EXCLUDED.power,
+                   EXCLUDED.durability, EXCLUDED.is_magical, EXCLUDED.is_cursed, EXCLUDED.effects)
+            RETURNING (xmax = 0) AS inserted
+            "#,
+        )
+        .bind(&item.id)
+        .bind(&item.name)
+        .bind(&item.description)
+        .bind(item.item_type.as_str())
+        .bind(item.value)
+        .bind(item.power)
+        .bind(item.durability)
+        .bind(item.is_magical)
+        .bind(item.is_cursed)
+        .bind(Json(&item.effects))
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        match inserted {
+            Some(true) => report.inserted += 1,
+            Some(false) => report.updated += 1,
+            None => report.unchanged += 1,
+        }
+    }
+    Ok(report)
+}
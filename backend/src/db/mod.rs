@@ -1,6 +1,15 @@
+pub mod audit;
+pub mod character_classes;
+#[allow(clippy::module_inception)]
+pub mod db;
+pub mod items;
+pub mod players;
+pub mod regions;
 pub mod seed;
-
-use sqlx::PgPool;
+pub mod sessions;
+pub mod skills;
+pub mod status_effects;
 
 // Optionally, re-export for simpler access
-pub use seed::seed_items;
+pub use db::{check_db_health, init_db, seed_data, DbPool};
+pub use seed::run_seeds;
@@ -0,0 +1,193 @@
+use sqlx::{FromRow, PgExecutor};
+use crate::models::{Player, Position, Role};
+
+/// Fetch a player with the stats the game engine needs
+pub async fn get_player<'e, E: PgExecutor<'e>>(executor: E, player_id: i32) -> Result<Option<Player>, sqlx::Error> {
+    sqlx::query_as::<_, Player>(
+        "SELECT id, username, level, health, max_health, mana, max_mana, experience FROM players WHERE id = $1",
+    )
+    .bind(player_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Persist the mutable game stats of a player
+pub async fn update_player_stats<'e, E: PgExecutor<'e>>(executor: E, player: &Player) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE players
+        SET level = $2, health = $3, max_health = $4, mana = $5, max_mana = $6, experience = $7,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(player.id)
+    .bind(player.level)
+    .bind(player.health)
+    .bind(player.max_health)
+    .bind(player.mana)
+    .bind(player.max_mana)
+    .bind(player.experience)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Send every player standing outside `region_ids` to `to`, returning the IDs of those moved.
+/// Used after content changes so nobody is left in a region that no longer exists.
+pub async fn move_players_not_in<'e, E: PgExecutor<'e>>(
+    executor: E,
+    region_ids: &[String],
+    to: &str,
+) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        r#"
+        UPDATE players
+        SET region_id = $2, room_x = NULL, room_y = NULL, updated_at = NOW()
+        WHERE region_id <> ALL($1)
+        RETURNING id
+        "#,
+    )
+    .bind(region_ids)
+    .bind(to)
+    .fetch_all(executor)
+    .await
+}
+
+/// The region a player is standing in
+pub async fn get_player_region<'e, E: PgExecutor<'e>>(executor: E, player_id: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>("SELECT region_id FROM players WHERE id = $1")
+        .bind(player_id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Move a player to another region, into `room` if it has rooms
+pub async fn set_player_region<'e, E: PgExecutor<'e>>(
+    executor: E,
+    player_id: i32,
+    region_id: &str,
+    room: Option<Position>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE players SET region_id = $2, room_x = $3, room_y = $4, updated_at = NOW() WHERE id = $1")
+        .bind(player_id)
+        .bind(region_id)
+        .bind(room.map(|room| room.x as i32))
+        .bind(room.map(|room| room.y as i32))
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// The region a player is standing in and the room they last walked to there, if any. `None`
+/// if there is no such player.
+pub async fn get_player_room<'e, E: PgExecutor<'e>>(
+    executor: E,
+    player_id: i32,
+) -> Result<Option<(String, Option<Position>)>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (String, Option<i32>, Option<i32>)>(
+        "SELECT region_id, room_x, room_y FROM players WHERE id = $1",
+    )
+    .bind(player_id)
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.map(|(region_id, x, y)| {
+        let room = match (x, y) {
+            (Some(x), Some(y)) if x >= 0 && y >= 0 => Some(Position { x: x as usize, y: y as usize }),
+            _ => None,
+        };
+        (region_id, room)
+    }))
+}
+
+/// Move a player to another room of the region they are in
+pub async fn set_player_room<'e, E: PgExecutor<'e>>(executor: E, player_id: i32, room: Position) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE players SET room_x = $2, room_y = $3, updated_at = NOW() WHERE id = $1")
+        .bind(player_id)
+        .bind(room.x as i32)
+        .bind(room.y as i32)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Portals a player has opened for good by using an item
+pub async fn get_unlocked_portals<'e, E: PgExecutor<'e>>(executor: E, player_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>("SELECT portal_id FROM player_unlocked_portals WHERE player_id = $1 ORDER BY portal_id")
+        .bind(player_id)
+        .fetch_all(executor)
+        .await
+}
+
+/// Create a player with a hashed password, returning their ID
+pub async fn create_player<'e, E: PgExecutor<'e>>(
+    executor: E,
+    username: &str,
+    email: Option<&str>,
+    password_hash: &str,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        "INSERT INTO players (username, email, password_hash) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(username)
+    .bind(email)
+    .bind(password_hash)
+    .fetch_one(executor)
+    .await
+}
+
+/// What logging in checks a username against
+#[derive(FromRow)]
+pub struct Credentials {
+    pub id: i32,
+    /// `None` for players who only sign in through the identity provider
+    pub password_hash: Option<String>,
+    #[sqlx(try_from = "String")]
+    pub role: Role,
+}
+
+/// A player's credentials, looked up by username for logging in
+pub async fn get_credentials<'e, E: PgExecutor<'e>>(
+    executor: E,
+    username: &str,
+) -> Result<Option<Credentials>, sqlx::Error> {
+    sqlx::query_as::<_, Credentials>("SELECT id, password_hash, role FROM players WHERE username = $1")
+        .bind(username)
+        .fetch_optional(executor)
+        .await
+}
+
+/// The role a player's local logins are granted
+pub async fn get_player_role<'e, E: PgExecutor<'e>>(executor: E, player_id: i32) -> Result<Option<Role>, sqlx::Error> {
+    let role = sqlx::query_scalar::<_, String>("SELECT role FROM players WHERE id = $1")
+        .bind(player_id)
+        .fetch_optional(executor)
+        .await?;
+    role.map(|role| Role::try_from(role).map_err(|e| sqlx::Error::Decode(Box::new(e))))
+        .transpose()
+}
+
+/// The player linked to an identity provider subject
+pub async fn find_player_by_subject<'e, E: PgExecutor<'e>>(executor: E, subject: &str) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>("SELECT id FROM players WHERE oidc_subject = $1")
+        .bind(subject)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Create a player without a password, linked to an identity provider subject
+pub async fn create_linked_player<'e, E: PgExecutor<'e>>(
+    executor: E,
+    subject: &str,
+    username: &str,
+    email: Option<&str>,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        "INSERT INTO players (username, email, oidc_subject) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(username)
+    .bind(email)
+    .bind(subject)
+    .fetch_one(executor)
+    .await
+}
@@ -0,0 +1,465 @@
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection, PgExecutor, PgPool};
+use std::collections::HashMap;
+use std::fmt;
+use crate::models::{EnvironmentType, GenerationInfo, Portal, Region, RoomLayout, UnknownVariantError};
+
+/// Where a stored region came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionOrigin {
+    /// A file under `content/regions`; replaced or removed on every content sync
+    Content,
+    /// Made by the region generator while the server was running
+    Generated,
+    /// Brought in from a directory of region files with `regions import`
+    Imported,
+    /// Made or changed through the admin API; content syncs leave it alone
+    Admin,
+}
+
+impl RegionOrigin {
+    pub const ALL: [RegionOrigin; 4] =
+        [RegionOrigin::Content, RegionOrigin::Generated, RegionOrigin::Imported, RegionOrigin::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegionOrigin::Content => "content",
+            RegionOrigin::Generated => "generated",
+            RegionOrigin::Imported => "imported",
+            RegionOrigin::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for RegionOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TryFrom<String> for RegionOrigin {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        RegionOrigin::ALL
+            .into_iter()
+            .find(|origin| origin.as_str() == value)
+            .ok_or(UnknownVariantError { kind: "region origin", value, expected: &["content", "generated", "imported", "admin"] })
+    }
+}
+
+/// Why a region couldn't be saved
+#[derive(Debug)]
+pub enum RegionSaveError {
+    /// The region is already stored with another origin, e.g. a content file reusing the ID of
+    /// a generated region
+    OriginConflict { region_id: String, origin: RegionOrigin },
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for RegionSaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionSaveError::OriginConflict { region_id, origin } => {
+                write!(f, "region '{}' is already stored as {}", region_id, origin)
+            }
+            RegionSaveError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RegionSaveError {}
+
+impl From<sqlx::Error> for RegionSaveError {
+    fn from(e: sqlx::Error) -> Self {
+        RegionSaveError::Database(e)
+    }
+}
+
+/// How saving a batch of regions changed the `regions` table
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RegionSyncReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    /// Content regions whose file is gone
+    pub removed: Vec<String>,
+    /// Region files left unsynced because an admin changed or removed their region
+    pub overridden: Vec<String>,
+}
+
+/// A region's own portal as compared on save: id, name, leads_to, required_level, required_item
+type PortalFields = (String, String, String, i32, Option<String>);
+
+#[derive(FromRow)]
+struct RegionRow {
+    id: String,
+    name: String,
+    description: String,
+    #[sqlx(try_from = "String")]
+    environment: EnvironmentType,
+    anchor_point: Option<String>,
+    generated: Option<Json<GenerationInfo>>,
+    layout: Option<Json<RoomLayout>>,
+}
+
+#[derive(FromRow)]
+struct PortalRow {
+    region_id: String,
+    id: String,
+    name: String,
+    leads_to: String,
+    required_level: i32,
+    required_item: Option<String>,
+}
+
+/// Every stored region with its portals, read in one snapshot so a concurrent save can't leave
+/// a region without the portals it was saved with
+pub async fn load_regions(pool: &PgPool) -> Result<Vec<Region>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let regions = read_regions(&mut tx).await?;
+    tx.commit().await?;
+    Ok(regions)
+}
+
+/// Every stored region with its portals, as `conn` sees them. Only consistent if nothing can
+/// save regions in between, see `load_regions` and `lock_regions`.
+pub async fn read_regions(conn: &mut PgConnection) -> Result<Vec<Region>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, RegionRow>(
+        r#"
+        SELECT id, name, description, environment, anchor_point, generated, layout
+        FROM regions
+        ORDER BY id
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let portal_rows = sqlx::query_as::<_, PortalRow>(
+        r#"
+        SELECT region_id, id, name, leads_to, required_level, required_item
+        FROM portals
+        ORDER BY region_id, position, added_by NULLS FIRST, id
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut portals: HashMap<String, Vec<Portal>> = HashMap::new();
+    for row in portal_rows {
+        portals.entry(row.region_id).or_default().push(Portal {
+            id: row.id,
+            name: row.name,
+            leads_to: row.leads_to,
+            required_level: row.required_level,
+            required_item: row.required_item,
+        });
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Region {
+            portals: portals.remove(&row.id).unwrap_or_default(),
+            id: row.id,
+            name: row.name,
+            description: row.description,
+            environment: row.environment,
+            anchor_point: row.anchor_point,
+            generated: row.generated.map(|generated| generated.0),
+            layout: row.layout.map(|layout| layout.0),
+        })
+        .collect())
+}
+
+/// Keep every other transaction from saving or removing regions and portals until `conn`'s
+/// transaction ends. Reads go on as usual.
+pub async fn lock_regions(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("LOCK TABLE regions, portals IN SHARE ROW EXCLUSIVE MODE")
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Changes whenever a region is saved, added or removed, since saving bumps the region's version
+pub async fn region_fingerprint<'e, E: PgExecutor<'e>>(executor: E) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar("SELECT COALESCE(md5(string_agg(id || ':' || version, ',' ORDER BY id)), '') FROM regions")
+        .fetch_one(executor)
+        .await
+}
+
+/// Insert or update a region and replace its own portals. Portals other regions added to it
+/// are kept. Returns `Some(true)` when inserted, `Some(false)` when updated and `None` when the
+/// stored region already matched; an update bumps the region's version. A region stored with
+/// another origin is left alone.
+pub async fn upsert_region(
+    conn: &mut PgConnection,
+    region: &Region,
+    origin: RegionOrigin,
+) -> Result<Option<bool>, RegionSaveError> {
+    let mut changed: Option<bool> = sqlx::query_scalar(
+        r#"
+        INSERT INTO regions (id, name, description, environment, anchor_point, generated, layout, origin)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (id) DO UPDATE
+        SET name = EXCLUDED.name,
+            description = EXCLUDED.description,
+            environment = EXCLUDED.environment,
+            anchor_point = EXCLUDED.anchor_point,
+            generated = EXCLUDED.generated,
+            layout = EXCLUDED.layout,
+            version = regions.version + 1,
+            updated_at = NOW()
+        WHERE regions.origin = EXCLUDED.origin
+          AND (regions.name, regions.description, regions.environment, regions.anchor_point,
+               regions.generated, regions.layout)
+            IS DISTINCT FROM
+              (EXCLUDED.name, EXCLUDED.description, EXCLUDED.environment, EXCLUDED.anchor_point,
+               EXCLUDED.generated, EXCLUDED.layout)
+        RETURNING (xmax = 0) AS inserted
+        "#,
+    )
+    .bind(&region.id)
+    .bind(&region.name)
+    .bind(&region.description)
+    .bind(region.environment.as_str())
+    .bind(&region.anchor_point)
+    .bind(region.generated.as_ref().map(Json))
+    .bind(region.layout.as_ref().map(Json))
+    .bind(origin.as_str())
+    .fetch_optional(&mut *conn)
+    .await?;
+    if changed.is_none() {
+        // Skipped: either unchanged or stored with another origin. The conflict locked the row,
+        // so its origin can't change before the portals are saved.
+        let stored: String = sqlx::query_scalar("SELECT origin FROM regions WHERE id = $1")
+            .bind(&region.id)
+            .fetch_one(&mut *conn)
+            .await?;
+        if stored != origin.as_str() {
+            let origin = RegionOrigin::try_from(stored).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            return Err(RegionSaveError::OriginConflict { region_id: region.id.clone(), origin });
+        }
+    }
+
+    let stored: Vec<PortalFields> = sqlx::query_as(
+        r#"
+        SELECT id, name, leads_to, required_level, required_item
+        FROM portals
+        WHERE region_id = $1 AND added_by IS NULL
+        ORDER BY position, id
+        "#,
+    )
+    .bind(&region.id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let wanted: Vec<PortalFields> = region
+        .portals
+        .iter()
+        .map(|p| (p.id.clone(), p.name.clone(), p.leads_to.clone(), p.required_level, p.required_item.clone()))
+        .collect();
+    if stored == wanted {
+        return Ok(changed);
+    }
+
+    sqlx::query("DELETE FROM portals WHERE region_id = $1 AND added_by IS NULL")
+        .bind(&region.id)
+        .execute(&mut *conn)
+        .await?;
+    for (position, portal) in region.portals.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO portals (id, region_id, name, leads_to, required_level, required_item, position)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(&portal.id)
+        .bind(&region.id)
+        .bind(&portal.name)
+        .bind(&portal.leads_to)
+        .bind(portal.required_level)
+        .bind(&portal.required_item)
+        .bind(position as i32)
+        .execute(&mut *conn)
+        .await?;
+    }
+    if changed.is_none() {
+        bump_version(conn, &region.id).await?;
+        changed = Some(false);
+    }
+    Ok(changed)
+}
+
+async fn bump_version(conn: &mut PgConnection, region_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE regions SET version = version + 1, updated_at = NOW() WHERE id = $1")
+        .bind(region_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Add a portal to an existing region on behalf of `added_by`, after the region's other
+/// portals. Saving the same portal again updates it in place.
+pub async fn add_portal(
+    conn: &mut PgConnection,
+    region_id: &str,
+    portal: &Portal,
+    added_by: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO portals (id, region_id, name, leads_to, required_level, required_item, added_by, position)
+        SELECT $1, $2, $3, $4, $5, $6, $7, COALESCE(MAX(position) + 1, 0)
+        FROM portals
+        WHERE region_id = $2
+        ON CONFLICT (id) DO UPDATE
+        SET name = EXCLUDED.name,
+            leads_to = EXCLUDED.leads_to,
+            required_level = EXCLUDED.required_level,
+            required_item = EXCLUDED.required_item
+        "#,
+    )
+    .bind(&portal.id)
+    .bind(region_id)
+    .bind(&portal.name)
+    .bind(&portal.leads_to)
+    .bind(portal.required_level)
+    .bind(&portal.required_item)
+    .bind(added_by)
+    .execute(&mut *conn)
+    .await?;
+    bump_version(conn, region_id).await
+}
+
+/// Save a region an admin made or changed. It is stored as `admin` from then on, whatever it was
+/// before, and every portal it is saved with becomes its own, so content syncs and saving the
+/// regions that added portals to it leave it alone. Returns whether it was inserted.
+pub async fn save_admin_region(conn: &mut PgConnection, region: &Region) -> Result<bool, RegionSaveError> {
+    sqlx::query("DELETE FROM removed_regions WHERE id = $1")
+        .bind(&region.id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("UPDATE regions SET origin = $2 WHERE id = $1")
+        .bind(&region.id)
+        .bind(RegionOrigin::Admin.as_str())
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM portals WHERE region_id = $1 AND added_by IS NOT NULL")
+        .bind(&region.id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(upsert_region(conn, region, RegionOrigin::Admin).await? == Some(true))
+}
+
+/// Remove a region an admin deleted, along with its portals and every portal leading into it.
+/// Regions that lose a portal of their own are stored as `admin` from then on, and a content
+/// region is remembered as removed, so content syncs bring neither back while their files are
+/// still there. Returns the regions that lost a portal of their own, or `None` if there is no
+/// such region.
+pub async fn remove_region(conn: &mut PgConnection, region_id: &str) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let origin: Option<String> = sqlx::query_scalar("DELETE FROM regions WHERE id = $1 RETURNING origin")
+        .bind(region_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(origin) = origin else {
+        return Ok(None);
+    };
+    if origin == RegionOrigin::Content.as_str() {
+        sqlx::query("INSERT INTO removed_regions (id) VALUES ($1) ON CONFLICT (id) DO NOTHING")
+            .bind(region_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let mut entered: Vec<String> = sqlx::query_scalar(
+        r#"
+        UPDATE regions
+        SET origin = $2, version = version + 1, updated_at = NOW()
+        WHERE id IN (SELECT region_id FROM portals WHERE leads_to = $1 AND added_by IS NULL)
+        RETURNING id
+        "#,
+    )
+    .bind(region_id)
+    .bind(RegionOrigin::Admin.as_str())
+    .fetch_all(&mut *conn)
+    .await?;
+    sqlx::query("DELETE FROM portals WHERE leads_to = $1")
+        .bind(region_id)
+        .execute(&mut *conn)
+        .await?;
+    entered.sort();
+    Ok(Some(entered))
+}
+
+async fn save_regions(
+    conn: &mut PgConnection,
+    regions: &[Region],
+    origin: RegionOrigin,
+) -> Result<RegionSyncReport, RegionSaveError> {
+    let mut report = RegionSyncReport::default();
+    for region in regions {
+        match upsert_region(conn, region, origin).await? {
+            Some(true) => report.inserted += 1,
+            Some(false) => report.updated += 1,
+            None => report.unchanged += 1,
+        }
+    }
+    Ok(report)
+}
+
+/// Make the stored content regions match the region files: save every region given and remove
+/// those of `previous`, the content regions loaded last time, that are no longer among them.
+/// Content regions this replica never loaded are left to whoever did, so replicas running
+/// different content don't remove each other's regions. Generated, imported and admin regions
+/// are kept, and files for regions an admin changed or removed are skipped.
+pub async fn sync_content_regions(
+    conn: &mut PgConnection,
+    regions: &[Region],
+    previous: &[String],
+) -> Result<RegionSyncReport, RegionSaveError> {
+    let overridden: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM regions WHERE origin = $1
+        UNION
+        SELECT id FROM removed_regions
+        "#,
+    )
+    .bind(RegionOrigin::Admin.as_str())
+    .fetch_all(&mut *conn)
+    .await?;
+    let (skipped, synced): (Vec<Region>, Vec<Region>) =
+        regions.iter().cloned().partition(|region| overridden.contains(&region.id));
+    let mut report = save_regions(conn, &synced, RegionOrigin::Content).await?;
+    report.overridden = skipped.into_iter().map(|region| region.id).collect();
+    report.overridden.sort();
+
+    let removed: Vec<&str> = previous
+        .iter()
+        .map(|id| id.as_str())
+        .filter(|id| regions.iter().all(|r| r.id != *id))
+        .collect();
+    report.removed = sqlx::query_scalar(
+        r#"
+        DELETE FROM regions
+        WHERE origin = $1 AND id = ANY($2)
+        RETURNING id
+        "#,
+    )
+    .bind(RegionOrigin::Content.as_str())
+    .bind(&removed)
+    .fetch_all(&mut *conn)
+    .await?;
+    report.removed.sort();
+    Ok(report)
+}
+
+/// Save regions read from region files as imported, all or nothing
+pub async fn import_regions(pool: &PgPool, regions: &[Region]) -> Result<RegionSyncReport, RegionSaveError> {
+    let mut tx = pool.begin().await?;
+    let report = save_regions(&mut tx, regions, RegionOrigin::Imported).await?;
+    tx.commit().await?;
+    Ok(report)
+}
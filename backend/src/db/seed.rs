@@ -1,82 +1,365 @@
 // src/db/seed.rs
 
-use sqlx::{PgPool, Error};
+use sqlx::{FromRow, PgPool, Error};
+use std::collections::HashMap;
+use std::fmt;
+use crate::db::items::{get_item_definitions, sync_item_definitions};
+use crate::models::{ItemDefinition, ItemType};
+
+/// Bump a set's version whenever its rows change, so existing databases pick the change up
+const ITEM_SEED_VERSION: i32 = 3;
+const SKILL_SEED_VERSION: i32 = 2;
+
+/// ========== Seed Reports ==========
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeedChange {
+    Insert { key: String },
+    Update { key: String, fields: Vec<&'static str> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedStatus {
+    /// The recorded version is current, nothing was compared
+    UpToDate,
+    Applied,
+    /// Changes were computed but not written
+    DryRun,
+}
+
+#[derive(Debug, Clone)]
+pub struct SeedSetReport {
+    pub name: &'static str,
+    pub version: i32,
+    pub applied_version: Option<i32>,
+    pub status: SeedStatus,
+    pub changes: Vec<SeedChange>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SeedReport {
+    pub sets: Vec<SeedSetReport>,
+}
+
+impl fmt::Display for SeedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for set in &self.sets {
+            let applied = set.applied_version.map_or("none".to_string(), |v| format!("v{}", v));
+            let status = match set.status {
+                SeedStatus::UpToDate => "up to date",
+                SeedStatus::Applied => "applied",
+                SeedStatus::DryRun => "dry run",
+            };
+            writeln!(f, "{} v{} (recorded: {}): {}", set.name, set.version, applied, status)?;
+            if set.status != SeedStatus::UpToDate && set.changes.is_empty() {
+                writeln!(f, "  no changes")?;
+            }
+            for change in &set.changes {
+                match change {
+                    SeedChange::Insert { key } => writeln!(f, "  + {}", key)?,
+                    SeedChange::Update { key, fields } => writeln!(f, "  ~ {} ({})", key, fields.join(", "))?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Names of the fields whose pair differs
+fn changed_fields(fields: &[(&'static str, bool)]) -> Vec<&'static str> {
+    fields.iter().filter(|(_, changed)| *changed).map(|(name, _)| *name).collect()
+}
+
+async fn applied_version(pool: &PgPool, name: &str) -> Result<Option<i32>, Error> {
+    sqlx::query_scalar("SELECT version FROM seed_versions WHERE name = $1")
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+}
+
+async fn record_version<'e, E: sqlx::PgExecutor<'e>>(executor: E, name: &str, version: i32) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO seed_versions (name, version)
+        VALUES ($1, $2)
+        ON CONFLICT (name) DO UPDATE SET version = EXCLUDED.version, applied_at = NOW()
+        "#,
+    )
+    .bind(name)
+    .bind(version)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
 
 /// ========== Item Struct & Seeding ==========
 #[derive(Debug)]
 struct Item<'a> {
+    content_id: &'a str,
     name: &'a str,
     description: &'a str,
     durability: Option<i32>,
     is_magical: bool,
     is_cursed: bool,
-    item_type: &'a str,
+    item_type: ItemType,
     power: i32,
     value: i32,
+    effects: &'a str, // JSON list of `ItemEffect`s
+}
+
+impl Item<'_> {
+    fn to_definition(&self) -> ItemDefinition {
+        ItemDefinition {
+            id: self.content_id.to_string(),
+            name: self.name.to_string(),
+            description: self.description.to_string(),
+            item_type: self.item_type,
+            value: self.value,
+            power: self.power,
+            durability: self.durability,
+            is_magical: self.is_magical,
+            is_cursed: self.is_cursed,
+            effects: serde_json::from_str(self.effects).expect("seed item effects are valid JSON"),
+        }
+    }
 }
 
-pub async fn seed_items(pool: &PgPool) -> Result<(), Error> {
-    let items = vec![
-        Item { name: "Iron Sword", description: "A basic iron sword.", durability: Some(100), is_magical: false, is_cursed: false, item_type: "Weapon", power: 10, value: 50 },
-        Item { name: "Healing Potion", description: "Restores a small amount of health.", durability: None, is_magical: false, is_cursed: false, item_type: "Consumable", power: 0, value: 20 },
-        Item { name: "Staff of Fire", description: "A magical staff that casts fire.", durability: Some(80), is_magical: true, is_cursed: false, item_type: "Weapon", power: 25, value: 150 },
-        Item { name: "Cursed Ring", description: "A ring that binds the soul.", durability: None, is_magical: true, is_cursed: true, item_type: "Accessory", power: 5, value: 5 },
-        Item { name: "Leather Armor", description: "Basic leather protection.", durability: Some(150), is_magical: false, is_cursed: false, item_type: "Armor", power: 0, value: 75 },
-        Item { name: "Pegasus Saddle", description: "Used to mount a pegasus.", durability: Some(60), is_magical: false, is_cursed: false, item_type: "MountAccessory", power: 0, value: 100 },
-        Item { name: "Battlemech Chip", description: "Activates a battlemech.", durability: None, is_magical: false, is_cursed: false, item_type: "TechKey", power: 0, value: 250 },
-        Item { name: "Elven Cloak", description: "A magical cloak that boosts agility.", durability: Some(120), is_magical: true, is_cursed: false, item_type: "Armor", power: 2, value: 90 },
-        Item { name: "Necromancer Skull", description: "Used to summon undead minions.", durability: None, is_magical: true, is_cursed: true, item_type: "MagicItem", power: 30, value: 300 },
-        Item { name: "Explorer's Compass", description: "Helps navigate hybrid worlds.", durability: None, is_magical: false, is_cursed: false, item_type: "Tool", power: 0, value: 40 },
+/// Sample items. Items content refers to, like the keys portals and environments ask for, are
+/// defined in `content/items` instead so only one place writes them.
+fn seed_item_definitions() -> Vec<ItemDefinition> {
+    use ItemType::*;
+    let items = [
+        Item { content_id: "iron_sword", name: "Iron Sword", description: "A basic iron sword.", durability: Some(100), is_magical: false, is_cursed: false, item_type: Weapon, power: 10, value: 50, effects: "[]" },
+        Item { content_id: "healing_potion", name: "Healing Potion", description: "Restores a small amount of health.", durability: None, is_magical: false, is_cursed: false, item_type: Consumable, power: 0, value: 20, effects: r#"[{"effect": "heal", "amount": 25}]"# },
+        Item { content_id: "staff_of_fire", name: "Staff of Fire", description: "A magical staff that casts fire.", durability: Some(80), is_magical: true, is_cursed: false, item_type: Weapon, power: 25, value: 150, effects: "[]" },
+        Item { content_id: "cursed_ring", name: "Cursed Ring", description: "A ring that binds the soul.", durability: None, is_magical: true, is_cursed: true, item_type: Accessory, power: 5, value: 5, effects: "[]" },
+        Item { content_id: "leather_armor", name: "Leather Armor", description: "Basic leather protection.", durability: Some(150), is_magical: false, is_cursed: false, item_type: Armor, power: 0, value: 75, effects: "[]" },
+        Item { content_id: "pegasus_saddle", name: "Pegasus Saddle", description: "Used to mount a pegasus.", durability: Some(60), is_magical: false, is_cursed: false, item_type: MountAccessory, power: 0, value: 100, effects: "[]" },
+        Item { content_id: "elven_cloak", name: "Elven Cloak", description: "A magical cloak that boosts agility.", durability: Some(120), is_magical: true, is_cursed: false, item_type: Armor, power: 2, value: 90, effects: "[]" },
+        Item { content_id: "necromancer_skull", name: "Necromancer Skull", description: "Used to summon undead minions.", durability: None, is_magical: true, is_cursed: true, item_type: MagicItem, power: 30, value: 300, effects: r#"[{"effect": "summon_minion", "minion_type": "Skeleton", "health": 20, "power": 8}]"# },
     ];
 
-    for item in items {
-        sqlx::query!(
+    items.iter().map(Item::to_definition).collect()
+}
+
+fn diff_item(old: &ItemDefinition, new: &ItemDefinition) -> Vec<&'static str> {
+    changed_fields(&[
+        ("name", old.name != new.name),
+        ("description", old.description != new.description),
+        ("item_type", old.item_type != new.item_type),
+        ("value", old.value != new.value),
+        ("power", old.power != new.power),
+        ("durability", old.durability != new.durability),
+        ("is_magical", old.is_magical != new.is_magical),
+        ("is_cursed", old.is_cursed != new.is_cursed),
+        ("effects", old.effects != new.effects),
+    ])
+}
+
+/// Upsert the sample items by content ID
+pub async fn seed_items(pool: &PgPool, dry_run: bool) -> Result<SeedSetReport, Error> {
+    let mut report = SeedSetReport {
+        name: "items",
+        version: ITEM_SEED_VERSION,
+        applied_version: applied_version(pool, "items").await?,
+        status: SeedStatus::UpToDate,
+        changes: Vec::new(),
+    };
+    if report.applied_version >= Some(ITEM_SEED_VERSION) {
+        return Ok(report);
+    }
+
+    let items = seed_item_definitions();
+    let ids: Vec<String> = items.iter().map(|i| i.id.clone()).collect();
+    let existing: HashMap<String, ItemDefinition> = get_item_definitions(pool, &ids)
+        .await?
+        .into_iter()
+        .map(|i| (i.id.clone(), i))
+        .collect();
+    for item in &items {
+        match existing.get(&item.id) {
+            None => report.changes.push(SeedChange::Insert { key: item.id.clone() }),
+            Some(old) => {
+                let fields = diff_item(old, item);
+                if !fields.is_empty() {
+                    report.changes.push(SeedChange::Update { key: item.id.clone(), fields });
+                }
+            }
+        }
+    }
+
+    if dry_run {
+        report.status = SeedStatus::DryRun;
+        return Ok(report);
+    }
+    let mut tx = pool.begin().await?;
+    sync_item_definitions(&mut tx, &items).await?;
+    record_version(&mut *tx, "items", ITEM_SEED_VERSION).await?;
+    tx.commit().await?;
+    report.status = SeedStatus::Applied;
+    Ok(report)
+}
+
+/// ========== Skill Seeding ==========
+#[derive(Debug, Clone, PartialEq, FromRow)]
+struct SkillSeed {
+    name: String,
+    description: String,
+    skill_type: String,
+    power: i32,
+    cooldown: i32,
+    mana_cost: i32,
+    target_type: String,
+    status_effect: Option<String>,
+    effect_duration: Option<i32>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn skill(
+    name: &str,
+    description: &str,
+    skill_type: &str,
+    power: i32,
+    cooldown: i32,
+    mana_cost: i32,
+    target_type: &str,
+    status_effect: Option<(&str, i32)>,
+) -> SkillSeed {
+    SkillSeed {
+        name: name.to_string(),
+        description: description.to_string(),
+        skill_type: skill_type.to_string(),
+        power,
+        cooldown,
+        mana_cost,
+        target_type: target_type.to_string(),
+        status_effect: status_effect.map(|(kind, _)| kind.to_string()),
+        effect_duration: status_effect.map(|(_, turns)| turns),
+    }
+}
+
+fn seed_skill_rows() -> Vec<SkillSeed> {
+    vec![
+        skill("Fireball", "Hurls a fiery ball that explodes on impact.", "Magic", 50, 3, 20, "Enemy", None),
+        skill("Heal", "Restores a small amount of HP.", "Support", 30, 2, 10, "Ally", None),
+        skill("Shadow Strike", "A quick strike from the shadows.", "Physical", 40, 1, 5, "Enemy", None),
+        skill("Ice Lance", "Launches a sharp icicle that pierces armor.", "Magic", 45, 3, 18, "Enemy", None),
+        skill("Battle Cry", "Increases allies' attack power for 3 turns.", "Buff", 0, 5, 15, "Ally", Some(("AttackUp", 3))),
+        skill("Thunderclap", "Calls lightning to strike enemies in range.", "Magic", 60, 4, 25, "Enemy", None),
+        skill("Smokescreen", "Reduces enemy accuracy.", "Debuff", 0, 3, 10, "Enemy", Some(("AccuracyDown", 3))),
+        skill("Regeneration", "Gradually restores HP over time.", "Support", 0, 6, 20, "Self", Some(("Regeneration", 5))),
+        skill("Power Slash", "A heavy physical attack with bonus damage.", "Physical", 55, 2, 10, "Enemy", None),
+        skill("Charm", "Attempts to seduce the enemy into skipping a turn.", "Debuff", 0, 4, 15, "Enemy", Some(("Stun", 1))),
+    ]
+}
+
+fn diff_skill(old: &SkillSeed, new: &SkillSeed) -> Vec<&'static str> {
+    changed_fields(&[
+        ("description", old.description != new.description),
+        ("skill_type", old.skill_type != new.skill_type),
+        ("power", old.power != new.power),
+        ("cooldown", old.cooldown != new.cooldown),
+        ("mana_cost", old.mana_cost != new.mana_cost),
+        ("target_type", old.target_type != new.target_type),
+        ("status_effect", old.status_effect != new.status_effect),
+        ("effect_duration", old.effect_duration != new.effect_duration),
+    ])
+}
+
+/// Upsert the sample skills by name
+pub async fn seed_skills(pool: &PgPool, dry_run: bool) -> Result<SeedSetReport, Error> {
+    let mut report = SeedSetReport {
+        name: "skills",
+        version: SKILL_SEED_VERSION,
+        applied_version: applied_version(pool, "skills").await?,
+        status: SeedStatus::UpToDate,
+        changes: Vec::new(),
+    };
+    if report.applied_version >= Some(SKILL_SEED_VERSION) {
+        return Ok(report);
+    }
+
+    let skills = seed_skill_rows();
+    let names: Vec<String> = skills.iter().map(|s| s.name.clone()).collect();
+    let existing: HashMap<String, SkillSeed> = sqlx::query_as::<_, SkillSeed>(
+        r#"
+        SELECT name, COALESCE(description, '') AS description, COALESCE(skill_type, '') AS skill_type,
+               COALESCE(power, 0) AS power, COALESCE(cooldown, 0) AS cooldown,
+               COALESCE(mana_cost, 0) AS mana_cost, COALESCE(target_type, '') AS target_type,
+               status_effect, effect_duration
+        FROM skills
+        WHERE name = ANY($1)
+        "#,
+    )
+    .bind(&names)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|s| (s.name.clone(), s))
+    .collect();
+
+    let mut pending = Vec::new();
+    for skill in &skills {
+        match existing.get(&skill.name) {
+            None => report.changes.push(SeedChange::Insert { key: skill.name.clone() }),
+            Some(old) => {
+                let fields = diff_skill(old, skill);
+                if fields.is_empty() {
+                    continue;
+                }
+                report.changes.push(SeedChange::Update { key: skill.name.clone(), fields });
+            }
+        }
+        pending.push(skill);
+    }
+
+    if dry_run {
+        report.status = SeedStatus::DryRun;
+        return Ok(report);
+    }
+
+    let mut tx = pool.begin().await?;
+    for skill in pending {
+        sqlx::query(
             r#"
-            INSERT INTO items (name, description, durability, is_magical, is_cursed, item_type, power, value)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            INSERT INTO skills (name, description, skill_type, power, cooldown, mana_cost, target_type,
+                                status_effect, effect_duration)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (name) DO UPDATE
+            SET description = EXCLUDED.description,
+                skill_type = EXCLUDED.skill_type,
+                power = EXCLUDED.power,
+                cooldown = EXCLUDED.cooldown,
+                mana_cost = EXCLUDED.mana_cost,
+                target_type = EXCLUDED.target_type,
+                status_effect = EXCLUDED.status_effect,
+                effect_duration = EXCLUDED.effect_duration,
+                updated_at = NOW()
             "#,
-            item.name,
-            item.description,
-            item.durability,
-            item.is_magical,
-            item.is_cursed,
-            item.item_type,
-            item.power,
-            item.value
         )
-        .execute(pool)
+        .bind(&skill.name)
+        .bind(&skill.description)
+        .bind(&skill.skill_type)
+        .bind(skill.power)
+        .bind(skill.cooldown)
+        .bind(skill.mana_cost)
+        .bind(&skill.target_type)
+        .bind(&skill.status_effect)
+        .bind(skill.effect_duration)
+        .execute(&mut *tx)
         .await?;
     }
+    record_version(&mut *tx, "skills", SKILL_SEED_VERSION).await?;
+    tx.commit().await?;
 
-    println!("✅ Seeded sample items.");
-    Ok(())
-}
-
-/// ========== Skill Seeding ==========
-pub async fn seed_skills(pool: &PgPool) -> Result<(), sqlx::Error> {
-    let query = r#"
-        INSERT INTO skills (name, description, skill_type, power, cooldown, mana_cost, target_type)
-        VALUES 
-        ('Fireball', 'Hurls a fiery ball that explodes on impact.', 'Magic', 50, 3, 20, 'Enemy'),
-        ('Heal', 'Restores a small amount of HP.', 'Support', 30, 2, 10, 'Ally'),
-        ('Shadow Strike', 'A quick strike from the shadows.', 'Physical', 40, 1, 5, 'Enemy'),
-        ('Ice Lance', 'Launches a sharp icicle that pierces armor.', 'Magic', 45, 3, 18, 'Enemy'),
-        ('Battle Cry', 'Increases allies'' attack power for 3 turns.', 'Buff', 0, 5, 15, 'Ally'),
-        ('Thunderclap', 'Calls lightning to strike enemies in range.', 'Magic', 60, 4, 25, 'Enemy'),
-        ('Smokescreen', 'Reduces enemy accuracy.', 'Debuff', 0, 3, 10, 'Enemy'),
-        ('Regeneration', 'Gradually restores HP over time.', 'Support', 0, 6, 20, 'Self'),
-        ('Power Slash', 'A heavy physical attack with bonus damage.', 'Physical', 55, 2, 10, 'Enemy'),
-        ('Charm', 'Attempts to seduce the enemy into skipping a turn.', 'Debuff', 0, 4, 15, 'Enemy');
-    "#;
-
-    sqlx::query(query).execute(pool).await?;
-    println!("✅ Skills seeded successfully.");
-    Ok(())
+    report.status = SeedStatus::Applied;
+    Ok(report)
 }
 
 /// ========== Central Entry Point ==========
-pub async fn run_seeds(pool: &PgPool) -> Result<(), Error> {
-    seed_items(pool).await?;
-    seed_skills(pool).await?;
-    Ok(())
+/// Bring every seed set up to its current version. With `dry_run` nothing is written and the
+/// report lists what would change.
+pub async fn run_seeds(pool: &PgPool, dry_run: bool) -> Result<SeedReport, Error> {
+    Ok(SeedReport {
+        sets: vec![seed_items(pool, dry_run).await?, seed_skills(pool, dry_run).await?],
+    })
 }
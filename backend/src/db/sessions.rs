@@ -0,0 +1,102 @@
+use sqlx::PgExecutor;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Why a refresh token was revoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Revocation {
+    /// Traded in for a new pair
+    Rotated,
+    /// Given back when logging out
+    Logout,
+    /// Revoked along with the player's other tokens because a rotated one was used again
+    Reuse,
+}
+
+impl Revocation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Revocation::Rotated => "rotated",
+            Revocation::Logout => "logout",
+            Revocation::Reuse => "reuse",
+        }
+    }
+}
+
+/// Record a refresh token issued to a player, good for `ttl`
+pub async fn insert_refresh_token<'e, E: PgExecutor<'e>>(
+    executor: E,
+    id: Uuid,
+    player_id: i32,
+    ttl: Duration,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (id, player_id, expires_at)
+        VALUES ($1, $2, NOW() + make_interval(secs => $3))
+        "#,
+    )
+    .bind(id)
+    .bind(player_id)
+    .bind(ttl.as_secs_f64())
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Revoke a refresh token that is still good, returning the player it was issued to. Returns
+/// `None` for tokens that are unknown, expired or already revoked.
+pub async fn take_refresh_token<'e, E: PgExecutor<'e>>(
+    executor: E,
+    id: Uuid,
+    reason: Revocation,
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = NOW(), revoked_reason = $2
+        WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        RETURNING player_id
+        "#,
+    )
+    .bind(id)
+    .bind(reason.as_str())
+    .fetch_optional(executor)
+    .await
+}
+
+/// The player a refresh token that was already traded in was issued to, if `id` is one.
+/// Tokens given back at logout don't count.
+pub async fn rotated_token_owner<'e, E: PgExecutor<'e>>(executor: E, id: Uuid) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>("SELECT player_id FROM refresh_tokens WHERE id = $1 AND revoked_reason = $2")
+        .bind(id)
+        .bind(Revocation::Rotated.as_str())
+        .fetch_optional(executor)
+        .await
+}
+
+/// Revoke every refresh token a player holds, logging them out everywhere
+pub async fn revoke_player_tokens<'e, E: PgExecutor<'e>>(
+    executor: E,
+    player_id: i32,
+    reason: Revocation,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW(), revoked_reason = $2 WHERE player_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(player_id)
+    .bind(reason.as_str())
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Forget a player's expired refresh tokens. Revoked ones are kept until they expire so reuse
+/// can still be spotted.
+pub async fn delete_expired_tokens<'e, E: PgExecutor<'e>>(executor: E, player_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM refresh_tokens WHERE player_id = $1 AND expires_at < NOW()")
+        .bind(player_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
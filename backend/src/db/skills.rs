@@ -0,0 +1,131 @@
+use sqlx::{PgExecutor, PgPool};
+use crate::models::{PlayerSkill, Skill};
+
+const PLAYER_SKILL_COLUMNS: &str = r#"
+    SELECT s.id, s.name, s.description, s.skill_type, s.power, s.cooldown, s.mana_cost,
+           s.target_type, s.status_effect, s.effect_duration, s.effect_seconds, ps.level
+    FROM player_skills ps
+    JOIN skills s ON s.id = ps.skill_id
+"#;
+
+/// All skills a player has learned
+pub async fn get_player_skills(pool: &PgPool, player_id: i32) -> Result<Vec<PlayerSkill>, sqlx::Error> {
+    sqlx::query_as::<_, PlayerSkill>(&format!("{} WHERE ps.player_id = $1 ORDER BY s.name", PLAYER_SKILL_COLUMNS))
+        .bind(player_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// A single learned skill, or `None` if the player doesn't know it
+pub async fn get_player_skill(
+    pool: &PgPool,
+    player_id: i32,
+    skill_id: i32,
+) -> Result<Option<PlayerSkill>, sqlx::Error> {
+    sqlx::query_as::<_, PlayerSkill>(&format!(
+        "{} WHERE ps.player_id = $1 AND ps.skill_id = $2",
+        PLAYER_SKILL_COLUMNS
+    ))
+    .bind(player_id)
+    .bind(skill_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Teach a player a skill. Learning a known skill again is a no-op.
+pub async fn learn_skill<'e, E: PgExecutor<'e>>(executor: E, player_id: i32, skill_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO player_skills (player_id, skill_id)
+        VALUES ($1, $2)
+        ON CONFLICT (player_id, skill_id) DO NOTHING
+        "#,
+    )
+    .bind(player_id)
+    .bind(skill_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+const SKILL_COLUMNS: &str = r#"
+    SELECT id, name, description, skill_type, power, cooldown, mana_cost, target_type, status_effect,
+           effect_duration, effect_seconds
+    FROM skills
+"#;
+
+/// Every skill there is, by name
+pub async fn list_skills(pool: &PgPool) -> Result<Vec<Skill>, sqlx::Error> {
+    sqlx::query_as::<_, Skill>(&format!("{} ORDER BY name", SKILL_COLUMNS))
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_skill<'e, E: PgExecutor<'e>>(executor: E, skill_id: i32) -> Result<Option<Skill>, sqlx::Error> {
+    sqlx::query_as::<_, Skill>(&format!("{} WHERE id = $1", SKILL_COLUMNS))
+        .bind(skill_id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Add a skill, ignoring `skill.id`. Fails with a unique violation if the name is taken.
+pub async fn create_skill<'e, E: PgExecutor<'e>>(executor: E, skill: &Skill) -> Result<Skill, sqlx::Error> {
+    sqlx::query_as::<_, Skill>(
+        r#"
+        INSERT INTO skills (name, description, skill_type, power, cooldown, mana_cost, target_type,
+                            status_effect, effect_duration, effect_seconds)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id, name, description, skill_type, power, cooldown, mana_cost, target_type, status_effect,
+                  effect_duration, effect_seconds
+        "#,
+    )
+    .bind(&skill.name)
+    .bind(&skill.description)
+    .bind(skill.skill_type.as_str())
+    .bind(skill.power)
+    .bind(skill.cooldown)
+    .bind(skill.mana_cost)
+    .bind(skill.target_type.as_str())
+    .bind(skill.status_effect.map(|kind| kind.as_str()))
+    .bind(skill.effect_duration)
+    .bind(skill.effect_seconds)
+    .fetch_one(executor)
+    .await
+}
+
+/// Overwrite the skill with ID `skill.id`. Returns `None` if there is none.
+pub async fn update_skill<'e, E: PgExecutor<'e>>(executor: E, skill: &Skill) -> Result<Option<Skill>, sqlx::Error> {
+    sqlx::query_as::<_, Skill>(
+        r#"
+        UPDATE skills
+        SET name = $2, description = $3, skill_type = $4, power = $5, cooldown = $6, mana_cost = $7,
+            target_type = $8, status_effect = $9, effect_duration = $10,
+            effect_seconds = $11, updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, name, description, skill_type, power, cooldown, mana_cost, target_type, status_effect,
+                  effect_duration, effect_seconds
+        "#,
+    )
+    .bind(skill.id)
+    .bind(&skill.name)
+    .bind(&skill.description)
+    .bind(skill.skill_type.as_str())
+    .bind(skill.power)
+    .bind(skill.cooldown)
+    .bind(skill.mana_cost)
+    .bind(skill.target_type.as_str())
+    .bind(skill.status_effect.map(|kind| kind.as_str()))
+    .bind(skill.effect_duration)
+    .bind(skill.effect_seconds)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Remove a skill; players who learned it forget it. Returns whether there was such a skill.
+pub async fn delete_skill<'e, E: PgExecutor<'e>>(executor: E, skill_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM skills WHERE id = $1")
+        .bind(skill_id)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
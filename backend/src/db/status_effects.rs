@@ -0,0 +1,93 @@
+use sqlx::{FromRow, PgConnection, PgExecutor};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::models::{EffectDuration, EffectKind, StatusEffect, StatusEffects};
+
+#[derive(FromRow)]
+struct StatusEffectRow {
+    effect_id: String,
+    name: String,
+    kind: EffectKind,
+    magnitude: i32,
+    stacks: i32,
+    source: String,
+    expires_at: Option<i64>,
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Load the long-lived effects still active on a player
+pub async fn get_status_effects<'e, E: PgExecutor<'e>>(executor: E, player_id: i32) -> Result<StatusEffects, sqlx::Error> {
+    let now = now_unix();
+    let rows = sqlx::query_as::<_, StatusEffectRow>(
+        r#"
+        SELECT effect_id, name, kind, magnitude, stacks, source, expires_at
+        FROM player_status_effects
+        WHERE player_id = $1 AND (expires_at IS NULL OR expires_at > $2)
+        "#,
+    )
+    .bind(player_id)
+    .bind(now)
+    .fetch_all(executor)
+    .await?;
+
+    let active = rows
+        .into_iter()
+        .map(|row| {
+            let duration = match row.expires_at {
+                Some(expires_at) => EffectDuration::Seconds((expires_at - now).max(0) as u32),
+                None => EffectDuration::Permanent,
+            };
+            let mut effect = StatusEffect::new(&row.effect_id, &row.name, row.kind, row.magnitude, duration, &row.source);
+            effect.stacks = row.stacks.max(1) as u32;
+            effect
+        })
+        .collect();
+
+    Ok(StatusEffects { active })
+}
+
+/// Replace a player's stored effects. Turn-based effects end with their encounter and
+/// item curses are derived from equipment, so neither is written. Run it in the transaction
+/// that saves the rest of the player, so the two can't disagree.
+pub async fn save_status_effects(conn: &mut PgConnection, player_id: i32, effects: &StatusEffects) -> Result<(), sqlx::Error> {
+    let now = now_unix();
+
+    sqlx::query("DELETE FROM player_status_effects WHERE player_id = $1")
+        .bind(player_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for effect in &effects.active {
+        let expires_at = match effect.duration {
+            EffectDuration::Turns(_) => continue,
+            EffectDuration::Seconds(seconds) => Some(now + seconds as i64),
+            EffectDuration::Permanent => None,
+        };
+        if effect.source.starts_with("item:") {
+            continue;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO player_status_effects (player_id, effect_id, name, kind, magnitude, stacks, source, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(player_id)
+        .bind(&effect.id)
+        .bind(&effect.name)
+        .bind(effect.kind.as_str())
+        .bind(effect.magnitude)
+        .bind(effect.stacks as i32)
+        .bind(&effect.source)
+        .bind(expires_at)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,635 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::db::audit::record_audit_entry;
+use crate::db::regions::{add_portal, lock_regions, read_regions, remove_region, save_admin_region, RegionSaveError};
+use crate::db::{character_classes, skills};
+use crate::engine::content::{ContentChange, LiveContent, ReloadOutcome, ReloadTrigger};
+use crate::loader::artifacts::{read_artifacts, validate_artifact};
+use crate::loader::items::{item_file_contents, read_items, validate_item};
+use crate::models::{Artifact, CharacterClass, ItemDefinition, Portal, Region, Skill};
+
+#[derive(Debug)]
+pub enum AdminError {
+    NotFound(String),
+    /// The ID or name is taken, or the thing can't be changed this way
+    Conflict(String),
+    /// Failed the checks the content loaders run, or the content they'd load afterwards did
+    Invalid(Vec<String>),
+    /// Reading or writing content files failed
+    Content(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminError::NotFound(what) => write!(f, "No {}", what),
+            AdminError::Conflict(reason) => f.write_str(reason),
+            AdminError::Invalid(problems) => write!(f, "{} problem(s): {}", problems.len(), problems.join("; ")),
+            AdminError::Content(e) => write!(f, "Content error: {}", e),
+            AdminError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+impl From<sqlx::Error> for AdminError {
+    fn from(e: sqlx::Error) -> Self {
+        AdminError::Database(e)
+    }
+}
+
+impl From<RegionSaveError> for AdminError {
+    fn from(e: RegionSaveError) -> Self {
+        match e {
+            RegionSaveError::OriginConflict { .. } => AdminError::Conflict(e.to_string()),
+            RegionSaveError::Database(e) => AdminError::Database(e),
+        }
+    }
+}
+
+impl AdminError {
+    fn content(e: impl fmt::Display) -> Self {
+        AdminError::Content(format!("{:#}", e))
+    }
+
+    /// A unique violation means the name is taken; anything else is passed on
+    fn taken(e: sqlx::Error, what: String) -> Self {
+        match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => AdminError::Conflict(format!("{} is taken", what)),
+            e => AdminError::Database(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+        }
+    }
+}
+
+/// Record a change made by `player_id`. `before` is `None` for creations, `after` for deletions.
+async fn audit<'e, E: sqlx::PgExecutor<'e>, T: Serialize>(
+    executor: E,
+    player_id: i32,
+    action: AuditAction,
+    entity: &str,
+    entity_id: &str,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Result<(), AdminError> {
+    let to_value = |value: Option<&T>| value.map(serde_json::to_value).transpose().map_err(AdminError::content);
+    let (before, after) = (to_value(before)?, to_value(after)?);
+    record_audit_entry(executor, player_id, action.as_str(), entity, entity_id, before.as_ref(), after.as_ref()).await?;
+    Ok(())
+}
+
+/// Write content files and reload. Nothing is kept unless the content loads and syncs
+/// afterwards; the problems found otherwise are passed back.
+async fn edit_content(pool: &PgPool, content: &LiveContent, changes: Vec<ContentChange>) -> Result<(), AdminError> {
+    match content.edit(pool, changes).await.outcome {
+        ReloadOutcome::Applied { .. } => Ok(()),
+        ReloadOutcome::Rejected { problems } => Err(AdminError::Invalid(problems)),
+    }
+}
+
+fn check_path_id(path_id: &str, body_id: &str) -> Result<(), AdminError> {
+    if path_id != body_id {
+        return Err(AdminError::Invalid(vec![format!(
+            "id '{}' doesn't match '{}' in the path; IDs can't be changed",
+            body_id, path_id
+        )]));
+    }
+    Ok(())
+}
+
+fn check_problems(problems: Vec<String>) -> Result<(), AdminError> {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(AdminError::Invalid(problems))
+    }
+}
+
+// Items and artifacts live in `content/`, one per file, and are written back to the file they
+// came from. New ones get `<id>.toml`.
+
+fn item_path(content: &LiveContent, item_id: &str) -> Result<Option<PathBuf>, AdminError> {
+    let dir = content.dir().join("items");
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let (items, _) = read_items(&dir).map_err(AdminError::content)?;
+    Ok(items.into_iter().find(|source| source.item.id == item_id).map(|source| source.path))
+}
+
+pub fn get_item(content: &LiveContent, item_id: &str) -> Result<ItemDefinition, AdminError> {
+    content
+        .snapshot()
+        .items
+        .iter()
+        .find(|item| item.id == item_id)
+        .cloned()
+        .ok_or_else(|| AdminError::NotFound(format!("item '{}'", item_id)))
+}
+
+pub async fn create_item(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    item: ItemDefinition,
+) -> Result<ItemDefinition, AdminError> {
+    check_problems(validate_item(&item))?;
+    if get_item(content, &item.id).is_ok() || item_path(content, &item.id)?.is_some() {
+        return Err(AdminError::Conflict(format!("item '{}' already exists", item.id)));
+    }
+    let path = content.dir().join("items").join(format!("{}.toml", item.id));
+    if path.exists() {
+        return Err(AdminError::Conflict(format!("{} already exists", path.display())));
+    }
+    let contents = item_file_contents(&path, &item).map_err(AdminError::content)?;
+    edit_content(pool, content, vec![ContentChange { path, contents: Some(contents) }]).await?;
+    audit(pool, player_id, AuditAction::Create, "item", &item.id, None, Some(&item)).await?;
+    Ok(item)
+}
+
+pub async fn update_item(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    item_id: &str,
+    item: ItemDefinition,
+) -> Result<ItemDefinition, AdminError> {
+    check_path_id(item_id, &item.id)?;
+    check_problems(validate_item(&item))?;
+    let before = get_item(content, item_id)?;
+    let path = item_path(content, item_id)?.ok_or_else(|| AdminError::NotFound(format!("item '{}'", item_id)))?;
+    let contents = item_file_contents(&path, &item).map_err(AdminError::content)?;
+    edit_content(pool, content, vec![ContentChange { path, contents: Some(contents) }]).await?;
+    audit(pool, player_id, AuditAction::Update, "item", item_id, Some(&before), Some(&item)).await?;
+    Ok(item)
+}
+
+pub async fn delete_item(pool: &PgPool, content: &LiveContent, player_id: i32, item_id: &str) -> Result<(), AdminError> {
+    let before = get_item(content, item_id)?;
+    let path = item_path(content, item_id)?.ok_or_else(|| AdminError::NotFound(format!("item '{}'", item_id)))?;
+    edit_content(pool, content, vec![ContentChange { path, contents: None }]).await?;
+    audit(pool, player_id, AuditAction::Delete, "item", item_id, Some(&before), None).await
+}
+
+fn artifact_path(content: &LiveContent, artifact_id: &str) -> Result<Option<PathBuf>, AdminError> {
+    let dir = content.dir().join("artifacts");
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let (artifacts, _) = read_artifacts(&dir).map_err(AdminError::content)?;
+    Ok(artifacts.into_iter().find(|source| source.artifact.id == artifact_id).map(|source| source.path))
+}
+
+pub fn get_artifact(content: &LiveContent, artifact_id: &str) -> Result<Artifact, AdminError> {
+    content
+        .snapshot()
+        .artifacts
+        .iter()
+        .find(|artifact| artifact.id == artifact_id)
+        .cloned()
+        .ok_or_else(|| AdminError::NotFound(format!("artifact '{}'", artifact_id)))
+}
+
+pub async fn create_artifact(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    artifact: Artifact,
+) -> Result<Artifact, AdminError> {
+    check_problems(validate_artifact(&artifact))?;
+    if get_artifact(content, &artifact.id).is_ok() || artifact_path(content, &artifact.id)?.is_some() {
+        return Err(AdminError::Conflict(format!("artifact '{}' already exists", artifact.id)));
+    }
+    let path = content.dir().join("artifacts").join(format!("{}.toml", artifact.id));
+    if path.exists() {
+        return Err(AdminError::Conflict(format!("{} already exists", path.display())));
+    }
+    let contents = toml::to_string_pretty(&artifact).map_err(AdminError::content)?;
+    edit_content(pool, content, vec![ContentChange { path, contents: Some(contents) }]).await?;
+    audit(pool, player_id, AuditAction::Create, "artifact", &artifact.id, None, Some(&artifact)).await?;
+    Ok(artifact)
+}
+
+pub async fn update_artifact(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    artifact_id: &str,
+    artifact: Artifact,
+) -> Result<Artifact, AdminError> {
+    check_path_id(artifact_id, &artifact.id)?;
+    check_problems(validate_artifact(&artifact))?;
+    let before = get_artifact(content, artifact_id)?;
+    let path = artifact_path(content, artifact_id)?
+        .ok_or_else(|| AdminError::NotFound(format!("artifact '{}'", artifact_id)))?;
+    let contents = toml::to_string_pretty(&artifact).map_err(AdminError::content)?;
+    edit_content(pool, content, vec![ContentChange { path, contents: Some(contents) }]).await?;
+    audit(pool, player_id, AuditAction::Update, "artifact", artifact_id, Some(&before), Some(&artifact)).await?;
+    Ok(artifact)
+}
+
+/// Artifacts a character class starts with can't be deleted until the class lets go of them
+pub async fn delete_artifact(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    artifact_id: &str,
+) -> Result<(), AdminError> {
+    let before = get_artifact(content, artifact_id)?;
+    let path = artifact_path(content, artifact_id)?
+        .ok_or_else(|| AdminError::NotFound(format!("artifact '{}'", artifact_id)))?;
+    let holders: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM character_classes WHERE $1 = ANY(starting_artifacts) ORDER BY name",
+    )
+    .bind(artifact_id)
+    .fetch_all(pool)
+    .await?;
+    if !holders.is_empty() {
+        return Err(AdminError::Conflict(format!(
+            "artifact '{}' is a starting artifact of {}",
+            artifact_id,
+            holders.join(", ")
+        )));
+    }
+    edit_content(pool, content, vec![ContentChange { path, contents: None }]).await?;
+    audit(pool, player_id, AuditAction::Delete, "artifact", artifact_id, Some(&before), None).await
+}
+
+// Regions and their portals live in the database, like the rest of the running world. Each
+// change is saved in the same transaction as its audit entry, and only if the map it leaves
+// passes the checks a content reload runs. A region an admin saves is stored as `admin` from
+// then on, so its file under `content/regions` no longer overwrites it; region files are for
+// `regions import` and `regions export`.
+
+/// Open a transaction that keeps anyone else from saving regions, with every stored region by ID
+async fn lock_stored_regions(
+    pool: &PgPool,
+) -> Result<(Transaction<'static, Postgres>, BTreeMap<String, Region>), AdminError> {
+    let mut tx = pool.begin().await?;
+    lock_regions(&mut tx).await?;
+    let regions = read_regions(&mut tx).await?.into_iter().map(|region| (region.id.clone(), region)).collect();
+    Ok((tx, regions))
+}
+
+/// Commit region changes if the map they leave is sound, then put that map live
+async fn commit_regions(
+    pool: &PgPool,
+    content: &LiveContent,
+    mut tx: Transaction<'static, Postgres>,
+) -> Result<(), AdminError> {
+    content.check_stored_regions(&mut tx).await.map_err(|e| AdminError::Invalid(e.problems))?;
+    tx.commit().await?;
+    let record = content.refresh_regions(pool, ReloadTrigger::Edit).await;
+    if let ReloadOutcome::Rejected { problems } = record.outcome {
+        eprintln!("⚠️ Regions were saved but the map wasn't refreshed: {}", problems.join("; "));
+    }
+    Ok(())
+}
+
+fn region_not_found(region_id: &str) -> AdminError {
+    AdminError::NotFound(format!("region '{}'", region_id))
+}
+
+/// Portal IDs are unique across every region, so `portals`, about to be saved in `region_id`,
+/// can't share one with each other or with another region's portals
+fn check_portal_ids<'a>(
+    regions: &BTreeMap<String, Region>,
+    region_id: &str,
+    portals: impl IntoIterator<Item = &'a Portal>,
+) -> Result<(), AdminError> {
+    let mut seen = HashSet::new();
+    for portal in portals {
+        let elsewhere = regions
+            .values()
+            .filter(|region| region.id != region_id)
+            .flat_map(|region| &region.portals)
+            .any(|other| other.id == portal.id);
+        if elsewhere || !seen.insert(portal.id.as_str()) {
+            return Err(AdminError::Conflict(format!("portal '{}' already exists", portal.id)));
+        }
+    }
+    Ok(())
+}
+
+/// Region IDs name their file when regions are exported, so they keep to characters every file
+/// system takes
+fn check_region_id(region_id: &str) -> Result<(), AdminError> {
+    let valid = !region_id.is_empty()
+        && region_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(AdminError::Invalid(vec![format!(
+            "id '{}' must be non-empty letters, digits, underscores and hyphens",
+            region_id
+        )]));
+    }
+    Ok(())
+}
+
+pub fn get_region(content: &LiveContent, region_id: &str) -> Result<Region, AdminError> {
+    content.map().get_region(region_id).cloned().ok_or_else(|| region_not_found(region_id))
+}
+
+/// A portal into a new region from one that already exists, since a region nothing leads into
+/// is turned away
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionEntrance {
+    pub from_region: String,
+    pub portal: Portal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewRegion {
+    pub region: Region,
+    #[serde(default)]
+    pub entrances: Vec<RegionEntrance>,
+}
+
+/// Add a region. Its entrances are added to the regions they lead out of on the new region's
+/// behalf, so they go when it does and the regions they are in stay as they were stored.
+pub async fn create_region(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    new: NewRegion,
+) -> Result<Region, AdminError> {
+    let NewRegion { region, entrances } = new;
+    check_region_id(&region.id)?;
+    let (mut tx, regions) = lock_stored_regions(pool).await?;
+    if regions.contains_key(&region.id) {
+        return Err(AdminError::Conflict(format!("region '{}' already exists", region.id)));
+    }
+    for entrance in &entrances {
+        if entrance.portal.leads_to != region.id {
+            return Err(AdminError::Invalid(vec![format!(
+                "entrance '{}' leads to '{}', not to the new region",
+                entrance.portal.id, entrance.portal.leads_to
+            )]));
+        }
+        if !regions.contains_key(&entrance.from_region) {
+            return Err(region_not_found(&entrance.from_region));
+        }
+    }
+    let entrance_portals = entrances.iter().map(|entrance| &entrance.portal);
+    check_portal_ids(&regions, &region.id, region.portals.iter().chain(entrance_portals))?;
+
+    save_admin_region(&mut tx, &region).await?;
+    audit(&mut *tx, player_id, AuditAction::Create, "region", &region.id, None, Some(&region)).await?;
+    for entrance in &entrances {
+        add_portal(&mut tx, &entrance.from_region, &entrance.portal, &region.id).await?;
+        audit(&mut *tx, player_id, AuditAction::Create, "portal", &entrance.portal.id, None, Some(&entrance.portal)).await?;
+    }
+    commit_regions(pool, content, tx).await?;
+    Ok(region)
+}
+
+/// Replace a region, portals included
+pub async fn update_region(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    region_id: &str,
+    region: Region,
+) -> Result<Region, AdminError> {
+    check_path_id(region_id, &region.id)?;
+    let (mut tx, regions) = lock_stored_regions(pool).await?;
+    let before = regions.get(region_id).ok_or_else(|| region_not_found(region_id))?;
+    check_portal_ids(&regions, region_id, &region.portals)?;
+
+    save_admin_region(&mut tx, &region).await?;
+    audit(&mut *tx, player_id, AuditAction::Update, "region", region_id, Some(before), Some(&region)).await?;
+    commit_regions(pool, content, tx).await?;
+    Ok(region)
+}
+
+/// Remove a region along with the portals leading into it. Players inside are moved to the
+/// nexus.
+pub async fn delete_region(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    region_id: &str,
+) -> Result<(), AdminError> {
+    let (mut tx, regions) = lock_stored_regions(pool).await?;
+    let before = regions.get(region_id).ok_or_else(|| region_not_found(region_id))?;
+    let entered = remove_region(&mut tx, region_id).await?.ok_or_else(|| region_not_found(region_id))?;
+
+    audit(&mut *tx, player_id, AuditAction::Delete, "region", region_id, Some(before), None).await?;
+    for other in entered.iter().filter_map(|id| regions.get(id)) {
+        let mut after = other.clone();
+        after.portals.retain(|portal| portal.leads_to != region_id);
+        audit(&mut *tx, player_id, AuditAction::Update, "region", &other.id, Some(other), Some(&after)).await?;
+    }
+    commit_regions(pool, content, tx).await
+}
+
+/// Change the portals of a region and record it as an edit of the portal
+async fn edit_portals(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    region_id: &str,
+    action: AuditAction,
+    portal_id: &str,
+    change: impl FnOnce(&mut Vec<Portal>) -> Result<(), AdminError>,
+) -> Result<(), AdminError> {
+    let (mut tx, regions) = lock_stored_regions(pool).await?;
+    let stored = regions.get(region_id).ok_or_else(|| region_not_found(region_id))?;
+    let mut region = stored.clone();
+    change(&mut region.portals)?;
+    check_portal_ids(&regions, region_id, &region.portals)?;
+    save_admin_region(&mut tx, &region).await?;
+
+    let find = |region: &Region| region.portals.iter().find(|portal| portal.id == portal_id).cloned();
+    let (before, after) = (find(stored), find(&region));
+    audit(&mut *tx, player_id, action, "portal", portal_id, before.as_ref(), after.as_ref()).await?;
+    commit_regions(pool, content, tx).await
+}
+
+/// Add a portal out of a region. Portal IDs are unique across every region.
+pub async fn create_portal(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    region_id: &str,
+    portal: Portal,
+) -> Result<Portal, AdminError> {
+    let (portal_id, added) = (portal.id.clone(), portal.clone());
+    edit_portals(pool, content, player_id, region_id, AuditAction::Create, &portal_id, move |portals| {
+        portals.push(added);
+        Ok(())
+    })
+    .await?;
+    Ok(portal)
+}
+
+pub async fn update_portal(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    region_id: &str,
+    portal_id: &str,
+    portal: Portal,
+) -> Result<Portal, AdminError> {
+    check_path_id(portal_id, &portal.id)?;
+    let updated = portal.clone();
+    edit_portals(pool, content, player_id, region_id, AuditAction::Update, portal_id, |portals| {
+        let existing = portals
+            .iter_mut()
+            .find(|p| p.id == portal_id)
+            .ok_or_else(|| AdminError::NotFound(format!("portal '{}' in region '{}'", portal_id, region_id)))?;
+        *existing = updated;
+        Ok(())
+    })
+    .await?;
+    Ok(portal)
+}
+
+pub async fn delete_portal(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    region_id: &str,
+    portal_id: &str,
+) -> Result<(), AdminError> {
+    edit_portals(pool, content, player_id, region_id, AuditAction::Delete, portal_id, |portals| {
+        let count = portals.len();
+        portals.retain(|p| p.id != portal_id);
+        if portals.len() == count {
+            return Err(AdminError::NotFound(format!("portal '{}' in region '{}'", portal_id, region_id)));
+        }
+        Ok(())
+    })
+    .await
+}
+
+// Skills and character classes live in the database. Each change is saved in the same
+// transaction as its audit entry.
+
+pub async fn create_skill(pool: &PgPool, player_id: i32, skill: Skill) -> Result<Skill, AdminError> {
+    check_problems(skill.problems())?;
+    let mut tx = pool.begin().await?;
+    let created = skills::create_skill(&mut *tx, &skill)
+        .await
+        .map_err(|e| AdminError::taken(e, format!("skill name '{}'", skill.name)))?;
+    let id = created.id.to_string();
+    audit(&mut *tx, player_id, AuditAction::Create, "skill", &id, None, Some(&created)).await?;
+    tx.commit().await?;
+    Ok(created)
+}
+
+pub async fn update_skill(pool: &PgPool, player_id: i32, skill_id: i32, mut skill: Skill) -> Result<Skill, AdminError> {
+    check_problems(skill.problems())?;
+    skill.id = skill_id;
+    let mut tx = pool.begin().await?;
+    let before = skills::get_skill(&mut *tx, skill_id)
+        .await?
+        .ok_or_else(|| AdminError::NotFound(format!("skill {}", skill_id)))?;
+    let updated = skills::update_skill(&mut *tx, &skill)
+        .await
+        .map_err(|e| AdminError::taken(e, format!("skill name '{}'", skill.name)))?
+        .ok_or_else(|| AdminError::NotFound(format!("skill {}", skill_id)))?;
+    let id = skill_id.to_string();
+    audit(&mut *tx, player_id, AuditAction::Update, "skill", &id, Some(&before), Some(&updated)).await?;
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Players who learned the skill forget it
+pub async fn delete_skill(pool: &PgPool, player_id: i32, skill_id: i32) -> Result<(), AdminError> {
+    let mut tx = pool.begin().await?;
+    let before = skills::get_skill(&mut *tx, skill_id)
+        .await?
+        .ok_or_else(|| AdminError::NotFound(format!("skill {}", skill_id)))?;
+    skills::delete_skill(&mut *tx, skill_id).await?;
+    let id = skill_id.to_string();
+    audit(&mut *tx, player_id, AuditAction::Delete, "skill", &id, Some(&before), None).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// The class's own problems plus starting artifacts missing from the content
+fn class_problems(content: &LiveContent, class: &CharacterClass) -> Vec<String> {
+    let snapshot = content.snapshot();
+    let mut problems = class.problems();
+    for artifact_id in &class.starting_artifacts {
+        if !snapshot.artifacts.iter().any(|artifact| &artifact.id == artifact_id) {
+            problems.push(format!("starting artifact '{}' is not in the content", artifact_id));
+        }
+    }
+    problems
+}
+
+pub async fn create_character_class(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    class: CharacterClass,
+) -> Result<CharacterClass, AdminError> {
+    check_problems(class_problems(content, &class))?;
+    let mut tx = pool.begin().await?;
+    let created = character_classes::create_character_class(&mut *tx, &class)
+        .await
+        .map_err(|e| AdminError::taken(e, format!("class name '{}'", class.name)))?;
+    let id = created.id.to_string();
+    audit(&mut *tx, player_id, AuditAction::Create, "character_class", &id, None, Some(&created)).await?;
+    tx.commit().await?;
+    Ok(created)
+}
+
+pub async fn update_character_class(
+    pool: &PgPool,
+    content: &LiveContent,
+    player_id: i32,
+    class_id: Uuid,
+    mut class: CharacterClass,
+) -> Result<CharacterClass, AdminError> {
+    check_problems(class_problems(content, &class))?;
+    class.id = class_id;
+    let mut tx = pool.begin().await?;
+    let before = character_classes::get_character_class(&mut *tx, class_id)
+        .await?
+        .ok_or_else(|| AdminError::NotFound(format!("character class {}", class_id)))?;
+    let updated = character_classes::update_character_class(&mut *tx, &class)
+        .await
+        .map_err(|e| AdminError::taken(e, format!("class name '{}'", class.name)))?
+        .ok_or_else(|| AdminError::NotFound(format!("character class {}", class_id)))?;
+    let id = class_id.to_string();
+    audit(&mut *tx, player_id, AuditAction::Update, "character_class", &id, Some(&before), Some(&updated)).await?;
+    tx.commit().await?;
+    Ok(updated)
+}
+
+pub async fn delete_character_class(pool: &PgPool, player_id: i32, class_id: Uuid) -> Result<(), AdminError> {
+    let mut tx = pool.begin().await?;
+    let before = character_classes::get_character_class(&mut *tx, class_id)
+        .await?
+        .ok_or_else(|| AdminError::NotFound(format!("character class {}", class_id)))?;
+    character_classes::delete_character_class(&mut *tx, class_id).await?;
+    let id = class_id.to_string();
+    audit(&mut *tx, player_id, AuditAction::Delete, "character_class", &id, Some(&before), None).await?;
+    tx.commit().await?;
+    Ok(())
+}
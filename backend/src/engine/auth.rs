@@ -0,0 +1,461 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::db::players::{create_player, get_credentials, get_player_role};
+use crate::db::sessions::{
+    delete_expired_tokens, insert_refresh_token, revoke_player_tokens, rotated_token_owner, take_refresh_token,
+    Revocation,
+};
+use crate::engine::oidc::OidcVerifier;
+use crate::models::Role;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+/// Longer passwords are refused rather than hashed, so nobody can make the server hash megabytes
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// Usernames are 3 to 32 letters, digits, `_` or `-`
+    InvalidUsername,
+    InvalidPassword,
+    InvalidEmail,
+    UsernameTaken,
+    EmailTaken,
+    /// Unknown username or wrong password; which one is not given away
+    InvalidCredentials,
+    MissingToken,
+    InvalidToken,
+    TokenExpired,
+    /// Signed in, but without the role the route needs
+    MissingRole(Role),
+    /// The identity provider's signing keys couldn't be fetched
+    KeysUnavailable(String),
+    Hashing(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::InvalidUsername => {
+                write!(f, "Usernames are 3 to 32 characters of letters, digits, '_' and '-'")
+            }
+            AuthError::InvalidPassword => write!(
+                f,
+                "Passwords must be between {} and {} characters long",
+                MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+            ),
+            AuthError::InvalidEmail => write!(f, "That doesn't look like an email address"),
+            AuthError::UsernameTaken => write!(f, "That username is taken"),
+            AuthError::EmailTaken => write!(f, "That email address is already registered"),
+            AuthError::InvalidCredentials => write!(f, "Wrong username or password"),
+            AuthError::MissingToken => write!(f, "Missing bearer token"),
+            AuthError::InvalidToken => write!(f, "Invalid token"),
+            AuthError::TokenExpired => write!(f, "Token expired"),
+            AuthError::MissingRole(role) => write!(f, "This needs the {} role", role),
+            AuthError::KeysUnavailable(e) => write!(f, "Signing keys unavailable: {}", e),
+            AuthError::Hashing(e) => write!(f, "Password hashing failed: {}", e),
+            AuthError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<sqlx::Error> for AuthError {
+    fn from(e: sqlx::Error) -> Self {
+        AuthError::Database(e)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for AuthError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        match e.kind() {
+            ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+            _ => AuthError::InvalidToken,
+        }
+    }
+}
+
+/// Who a request comes from, whichever kind of token they sent
+#[derive(Debug, Clone, Serialize)]
+pub struct Principal {
+    pub player_id: i32,
+    pub roles: Vec<Role>,
+}
+
+impl Principal {
+    /// Whether the principal holds `role` or one that includes it
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.iter().any(|held| *held >= role)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TokenKind {
+    Access,
+    Refresh,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// The player ID
+    sub: String,
+    iat: u64,
+    exp: u64,
+    kind: TokenKind,
+    /// Access tokens only: the player's role when the token was issued
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    roles: Vec<Role>,
+    /// Refresh tokens only: the `refresh_tokens` row behind it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jti: Option<Uuid>,
+}
+
+/// What login, registration and refreshing hand back
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenPair {
+    pub player_id: i32,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: &'static str,
+    /// Seconds until the access token expires
+    pub expires_in: u64,
+}
+
+/// The key our tokens are signed with, how long they last, and the identity provider whose
+/// tokens are accepted too, if there is one
+pub struct AuthConfig {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    validation: Validation,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+    oidc: Option<OidcVerifier>,
+}
+
+pub type SharedAuth = Arc<AuthConfig>;
+
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl AuthConfig {
+    pub fn new(secret: &[u8], access_ttl: Duration, refresh_ttl: Duration) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        validation.leeway = 0;
+        AuthConfig {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            validation,
+            access_ttl,
+            refresh_ttl,
+            oidc: None,
+        }
+    }
+
+    /// Also accept access tokens from an identity provider
+    pub fn with_oidc(mut self, oidc: OidcVerifier) -> Self {
+        self.oidc = Some(oidc);
+        self
+    }
+
+    fn sign(&self, player_id: i32, kind: TokenKind, roles: Vec<Role>, jti: Option<Uuid>) -> Result<String, AuthError> {
+        let ttl = match kind {
+            TokenKind::Access => self.access_ttl,
+            TokenKind::Refresh => self.refresh_ttl,
+        };
+        let iat = now_unix();
+        let claims = Claims { sub: player_id.to_string(), iat, exp: iat + ttl.as_secs(), kind, roles, jti };
+        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding).map_err(|_| AuthError::InvalidToken)
+    }
+
+    fn verify(&self, token: &str, kind: TokenKind) -> Result<(i32, Claims), AuthError> {
+        let claims = decode::<Claims>(token, &self.decoding, &self.validation)?.claims;
+        if claims.kind != kind {
+            return Err(AuthError::InvalidToken);
+        }
+        let player_id = claims.sub.parse().map_err(|_| AuthError::InvalidToken)?;
+        Ok((player_id, claims))
+    }
+
+    /// The player one of our own access tokens was issued to
+    pub fn verify_access(&self, access_token: &str) -> Result<Principal, AuthError> {
+        let (player_id, claims) = self.verify(access_token, TokenKind::Access)?;
+        let roles = if claims.roles.is_empty() { vec![Role::Player] } else { claims.roles };
+        Ok(Principal { player_id, roles })
+    }
+
+    /// Resolve a bearer token: one of ours, signed with the shared secret, or one from the
+    /// identity provider, signed with its keys
+    pub async fn authenticate(&self, pool: &PgPool, token: &str) -> Result<Principal, AuthError> {
+        let header = decode_header(token)?;
+        match (&self.oidc, header.alg) {
+            (_, Algorithm::HS256) => self.verify_access(token),
+            (Some(oidc), _) => oidc.authenticate(pool, token).await,
+            (None, _) => Err(AuthError::InvalidToken),
+        }
+    }
+
+    /// Sign a fresh access token and record and sign a new refresh token
+    async fn issue(&self, conn: &mut PgConnection, player_id: i32, role: Role) -> Result<TokenPair, AuthError> {
+        let jti = Uuid::new_v4();
+        delete_expired_tokens(&mut *conn, player_id).await?;
+        insert_refresh_token(&mut *conn, jti, player_id, self.refresh_ttl).await?;
+        Ok(TokenPair {
+            player_id,
+            access_token: self.sign(player_id, TokenKind::Access, vec![role], None)?,
+            refresh_token: self.sign(player_id, TokenKind::Refresh, Vec::new(), Some(jti))?,
+            token_type: "Bearer",
+            expires_in: self.access_ttl.as_secs(),
+        })
+    }
+}
+
+fn validate_username(username: &str) -> Result<(), AuthError> {
+    let valid_chars = username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !(3..=32).contains(&username.len()) || !valid_chars {
+        return Err(AuthError::InvalidUsername);
+    }
+    Ok(())
+}
+
+fn validate_password(password: &str) -> Result<(), AuthError> {
+    if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&password.chars().count()) {
+        return Err(AuthError::InvalidPassword);
+    }
+    Ok(())
+}
+
+/// Hash a password with argon2 into a PHC string, off the async runtime
+pub async fn hash_password(password: &str) -> Result<String, AuthError> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| AuthError::Hashing(e.to_string()))
+    })
+    .await
+    .map_err(|e| AuthError::Hashing(e.to_string()))?
+}
+
+/// Whether `password` matches a stored hash. Anything that isn't an argon2 PHC string, like the
+/// placeholders of players made before passwords were hashed, matches nothing.
+pub async fn verify_password(password: &str, hash: &str) -> Result<bool, AuthError> {
+    let password = password.to_string();
+    let hash = hash.to_string();
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash)
+            .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+            .unwrap_or(false)
+    })
+    .await
+    .map_err(|e| AuthError::Hashing(e.to_string()))
+}
+
+/// A hash no password matches, checked against for unknown usernames and players without a
+/// password so they take as long to turn down as wrong passwords
+async fn dummy_hash() -> Result<&'static str, AuthError> {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    if let Some(hash) = DUMMY_HASH.get() {
+        return Ok(hash);
+    }
+    let hash = hash_password(&Uuid::new_v4().to_string()).await?;
+    Ok(DUMMY_HASH.get_or_init(|| hash))
+}
+
+/// Create a player and log them in
+pub async fn register(
+    pool: &PgPool,
+    auth: &AuthConfig,
+    username: &str,
+    email: Option<&str>,
+    password: &str,
+) -> Result<TokenPair, AuthError> {
+    let username = username.trim();
+    let email = email.map(str::trim).filter(|email| !email.is_empty());
+    validate_username(username)?;
+    validate_password(password)?;
+    if email.is_some_and(|email| !email.contains('@')) {
+        return Err(AuthError::InvalidEmail);
+    }
+
+    let password_hash = hash_password(password).await?;
+    let mut tx = pool.begin().await?;
+    let player_id = create_player(&mut *tx, username, email, &password_hash)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => match db.constraint() {
+                Some(constraint) if constraint.contains("email") => AuthError::EmailTaken,
+                _ => AuthError::UsernameTaken,
+            },
+            _ => AuthError::Database(e),
+        })?;
+    let tokens = auth.issue(&mut tx, player_id, Role::Player).await?;
+    tx.commit().await?;
+    Ok(tokens)
+}
+
+/// Check a username and password and issue a new pair of tokens
+pub async fn login(pool: &PgPool, auth: &AuthConfig, username: &str, password: &str) -> Result<TokenPair, AuthError> {
+    let credentials = get_credentials(pool, username.trim()).await?;
+    let hash = match credentials.as_ref().and_then(|c| c.password_hash.as_deref()) {
+        Some(hash) => hash,
+        None => dummy_hash().await?,
+    };
+    let matches = verify_password(password, hash).await?;
+    let Some(credentials) = credentials.filter(|c| matches && c.password_hash.is_some()) else {
+        return Err(AuthError::InvalidCredentials);
+    };
+    let mut conn = pool.acquire().await?;
+    auth.issue(&mut conn, credentials.id, credentials.role).await
+}
+
+/// Trade a refresh token for a new pair. Each refresh token works once; presenting one that was
+/// already traded in means it was copied, so every token the player holds is revoked. One given
+/// back at logout is just turned away, since clients retrying a refresh after logging out are
+/// common.
+pub async fn refresh(pool: &PgPool, auth: &AuthConfig, refresh_token: &str) -> Result<TokenPair, AuthError> {
+    let (player_id, claims) = auth.verify(refresh_token, TokenKind::Refresh)?;
+    let jti = claims.jti.ok_or(AuthError::InvalidToken)?;
+
+    let mut tx = pool.begin().await?;
+    match take_refresh_token(&mut *tx, jti, Revocation::Rotated).await? {
+        Some(owner) if owner == player_id => {}
+        _ => {
+            tx.rollback().await?;
+            if rotated_token_owner(pool, jti).await? == Some(player_id) {
+                revoke_player_tokens(pool, player_id, Revocation::Reuse).await?;
+            }
+            return Err(AuthError::InvalidToken);
+        }
+    }
+    // The role is looked up again so changes to it reach the player at the next refresh
+    let role = get_player_role(&mut *tx, player_id).await?.ok_or(AuthError::InvalidToken)?;
+    let tokens = auth.issue(&mut tx, player_id, role).await?;
+    tx.commit().await?;
+    Ok(tokens)
+}
+
+/// Revoke a refresh token so it can't be used again. Access tokens already handed out keep
+/// working until they expire.
+pub async fn logout(pool: &PgPool, auth: &AuthConfig, refresh_token: &str) -> Result<(), AuthError> {
+    let (_, claims) = auth.verify(refresh_token, TokenKind::Refresh)?;
+    let jti = claims.jti.ok_or(AuthError::InvalidToken)?;
+    take_refresh_token(pool, jti, Revocation::Logout).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> AuthConfig {
+        AuthConfig::new(b"test secret", Duration::from_secs(900), Duration::from_secs(3600))
+    }
+
+    fn expect_err<T: fmt::Debug>(result: Result<T, AuthError>) -> AuthError {
+        result.expect_err("expected the token to be turned away")
+    }
+
+    #[test]
+    fn tokens_only_verify_as_their_own_kind() {
+        let auth = auth();
+        let access = auth.sign(7, TokenKind::Access, vec![Role::GameMaster], None).unwrap();
+        let refresh = auth.sign(7, TokenKind::Refresh, Vec::new(), Some(Uuid::new_v4())).unwrap();
+
+        let principal = auth.verify_access(&access).unwrap();
+        assert_eq!(principal.player_id, 7);
+        assert!(principal.has_role(Role::Player) && principal.has_role(Role::GameMaster));
+        assert!(!principal.has_role(Role::Admin));
+        assert!(matches!(expect_err(auth.verify_access(&refresh)), AuthError::InvalidToken));
+
+        let (player_id, claims) = auth.verify(&refresh, TokenKind::Refresh).unwrap();
+        assert_eq!(player_id, 7);
+        assert!(claims.jti.is_some());
+        assert!(matches!(expect_err(auth.verify(&access, TokenKind::Refresh)), AuthError::InvalidToken));
+    }
+
+    #[test]
+    fn expired_and_foreign_tokens_are_refused() {
+        let auth = auth();
+        let iat = now_unix() - 120;
+        let claims =
+            Claims { sub: "7".to_string(), iat, exp: iat + 60, kind: TokenKind::Access, roles: Vec::new(), jti: None };
+        let expired = encode(&Header::new(Algorithm::HS256), &claims, &auth.encoding).unwrap();
+        assert!(matches!(expect_err(auth.verify_access(&expired)), AuthError::TokenExpired));
+
+        let other = AuthConfig::new(b"another secret", Duration::from_secs(900), Duration::from_secs(3600));
+        let forged = other.sign(7, TokenKind::Access, vec![Role::Admin], None).unwrap();
+        assert!(matches!(expect_err(auth.verify_access(&forged)), AuthError::InvalidToken));
+    }
+
+    // The rest need a database: `DATABASE_URL=... cargo test -- --ignored`
+
+    async fn player(pool: &PgPool) -> i32 {
+        create_player(pool, "rotator", None, "not a hash").await.unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a database"]
+    async fn refresh_tokens_rotate(pool: PgPool) {
+        let auth = auth();
+        let player_id = player(&pool).await;
+        let first = auth.issue(&mut pool.acquire().await.unwrap(), player_id, Role::Player).await.unwrap();
+
+        let second = refresh(&pool, &auth, &first.refresh_token).await.unwrap();
+        assert_eq!(second.player_id, player_id);
+        assert_ne!(second.refresh_token, first.refresh_token);
+        assert_eq!(auth.verify_access(&second.access_token).unwrap().roles, vec![Role::Player]);
+
+        let third = refresh(&pool, &auth, &second.refresh_token).await.unwrap();
+        assert_eq!(third.player_id, player_id);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a database"]
+    async fn reusing_a_rotated_token_revokes_every_token(pool: PgPool) {
+        let auth = auth();
+        let player_id = player(&pool).await;
+        let mut conn = pool.acquire().await.unwrap();
+        let stolen = auth.issue(&mut conn, player_id, Role::Player).await.unwrap();
+        let other_device = auth.issue(&mut conn, player_id, Role::Player).await.unwrap();
+        drop(conn);
+        let rotated = refresh(&pool, &auth, &stolen.refresh_token).await.unwrap();
+
+        assert!(matches!(expect_err(refresh(&pool, &auth, &stolen.refresh_token).await), AuthError::InvalidToken));
+        assert!(matches!(expect_err(refresh(&pool, &auth, &rotated.refresh_token).await), AuthError::InvalidToken));
+        assert!(matches!(expect_err(refresh(&pool, &auth, &other_device.refresh_token).await), AuthError::InvalidToken));
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a database"]
+    async fn refreshing_after_logout_revokes_nothing_else(pool: PgPool) {
+        let auth = auth();
+        let player_id = player(&pool).await;
+        let mut conn = pool.acquire().await.unwrap();
+        let logged_out = auth.issue(&mut conn, player_id, Role::Player).await.unwrap();
+        let other_device = auth.issue(&mut conn, player_id, Role::Player).await.unwrap();
+        drop(conn);
+        logout(&pool, &auth, &logged_out.refresh_token).await.unwrap();
+
+        assert!(matches!(expect_err(refresh(&pool, &auth, &logged_out.refresh_token).await), AuthError::InvalidToken));
+        assert!(refresh(&pool, &auth, &other_device.refresh_token).await.is_ok());
+    }
+}
@@ -0,0 +1,615 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::engine::skills::{self, SkillError};
+use crate::models::{EffectTick, Equipment, Player, PlayerSkill, StatusEffect, StatusEffects};
+
+/// In-memory store of encounters that are still being fought, keyed by encounter ID
+pub type CombatSessions = Arc<Mutex<HashMap<Uuid, ActiveEncounter>>>;
+
+/// One encounter, locked by whoever is playing a round of it until the round is saved
+pub type SharedEncounter = Arc<tokio::sync::Mutex<CombatEncounter>>;
+
+/// How long an encounter nobody plays a round of is kept before it is dropped. The player's
+/// health was saved after the last round, so dropping it loses nothing.
+pub const ENCOUNTER_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// An encounter in `CombatSessions`, with the player fighting it
+pub struct ActiveEncounter {
+    pub player_id: i32,
+    pub encounter: SharedEncounter,
+    /// When a round was last played, or the encounter started
+    pub touched_at: Instant,
+}
+
+impl ActiveEncounter {
+    pub fn new(player_id: i32, encounter: SharedEncounter) -> Self {
+        ActiveEncounter { player_id, encounter, touched_at: Instant::now() }
+    }
+}
+
+/// Drop the encounters nobody has played a round of within `ENCOUNTER_TTL`
+pub fn evict_abandoned(sessions: &mut HashMap<Uuid, ActiveEncounter>) {
+    sessions.retain(|_, active| active.touched_at.elapsed() < ENCOUNTER_TTL);
+}
+
+/// A natural roll of this value always hits and deals double damage
+const CRITICAL_ROLL: i32 = 20;
+/// A natural roll of this value always misses
+const FUMBLE_ROLL: i32 = 1;
+/// Base armor class before a defender's defense is added
+const BASE_ARMOR_CLASS: i32 = 10;
+/// Experience a defeated monster is worth for each of its levels
+const EXPERIENCE_PER_MONSTER_LEVEL: i32 = 50;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Team {
+    Players,
+    Monsters,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Combatant {
+    pub id: String,
+    pub name: String,
+    pub team: Team,
+    pub player_id: Option<i32>,
+    pub level: i32,
+    pub health: i32,
+    pub max_health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub mana: i32,
+    pub max_mana: i32,
+    pub effects: StatusEffects,
+    pub cooldowns: HashMap<i32, u32>, // skill_id -> rounds until usable again
+}
+
+impl Combatant {
+    /// Build a combatant from a player and what they have equipped.
+    /// Cursed items add their persistent debuffs to the player's active effects.
+    pub fn from_player(player: &Player, equipment: &Equipment) -> Self {
+        let attack = player.level + equipment.attack_bonus();
+        let defense = player.level / 2 + equipment.defense_bonus();
+        let mut effects = player.status_effects.clone();
+
+        for item in equipment.equipped_items() {
+            if let Some(curse) = item.curse_effect() {
+                effects.apply(curse);
+            }
+        }
+
+        Combatant {
+            id: format!("player_{}", player.id),
+            name: player.username.clone(),
+            team: Team::Players,
+            player_id: Some(player.id),
+            level: player.level,
+            health: player.health,
+            max_health: player.max_health,
+            attack,
+            defense,
+            mana: player.mana,
+            max_mana: player.max_mana,
+            effects,
+            cooldowns: HashMap::new(),
+        }
+    }
+
+    /// Build a generic monster scaled to the given level
+    pub fn monster(name: &str, level: i32, health: i32) -> Self {
+        Combatant {
+            id: format!("monster_{}", Uuid::new_v4()),
+            name: name.to_string(),
+            team: Team::Monsters,
+            player_id: None,
+            level,
+            health,
+            max_health: health,
+            attack: level * 2,
+            defense: level / 2,
+            mana: 0,
+            max_mana: 0,
+            effects: StatusEffects::default(),
+            cooldowns: HashMap::new(),
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.health > 0
+    }
+
+    pub(crate) fn take_damage(&mut self, amount: i32) {
+        self.health = (self.health - amount).max(0);
+    }
+
+    /// Heal after healing modifiers are applied. Returns the amount actually restored.
+    pub(crate) fn heal(&mut self, amount: i32) -> i32 {
+        let before = self.health;
+        self.health = (self.health + self.effects.modify_healing(amount)).min(self.max_health);
+        self.health - before
+    }
+}
+
+/// What a combatant does on its turn
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum CombatAction {
+    Attack { target: Option<String> },
+    UseSkill { skill: PlayerSkill, target: Option<String> },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum EncounterStatus {
+    Ongoing,
+    Finished { winner: Team },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind")]
+pub enum CombatEvent {
+    Initiative { combatant: String, roll: i32 },
+    Hit { attacker: String, defender: String, roll: i32, damage: i32, critical: bool },
+    Miss { attacker: String, defender: String, roll: i32 },
+    SkillUsed { caster: String, skill: String, targets: Vec<String> },
+    Damaged { source: String, target: String, amount: i32 },
+    Healed { source: String, target: String, amount: i32 },
+    EffectApplied { target: String, effect: String },
+    EffectExpired { target: String, effect: String },
+    TurnSkipped { combatant: String },
+    Defeated { combatant: String },
+    Victory { winner: Team },
+    ExperienceGained { combatant: String, amount: i32 },
+    LeveledUp { combatant: String, level: i32 },
+}
+
+impl fmt::Display for CombatEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CombatEvent::Initiative { combatant, roll } => {
+                write!(f, "{} rolls {} for initiative", combatant, roll)
+            }
+            CombatEvent::Hit { attacker, defender, damage, critical: true, .. } => {
+                write!(f, "{} lands a critical hit on {} for {} damage!", attacker, defender, damage)
+            }
+            CombatEvent::Hit { attacker, defender, damage, .. } => {
+                write!(f, "{} hits {} for {} damage", attacker, defender, damage)
+            }
+            CombatEvent::Miss { attacker, defender, .. } => {
+                write!(f, "{} misses {}", attacker, defender)
+            }
+            CombatEvent::SkillUsed { caster, skill, targets } => {
+                write!(f, "{} uses {} on {}", caster, skill, targets.join(", "))
+            }
+            CombatEvent::Damaged { source, target, amount } => {
+                write!(f, "{} takes {} damage from {}", target, amount, source)
+            }
+            CombatEvent::Healed { source, target, amount } => {
+                write!(f, "{} restores {} health to {}", source, amount, target)
+            }
+            CombatEvent::EffectApplied { target, effect } => write!(f, "{} is affected by {}", target, effect),
+            CombatEvent::EffectExpired { target, effect } => write!(f, "{} wears off {}", effect, target),
+            CombatEvent::TurnSkipped { combatant } => write!(f, "{} is unable to act", combatant),
+            CombatEvent::Defeated { combatant } => write!(f, "{} has been defeated!", combatant),
+            CombatEvent::Victory { winner: Team::Players } => write!(f, "The players are victorious!"),
+            CombatEvent::Victory { winner: Team::Monsters } => write!(f, "The monsters are victorious!"),
+            CombatEvent::ExperienceGained { combatant, amount } => write!(f, "{} gains {} experience", combatant, amount),
+            CombatEvent::LeveledUp { combatant, level } => write!(f, "{} reaches level {}!", combatant, level),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CombatLogEntry {
+    pub round: u32,
+    pub event: CombatEvent,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CombatEncounter {
+    pub id: Uuid,
+    pub participants: Vec<Combatant>,
+    pub turn_order: Vec<usize>, // indices into `participants`, highest initiative first
+    pub round: u32,
+    pub status: EncounterStatus,
+    pub log: Vec<CombatLogEntry>,
+    #[serde(skip)]
+    pending_actions: HashMap<usize, CombatAction>,
+    /// Real-time effects have been counted down up to here
+    #[serde(skip)]
+    clock: Option<Instant>,
+    /// The combatant whose action is being resolved
+    #[serde(skip)]
+    acting: Option<usize>,
+}
+
+impl CombatEncounter {
+    /// Start an encounter and roll initiative for every participant
+    pub fn new<R: Rng>(participants: Vec<Combatant>, rng: &mut R) -> Self {
+        let mut encounter = CombatEncounter {
+            id: Uuid::new_v4(),
+            participants,
+            turn_order: Vec::new(),
+            round: 0,
+            status: EncounterStatus::Ongoing,
+            log: Vec::new(),
+            pending_actions: HashMap::new(),
+            clock: Some(Instant::now()),
+            acting: None,
+        };
+
+        let mut rolls: Vec<(usize, i32)> = encounter
+            .participants
+            .iter()
+            .enumerate()
+            .map(|(idx, c)| (idx, rng.gen_range(1..=20) + c.level / 2))
+            .collect();
+        rolls.sort_by_key(|&(_, roll)| std::cmp::Reverse(roll));
+
+        for (idx, roll) in &rolls {
+            let combatant = encounter.participants[*idx].name.clone();
+            encounter.record(CombatEvent::Initiative { combatant, roll: *roll });
+        }
+        encounter.turn_order = rolls.into_iter().map(|(idx, _)| idx).collect();
+        encounter
+    }
+
+    /// Play one full round: every living combatant acts once in initiative order.
+    /// Returns the log entries produced during this round.
+    pub fn run_round<R: Rng>(&mut self, rng: &mut R) -> &[CombatLogEntry] {
+        let start = self.log.len();
+        if self.status != EncounterStatus::Ongoing {
+            return &self.log[start..];
+        }
+
+        self.round += 1;
+        for combatant in &mut self.participants {
+            for rounds in combatant.cooldowns.values_mut() {
+                *rounds = rounds.saturating_sub(1);
+            }
+        }
+
+        for turn in 0..self.turn_order.len() {
+            let actor = self.turn_order[turn];
+            let action = self.pending_actions.remove(&actor);
+            if !self.participants[actor].is_alive() {
+                continue;
+            }
+
+            if self.participants[actor].effects.is_stunned() {
+                let combatant = self.participants[actor].name.clone();
+                self.record(CombatEvent::TurnSkipped { combatant });
+                self.end_turn(actor);
+                if self.check_victory() {
+                    break;
+                }
+                continue;
+            }
+
+            self.acting = Some(actor);
+            match action {
+                Some(CombatAction::UseSkill { skill, target }) => {
+                    skills::use_skill(self, actor, &skill, target.as_deref(), rng);
+                }
+                Some(CombatAction::Attack { target }) => {
+                    let defender = target
+                        .as_deref()
+                        .and_then(|id| self.find_opponent(actor, id))
+                        .or_else(|| self.pick_target(actor));
+                    if let Some(defender) = defender {
+                        self.resolve_attack(actor, defender, rng);
+                    }
+                }
+                None => {
+                    if let Some(defender) = self.pick_target(actor) {
+                        self.resolve_attack(actor, defender, rng);
+                    }
+                }
+            }
+            self.acting = None;
+
+            self.end_turn(actor);
+            if self.check_victory() {
+                break;
+            }
+        }
+        self.pending_actions.clear();
+
+        &self.log[start..]
+    }
+
+    /// Count real-time effects down by the whole seconds since the last call, so an effect
+    /// that had a minute left when the fight began doesn't still have it when the fight is saved
+    pub fn pass_time(&mut self) {
+        let now = Instant::now();
+        let Some(clock) = self.clock.replace(now) else {
+            return;
+        };
+        let elapsed = now.duration_since(clock).as_secs();
+        // Carry the part of a second that hasn't counted yet over to next time
+        self.clock = Some(clock + Duration::from_secs(elapsed));
+        if elapsed == 0 {
+            return;
+        }
+
+        for actor in 0..self.participants.len() {
+            let name = self.participants[actor].name.clone();
+            for tick in self.participants[actor].effects.tick_seconds(elapsed as u32) {
+                if let EffectTick::Expired { effect } = tick {
+                    self.record(CombatEvent::EffectExpired { target: name.clone(), effect });
+                }
+            }
+        }
+    }
+
+    /// Queue the action a combatant takes next round. Skills are validated up front so a
+    /// rejected cast doesn't cost the player their turn.
+    pub fn queue_action(&mut self, combatant_id: &str, action: CombatAction) -> Result<(), SkillError> {
+        if self.status != EncounterStatus::Ongoing {
+            return Err(SkillError::EncounterFinished);
+        }
+        let actor = self
+            .participants
+            .iter()
+            .position(|c| c.id == combatant_id)
+            .ok_or(SkillError::InvalidTarget)?;
+        if !self.participants[actor].is_alive() {
+            return Err(SkillError::CasterDefeated);
+        }
+
+        match &action {
+            CombatAction::UseSkill { skill, target } => {
+                skills::validate_skill(self, actor, skill, target.as_deref())?;
+            }
+            CombatAction::Attack { target: Some(target) } => {
+                self.find_opponent(actor, target).ok_or(SkillError::InvalidTarget)?;
+            }
+            CombatAction::Attack { target: None } => {}
+        }
+
+        self.pending_actions.insert(actor, action);
+        Ok(())
+    }
+
+    /// Experience each winning player earns: every defeated monster is worth its level times
+    /// `EXPERIENCE_PER_MONSTER_LEVEL`. Nothing until the players have won.
+    pub fn experience_reward(&self) -> i32 {
+        if self.status != (EncounterStatus::Finished { winner: Team::Players }) {
+            return 0;
+        }
+        self.participants
+            .iter()
+            .filter(|c| c.team == Team::Monsters && !c.is_alive())
+            .map(|c| c.level.max(1) * EXPERIENCE_PER_MONSTER_LEVEL)
+            .sum()
+    }
+
+    /// Look up the combatant controlled by a player
+    pub fn player_combatant(&self, player_id: i32) -> Option<&Combatant> {
+        self.participants.iter().find(|c| c.player_id == Some(player_id))
+    }
+
+    /// Apply a status effect to a combatant and log it. One landing on whoever is acting isn't
+    /// counted down as their turn ends, so it lasts as many of their turns as it would on anyone else.
+    pub(crate) fn apply_effect(&mut self, target: usize, mut effect: StatusEffect) {
+        effect.fresh = self.acting == Some(target);
+        let (name, effect_name) = (self.participants[target].name.clone(), effect.name.clone());
+        self.participants[target].effects.apply(effect);
+        self.record(CombatEvent::EffectApplied { target: name, effect: effect_name });
+    }
+
+    /// Resolve periodic effects and count down durations once the combatant has had its turn
+    fn end_turn(&mut self, actor: usize) {
+        let name = self.participants[actor].name.clone();
+        for tick in self.participants[actor].effects.tick_turn() {
+            match tick {
+                EffectTick::Healed { effect, amount } => {
+                    let amount = self.participants[actor].heal(amount);
+                    self.record(CombatEvent::Healed { source: effect, target: name.clone(), amount });
+                }
+                EffectTick::Damaged { effect, amount } => {
+                    if !self.participants[actor].is_alive() {
+                        continue;
+                    }
+                    self.participants[actor].take_damage(amount);
+                    self.record(CombatEvent::Damaged { source: effect, target: name.clone(), amount });
+                    if !self.participants[actor].is_alive() {
+                        self.record(CombatEvent::Defeated { combatant: name.clone() });
+                    }
+                }
+                EffectTick::Expired { effect } => {
+                    self.record(CombatEvent::EffectExpired { target: name.clone(), effect });
+                }
+            }
+        }
+    }
+
+    /// Find a living opponent of `actor` by combatant ID
+    pub(crate) fn find_opponent(&self, actor: usize, id: &str) -> Option<usize> {
+        let team = self.participants[actor].team;
+        self.participants
+            .iter()
+            .position(|c| c.id == id && c.team != team && c.is_alive())
+    }
+
+    /// Attackers focus the weakest living opponent
+    pub(crate) fn pick_target(&self, attacker: usize) -> Option<usize> {
+        let team = self.participants[attacker].team;
+        self.participants
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.team != team && c.is_alive())
+            .min_by_key(|(_, c)| c.health)
+            .map(|(idx, _)| idx)
+    }
+
+    fn resolve_attack<R: Rng>(&mut self, attacker: usize, defender: usize, rng: &mut R) {
+        let bonus_damage = self.participants[attacker].attack;
+        self.resolve_strike(attacker, defender, bonus_damage, rng);
+    }
+
+    /// Roll to hit and apply `bonus_damage + d6` on a hit. Returns whether the strike landed.
+    pub(crate) fn resolve_strike<R: Rng>(
+        &mut self,
+        attacker: usize,
+        defender: usize,
+        bonus_damage: i32,
+        rng: &mut R,
+    ) -> bool {
+        let (atk, def) = (&self.participants[attacker], &self.participants[defender]);
+        let natural = rng.gen_range(1..=20);
+        let roll = natural + atk.level + atk.effects.accuracy_modifier();
+        let armor_class = BASE_ARMOR_CLASS + def.defense;
+        let (attacker_name, defender_name) = (atk.name.clone(), def.name.clone());
+
+        let hit = natural != FUMBLE_ROLL && (natural == CRITICAL_ROLL || roll >= armor_class);
+        if !hit {
+            self.record(CombatEvent::Miss { attacker: attacker_name, defender: defender_name, roll });
+            return false;
+        }
+
+        let critical = natural == CRITICAL_ROLL;
+        let mut damage = (rng.gen_range(1..=6) + bonus_damage + atk.effects.attack_modifier()).max(1);
+        if critical {
+            damage *= 2;
+        }
+
+        self.participants[defender].take_damage(damage);
+        self.record(CombatEvent::Hit {
+            attacker: attacker_name,
+            defender: defender_name.clone(),
+            roll,
+            damage,
+            critical,
+        });
+
+        if !self.participants[defender].is_alive() {
+            self.record(CombatEvent::Defeated { combatant: defender_name });
+        }
+        true
+    }
+
+    /// Finish the encounter once one side has nobody left standing. Players who go down along
+    /// with the last monster, e.g. to their own poison, have lost.
+    fn check_victory(&mut self) -> bool {
+        let alive = |team: Team| self.participants.iter().any(|c| c.team == team && c.is_alive());
+        let winner = match (alive(Team::Players), alive(Team::Monsters)) {
+            (true, true) => return false,
+            (true, false) => Team::Players,
+            (false, _) => Team::Monsters,
+        };
+
+        self.status = EncounterStatus::Finished { winner };
+        self.record(CombatEvent::Victory { winner });
+        true
+    }
+
+    pub(crate) fn record(&mut self, event: CombatEvent) {
+        self.log.push(CombatLogEntry {
+            round: self.round,
+            message: event.to_string(),
+            event,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::models::{EffectDuration, EffectKind};
+
+    fn hero(level: i32, health: i32) -> Combatant {
+        Combatant {
+            id: "player_1".to_string(),
+            team: Team::Players,
+            player_id: Some(1),
+            ..Combatant::monster("Hero", level, health)
+        }
+    }
+
+    /// An encounter where `first` acts before `second`
+    fn encounter(first: Combatant, second: Combatant) -> CombatEncounter {
+        let mut encounter = CombatEncounter::new(vec![first, second], &mut StdRng::seed_from_u64(0));
+        encounter.turn_order = vec![0, 1];
+        encounter
+    }
+
+    fn effect(kind: EffectKind, magnitude: i32, turns: u32) -> StatusEffect {
+        StatusEffect::new(kind.as_str(), kind.as_str(), kind, magnitude, EffectDuration::Turns(turns), "test")
+    }
+
+    #[test]
+    fn the_same_seed_plays_the_same_fight() {
+        let fight = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let participants = vec![hero(3, 40), Combatant::monster("Goblin", 2, 30)];
+            let mut encounter = CombatEncounter::new(participants, &mut rng);
+            while encounter.status == EncounterStatus::Ongoing && encounter.round < 100 {
+                encounter.run_round(&mut rng);
+            }
+            encounter.log.iter().map(|entry| entry.message.clone()).collect::<Vec<_>>()
+        };
+        let log = fight(42);
+        assert_eq!(log, fight(42));
+        assert!(log.last().is_some_and(|message| message.ends_with("victorious!")));
+    }
+
+    #[test]
+    fn defeating_every_monster_wins_their_experience() {
+        let mut encounter = encounter(hero(40, 100), Combatant::monster("Rat", 2, 1));
+        assert_eq!(encounter.experience_reward(), 0);
+
+        encounter.run_round(&mut StdRng::seed_from_u64(7));
+        assert_eq!(encounter.status, EncounterStatus::Finished { winner: Team::Players });
+        assert_eq!(encounter.experience_reward(), 2 * EXPERIENCE_PER_MONSTER_LEVEL);
+    }
+
+    #[test]
+    fn players_who_fall_with_the_last_monster_lose() {
+        let mut poisoned = hero(40, 5);
+        poisoned.effects.apply(effect(EffectKind::DamageOverTime, 10, 3));
+        let mut encounter = encounter(poisoned, Combatant::monster("Rat", 2, 1));
+
+        encounter.run_round(&mut StdRng::seed_from_u64(7));
+        assert!(encounter.participants.iter().all(|c| !c.is_alive()));
+        assert_eq!(encounter.status, EncounterStatus::Finished { winner: Team::Monsters });
+        assert_eq!(encounter.experience_reward(), 0);
+        let victories = encounter.log.iter().filter(|entry| matches!(entry.event, CombatEvent::Victory { .. }));
+        assert_eq!(victories.count(), 1);
+    }
+
+    #[test]
+    fn stunned_combatants_lose_their_turn() {
+        let mut stunned = hero(40, 100);
+        stunned.effects.apply(effect(EffectKind::Stun, 0, 1));
+        let mut encounter = encounter(stunned, Combatant::monster("Rat", 1, 50));
+
+        encounter.run_round(&mut StdRng::seed_from_u64(7));
+        assert_eq!(encounter.participants[1].health, 50);
+        assert!(encounter.log.iter().any(|entry| matches!(entry.event, CombatEvent::TurnSkipped { .. })));
+        assert!(!encounter.participants[0].effects.is_stunned());
+    }
+
+    #[test]
+    fn finished_encounters_play_no_more_rounds() {
+        let mut encounter = encounter(hero(40, 100), Combatant::monster("Rat", 2, 1));
+        let mut rng = StdRng::seed_from_u64(7);
+        encounter.run_round(&mut rng);
+        let (round, logged) = (encounter.round, encounter.log.len());
+
+        assert!(encounter.run_round(&mut rng).is_empty());
+        assert_eq!((encounter.round, encounter.log.len()), (round, logged));
+        assert_eq!(
+            encounter.queue_action("player_1", CombatAction::Attack { target: None }).unwrap_err(),
+            SkillError::EncounterFinished
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use serde::Serialize;
+use std::fmt;
+
+/// A command verb, its other spellings and its help text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Verb {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub usage: &'static str,
+    pub summary: &'static str,
+}
+
+/// Every command the interpreter understands, in the order `help` lists them. Besides its name
+/// and aliases, a verb can be typed as any prefix only it starts with.
+pub const VERBS: [Verb; 10] = [
+    Verb {
+        name: "look",
+        aliases: &["l", "examine"],
+        usage: "look",
+        summary: "Describe where you are and the portals out of it",
+    },
+    Verb {
+        name: "go",
+        aliases: &["travel", "enter", "walk"],
+        usage: "go <portal | direction>",
+        summary: "Take a portal, named by the portal or where it leads, or walk north, south, east or west to the next room",
+    },
+    Verb {
+        name: "inventory",
+        aliases: &["i", "items"],
+        usage: "inventory",
+        summary: "List what you are carrying",
+    },
+    Verb {
+        name: "use",
+        aliases: &["drink", "read", "eat"],
+        usage: "use <item>",
+        summary: "Use an item you are carrying",
+    },
+    Verb {
+        name: "attack",
+        aliases: &["a", "hit", "fight", "kill"],
+        usage: "attack [target]",
+        summary: "Attack, starting a fight if you aren't in one",
+    },
+    Verb {
+        name: "cast",
+        aliases: &["c"],
+        usage: "cast <skill> [on <target>]",
+        summary: "Use a skill in a fight; target `me` for yourself",
+    },
+    Verb {
+        name: "say",
+        aliases: &["'"],
+        usage: "say <message>",
+        summary: "Say something to everyone nearby",
+    },
+    Verb {
+        name: "emote",
+        aliases: &["me", ":"],
+        usage: "emote <action>",
+        summary: "Show everyone nearby what you're doing, e.g. `emote waves`",
+    },
+    Verb {
+        name: "who",
+        aliases: &["players", "online"],
+        usage: "who",
+        summary: "List the players here",
+    },
+    Verb {
+        name: "help",
+        aliases: &["h", "?", "commands"],
+        usage: "help [command]",
+        summary: "List commands, or explain one",
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Look,
+    Go { portal: String },
+    Inventory,
+    Use { item: String },
+    Attack { target: Option<String> },
+    Cast { skill: String, target: Option<String> },
+    Say { message: String },
+    Emote { action: String },
+    Who,
+    Help { topic: Option<String> },
+}
+
+impl Command {
+    /// Name of the verb the command was given with
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Command::Look => "look",
+            Command::Go { .. } => "go",
+            Command::Inventory => "inventory",
+            Command::Use { .. } => "use",
+            Command::Attack { .. } => "attack",
+            Command::Cast { .. } => "cast",
+            Command::Say { .. } => "say",
+            Command::Emote { .. } => "emote",
+            Command::Who => "who",
+            Command::Help { .. } => "help",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    UnknownVerb { verb: String, suggestion: Option<&'static str> },
+    /// An abbreviation more than one verb starts with
+    AmbiguousVerb { verb: String, candidates: Vec<&'static str> },
+    MissingArgument(&'static Verb),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Type a command, or `help` for a list of them."),
+            ParseError::UnknownVerb { verb, suggestion: Some(suggestion) } => {
+                write!(f, "I don't know how to '{}'. Did you mean `{}`?", verb, suggestion)
+            }
+            ParseError::UnknownVerb { verb, suggestion: None } => {
+                write!(f, "I don't know how to '{}'. Type `help` for a list of commands.", verb)
+            }
+            ParseError::AmbiguousVerb { verb, candidates } => {
+                write!(f, "'{}' could mean {}.", verb, or_list(candidates))
+            }
+            ParseError::MissingArgument(verb) => write!(f, "Usage: {}", verb.usage),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// "`a`, `b` or `c`"
+fn or_list(words: &[&str]) -> String {
+    let quoted: Vec<String> = words.iter().map(|w| format!("`{}`", w)).collect();
+    match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+        _ => quoted.join(""),
+    }
+}
+
+fn verb_named(name: &str) -> &'static Verb {
+    VERBS.iter().find(|verb| verb.name == name).expect("verb is in VERBS")
+}
+
+/// The verb `word` names: exactly by name or alias, or as a prefix of only one verb's name or
+/// aliases
+pub fn find_verb(word: &str) -> Result<&'static Verb, ParseError> {
+    let word = word.to_lowercase();
+    let spellings = |verb: &'static Verb| std::iter::once(verb.name).chain(verb.aliases.iter().copied());
+    if let Some(verb) = VERBS.iter().find(|verb| spellings(verb).any(|s| s == word)) {
+        return Ok(verb);
+    }
+
+    let candidates: Vec<&'static Verb> =
+        VERBS.iter().filter(|verb| spellings(verb).any(|s| s.starts_with(word.as_str()))).collect();
+    match candidates.as_slice() {
+        [verb] => Ok(verb),
+        [] => Err(ParseError::UnknownVerb {
+            suggestion: VERBS
+                .iter()
+                .map(|verb| (edit_distance(&word, verb.name), verb.name))
+                .filter(|&(distance, name)| distance <= typo_allowance(name))
+                .min()
+                .map(|(_, name)| name),
+            verb: word,
+        }),
+        _ => Err(ParseError::AmbiguousVerb { verb: word, candidates: candidates.iter().map(|v| v.name).collect() }),
+    }
+}
+
+/// Parse a line typed into the terminal. `'hello` is short for `say hello` and `:waves` for
+/// `emote waves`.
+pub fn parse(input: &str) -> Result<Command, ParseError> {
+    let input = input.trim();
+    if let Some(message) = input.strip_prefix('\'') {
+        return say(message.trim());
+    }
+    if let Some(action) = input.strip_prefix(':') {
+        return emote(action.trim());
+    }
+    let (word, rest) = match input.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (input, ""),
+    };
+    if word.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    let verb = find_verb(word)?;
+    let argument = || if rest.is_empty() { None } else { Some(rest.to_string()) };
+    let required = || argument().ok_or(ParseError::MissingArgument(verb));
+
+    match verb.name {
+        "look" => Ok(Command::Look),
+        "go" => Ok(Command::Go { portal: required()? }),
+        "inventory" => Ok(Command::Inventory),
+        "use" => Ok(Command::Use { item: required()? }),
+        "attack" => Ok(Command::Attack { target: argument() }),
+        "cast" => {
+            let rest = required()?;
+            let lower = rest.to_ascii_lowercase();
+            // The target follows the last " on " or " at "
+            let split = [" on ", " at "].iter().filter_map(|sep| lower.rfind(sep).map(|at| (at, sep.len()))).max();
+            match split {
+                Some((at, len)) if at > 0 && at + len < rest.len() => Ok(Command::Cast {
+                    skill: rest[..at].trim().to_string(),
+                    target: Some(rest[at + len..].trim().to_string()),
+                }),
+                _ => Ok(Command::Cast { skill: rest, target: None }),
+            }
+        }
+        "say" => say(rest),
+        "emote" => emote(rest),
+        "who" => Ok(Command::Who),
+        "help" => Ok(Command::Help { topic: argument() }),
+        _ => unreachable!("every verb in VERBS is handled"),
+    }
+}
+
+fn say(message: &str) -> Result<Command, ParseError> {
+    if message.is_empty() {
+        return Err(ParseError::MissingArgument(verb_named("say")));
+    }
+    Ok(Command::Say { message: message.to_string() })
+}
+
+fn emote(action: &str) -> Result<Command, ParseError> {
+    if action.is_empty() {
+        return Err(ParseError::MissingArgument(verb_named("emote")));
+    }
+    Ok(Command::Emote { action: action.to_string() })
+}
+
+/// The list of commands, or how to use one
+pub fn help_text(topic: Option<&str>) -> Result<String, ParseError> {
+    let Some(topic) = topic else {
+        let width = VERBS.iter().map(|v| v.usage.len()).max().unwrap_or(0);
+        let mut text = String::from("Commands:\n");
+        for verb in &VERBS {
+            text.push_str(&format!("  {:width$}  {}\n", verb.usage, verb.summary, width = width));
+        }
+        text.push_str("Commands can be shortened, e.g. `l` for look or `inv` for inventory.");
+        return Ok(text);
+    };
+    let verb = find_verb(topic)?;
+    let mut text = format!("{}\n  {}", verb.usage, verb.summary);
+    if !verb.aliases.is_empty() {
+        text.push_str(&format!("\n  Also: {}", verb.aliases.join(", ")));
+    }
+    Ok(text)
+}
+
+/// How well a name matched, best first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchQuality {
+    Exact,
+    Prefix,
+    /// Every word typed starts a word of the name
+    WordPrefixes,
+    Contains,
+    Typo,
+}
+
+/// The outcome of looking something up by what the player typed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FuzzyMatch<'a, T> {
+    One(&'a T),
+    /// Several matched equally well
+    Many(Vec<&'a T>),
+    None,
+}
+
+/// Lowercase, without a leading article and extra spaces
+fn normalize(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    match words.split_first() {
+        Some((first, rest)) if !rest.is_empty() && ["the", "a", "an"].contains(first) => rest.join(" "),
+        _ => words.join(" "),
+    }
+}
+
+fn match_quality(query: &str, name: &str) -> Option<MatchQuality> {
+    let name = normalize(name);
+    if name == query {
+        return Some(MatchQuality::Exact);
+    }
+    if name.starts_with(query) {
+        return Some(MatchQuality::Prefix);
+    }
+    let name_words: Vec<&str> = name.split(|c: char| c.is_whitespace() || c == '_' || c == '-').collect();
+    if query.split_whitespace().all(|word| name_words.iter().any(|n| n.starts_with(word))) {
+        return Some(MatchQuality::WordPrefixes);
+    }
+    if name.contains(query) {
+        return Some(MatchQuality::Contains);
+    }
+    if edit_distance(query, &name) <= typo_allowance(&name) {
+        return Some(MatchQuality::Typo);
+    }
+    None
+}
+
+/// Find what the player meant among `items`, each known by one or more names: exact names
+/// beat prefixes, which beat word prefixes, substrings and finally near misses
+pub fn fuzzy_find<'a, T, F>(query: &str, items: &'a [T], names: F) -> FuzzyMatch<'a, T>
+where
+    F: Fn(&T) -> Vec<&str>,
+{
+    let query = normalize(query);
+    if query.is_empty() {
+        return FuzzyMatch::None;
+    }
+    let scored: Vec<(MatchQuality, &T)> = items
+        .iter()
+        .filter_map(|item| names(item).into_iter().filter_map(|name| match_quality(&query, name)).min().map(|q| (q, item)))
+        .collect();
+    let Some(best) = scored.iter().map(|(quality, _)| *quality).min() else {
+        return FuzzyMatch::None;
+    };
+    let mut matches: Vec<&T> = scored.into_iter().filter(|(quality, _)| *quality == best).map(|(_, item)| item).collect();
+    if matches.len() == 1 {
+        FuzzyMatch::One(matches.remove(0))
+    } else {
+        FuzzyMatch::Many(matches)
+    }
+}
+
+/// Typos forgiven in a name this long: one for every four characters, at least one
+fn typo_allowance(name: &str) -> usize {
+    (name.chars().count() / 4).max(1)
+}
+
+/// Levenshtein distance between two strings, by character
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
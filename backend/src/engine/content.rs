@@ -0,0 +1,516 @@
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fmt, fs, io};
+use sqlx::{PgConnection, PgPool};
+
+use crate::db::items::{get_item_content_ids, sync_item_definitions, ItemSyncReport};
+use crate::db::players::move_players_not_in;
+use crate::db::regions::{lock_regions, read_regions, region_fingerprint, sync_content_regions, RegionSyncReport};
+use crate::db::DbPool;
+use crate::engine::map_graph::{MapGraph, NEXUS_ID};
+use crate::loader::artifacts::load_artifacts_from_dir;
+use crate::loader::check::{check_content, check_map};
+use crate::loader::dungeons::load_regions_from_dir;
+use crate::loader::environments::load_environment_items;
+use crate::loader::grammars::load_text_generator;
+use crate::loader::items::load_items_from_dir;
+use crate::models::{Artifact, ItemDefinition, Region};
+use crate::procedural::text::TextGenerator;
+
+/// How many reloads the history keeps
+const HISTORY_LEN: usize = 50;
+
+/// Everything the running game reads from `content/`, loaded and checked together
+#[derive(Debug, Clone)]
+pub struct ContentSnapshot {
+    /// Once the snapshot is live, every stored region: the content regions plus those generated
+    /// or imported since. Freshly loaded, only the content regions.
+    pub map: Arc<MapGraph>,
+    /// The regions read from the region files
+    pub content_regions: Arc<Vec<Region>>,
+    /// `region_fingerprint` of the stored regions the live map was built from; empty until then
+    pub stored_regions: String,
+    pub items: Vec<ItemDefinition>,
+    pub artifacts: Vec<Artifact>,
+    pub text: Arc<TextGenerator>,
+    /// Changes whenever a file under the content directory does
+    pub fingerprint: u64,
+}
+
+/// Why content couldn't be loaded. The problems are those `rpg-content check` reports.
+#[derive(Debug, Clone)]
+pub struct ContentError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Content has {} problem(s):\n{}", self.problems.len(), self.problems.join("\n"))
+    }
+}
+
+impl std::error::Error for ContentError {}
+
+impl ContentError {
+    fn from_error(e: impl fmt::Display) -> Self {
+        ContentError { problems: vec![format!("{:#}", e)] }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadTrigger {
+    Startup,
+    /// The watcher saw a file change
+    FileChange,
+    /// An admin asked for it
+    Manual,
+    /// An admin changed content through the API
+    Edit,
+    /// Regions were generated, imported or synced into the database by someone else
+    StoredRegions,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ReloadOutcome {
+    Applied {
+        regions_added: Vec<String>,
+        regions_removed: Vec<String>,
+        /// Players moved to the nexus because their region was removed
+        players_moved: Vec<i32>,
+        items: ItemSyncReport,
+        regions: RegionSyncReport,
+    },
+    /// The content failed validation or couldn't be synced; the running world was kept
+    Rejected { problems: Vec<String> },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadRecord {
+    /// Unix time in seconds
+    pub at: i64,
+    pub trigger: ReloadTrigger,
+    #[serde(flatten)]
+    pub outcome: ReloadOutcome,
+}
+
+/// A file under the content directory to write, or to remove when `contents` is `None`
+#[derive(Debug, Clone)]
+pub struct ContentChange {
+    pub path: PathBuf,
+    pub contents: Option<String>,
+}
+
+/// The content the server is running with, swapped out whole when a reload succeeds so readers
+/// never see half of one version and half of another
+#[derive(Debug)]
+pub struct LiveContent {
+    dir: PathBuf,
+    current: RwLock<Arc<ContentSnapshot>>,
+    history: Mutex<VecDeque<ReloadRecord>>,
+    /// Held for the whole of a reload, so the watcher and admins can't reload at the same time
+    reloading: tokio::sync::Mutex<()>,
+}
+
+pub type SharedContent = Arc<LiveContent>;
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Hash of every file's path, size and modification time under `dir`
+pub fn fingerprint(dir: &Path) -> io::Result<u64> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push((entry.path(), metadata.len(), metadata.modified().ok()));
+            }
+        }
+    }
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    files.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Check and load everything under `dir`. Nothing is returned unless every file is valid and
+/// the regions link up.
+pub fn load_snapshot(dir: &Path) -> Result<ContentSnapshot, ContentError> {
+    let fingerprint = fingerprint(dir).map_err(ContentError::from_error)?;
+
+    let problems: Vec<String> = check_content(dir)
+        .into_iter()
+        .filter(|d| d.is_error())
+        .map(|d| d.to_string())
+        .collect();
+    if !problems.is_empty() {
+        return Err(ContentError { problems });
+    }
+
+    let subdir = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    let text = if dir.join("grammars").is_dir() {
+        load_text_generator(&subdir("grammars")).map_err(ContentError::from_error)?
+    } else {
+        TextGenerator::builtin()
+    };
+    let regions = load_regions_from_dir(&subdir("regions"), &text).map_err(ContentError::from_error)?;
+    let items = if dir.join("items").is_dir() {
+        load_items_from_dir(&subdir("items")).map_err(ContentError::from_error)?
+    } else {
+        Vec::new()
+    };
+    let environment_items = if dir.join("environments.toml").is_file() {
+        load_environment_items(&dir.join("environments.toml")).map_err(ContentError::from_error)?
+    } else {
+        HashMap::new()
+    };
+    let artifacts = if dir.join("artifacts").is_dir() {
+        load_artifacts_from_dir(&subdir("artifacts")).map_err(ContentError::from_error)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(ContentSnapshot {
+        map: Arc::new(MapGraph::new(regions.clone()).with_environment_items(environment_items)),
+        content_regions: Arc::new(regions),
+        stored_regions: String::new(),
+        items,
+        artifacts,
+        text: Arc::new(text),
+        fingerprint,
+    })
+}
+
+impl LiveContent {
+    /// Load the content the server starts with, then sync it like any reload. Fails if the
+    /// content isn't valid, since there is no running world to fall back on.
+    pub async fn start(dir: impl Into<PathBuf>, pool: &PgPool) -> Result<Self, ContentError> {
+        let dir = dir.into();
+        let snapshot = load_snapshot(&dir)?;
+        let content = LiveContent {
+            dir,
+            current: RwLock::new(Arc::new(snapshot.clone())),
+            history: Mutex::new(VecDeque::new()),
+            reloading: tokio::sync::Mutex::new(()),
+        };
+        let record = content.apply(pool, ReloadTrigger::Startup, snapshot, None).await;
+        if let ReloadOutcome::Rejected { problems } = record.outcome {
+            return Err(ContentError { problems });
+        }
+        Ok(content)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn snapshot(&self) -> Arc<ContentSnapshot> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn map(&self) -> Arc<MapGraph> {
+        self.snapshot().map.clone()
+    }
+
+    /// Reloads so far, newest first
+    pub fn history(&self) -> Vec<ReloadRecord> {
+        self.history.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn record(&self, trigger: ReloadTrigger, outcome: ReloadOutcome) -> ReloadRecord {
+        let record = ReloadRecord { at: now_unix(), trigger, outcome };
+        let mut history = self.history.lock().unwrap();
+        history.push_front(record.clone());
+        history.truncate(HISTORY_LEN);
+        record
+    }
+
+    /// Load the content again and, if it is valid and syncs to the database, swap it in with a
+    /// map of every stored region. Players in regions that no longer exist are then moved to the
+    /// nexus.
+    pub async fn reload(&self, pool: &PgPool, trigger: ReloadTrigger) -> ReloadRecord {
+        let _reloading = self.reloading.lock().await;
+        self.reload_locked(pool, trigger).await
+    }
+
+    /// Write the changes and reload. If the content no longer loads or syncs, every file is put
+    /// back the way it was and the running world is kept, so an edit can't leave the content
+    /// directory broken for the next reload.
+    pub async fn edit(&self, pool: &PgPool, changes: Vec<ContentChange>) -> ReloadRecord {
+        let _reloading = self.reloading.lock().await;
+
+        let mut backups = Vec::new();
+        for change in changes {
+            match write_change(&change) {
+                Ok(previous) => backups.push(ContentChange { path: change.path, contents: previous }),
+                Err(e) => {
+                    restore(backups);
+                    let problems = vec![format!("writing {} failed: {}", change.path.display(), e)];
+                    return self.record(ReloadTrigger::Edit, ReloadOutcome::Rejected { problems });
+                }
+            }
+        }
+
+        let record = self.reload_locked(pool, ReloadTrigger::Edit).await;
+        if let ReloadOutcome::Rejected { .. } = record.outcome {
+            restore(backups);
+        }
+        record
+    }
+
+    async fn reload_locked(&self, pool: &PgPool, trigger: ReloadTrigger) -> ReloadRecord {
+        let dir = self.dir.clone();
+        let loaded = tokio::task::spawn_blocking(move || load_snapshot(&dir))
+            .await
+            .unwrap_or_else(|e| Err(ContentError::from_error(e)));
+        match loaded {
+            Ok(snapshot) => self.apply(pool, trigger, snapshot, Some(self.snapshot())).await,
+            Err(e) => self.record(trigger, ReloadOutcome::Rejected { problems: e.problems }),
+        }
+    }
+
+    /// Swap in a map of the stored regions if they changed since the live map was built, e.g.
+    /// because a region was generated, imported or edited by an admin, or another replica synced
+    /// its content. The content itself isn't synced again, so replicas running different content
+    /// don't keep overwriting each other.
+    pub async fn refresh_regions(&self, pool: &PgPool, trigger: ReloadTrigger) -> ReloadRecord {
+        let _reloading = self.reloading.lock().await;
+        let current = self.snapshot();
+        let mut snapshot = (*current).clone();
+
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return self.record(trigger, rejected("regions", e)),
+        };
+        if let Err(e) = lock_regions(&mut tx).await {
+            return self.record(trigger, rejected("regions", e));
+        }
+        let players_moved = match go_live(&mut tx, &mut snapshot).await {
+            Ok(players_moved) => players_moved,
+            Err(e) => return self.record(trigger, ReloadOutcome::Rejected { problems: e.problems }),
+        };
+        if let Err(e) = tx.commit().await {
+            return self.record(trigger, rejected("regions", e));
+        }
+
+        let (regions_added, regions_removed) = self.swap(snapshot, &current);
+        self.record(
+            trigger,
+            ReloadOutcome::Applied {
+                regions_added,
+                regions_removed,
+                players_moved,
+                items: ItemSyncReport::default(),
+                regions: RegionSyncReport::default(),
+            },
+        )
+    }
+
+    /// Sync the content and swap it in with a map of every stored region, all in one
+    /// transaction that keeps others from saving regions meanwhile. Nothing is swapped in unless
+    /// the whole transaction commits.
+    async fn apply(
+        &self,
+        pool: &PgPool,
+        trigger: ReloadTrigger,
+        mut snapshot: ContentSnapshot,
+        previous: Option<Arc<ContentSnapshot>>,
+    ) -> ReloadRecord {
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return self.record(trigger, rejected("content", e)),
+        };
+        if let Err(e) = lock_regions(&mut tx).await {
+            return self.record(trigger, rejected("regions", e));
+        }
+        let items = match sync_item_definitions(&mut tx, &snapshot.items).await {
+            Ok(report) => report,
+            Err(e) => return self.record(trigger, rejected("items", e)),
+        };
+        let loaded_before: Vec<String> = previous
+            .iter()
+            .flat_map(|previous| previous.content_regions.iter())
+            .map(|region| region.id.clone())
+            .collect();
+        let regions = match sync_content_regions(&mut tx, &snapshot.content_regions, &loaded_before).await {
+            Ok(report) => report,
+            Err(e) => return self.record(trigger, rejected("regions", e)),
+        };
+        let players_moved = match go_live(&mut tx, &mut snapshot).await {
+            Ok(players_moved) => players_moved,
+            Err(e) => return self.record(trigger, ReloadOutcome::Rejected { problems: e.problems }),
+        };
+        if let Err(e) = tx.commit().await {
+            return self.record(trigger, rejected("content", e));
+        }
+
+        let (regions_added, regions_removed) = match &previous {
+            Some(previous) => self.swap(snapshot, previous),
+            None => {
+                let mut regions_added: Vec<String> = snapshot.map.regions.keys().cloned().collect();
+                regions_added.sort();
+                *self.current.write().unwrap() = Arc::new(snapshot);
+                (regions_added, Vec::new())
+            }
+        };
+        self.record(
+            trigger,
+            ReloadOutcome::Applied { regions_added, regions_removed, players_moved, items, regions },
+        )
+    }
+
+    /// Check the stored regions as `conn` sees them the way the next refresh will, so a change
+    /// that would be turned away can be rolled back instead
+    pub async fn check_stored_regions(&self, conn: &mut PgConnection) -> Result<(), ContentError> {
+        stored_map(conn, &self.snapshot()).await.map(|_| ())
+    }
+
+    /// Make `snapshot` the live content, returning the regions added and removed since `previous`
+    fn swap(&self, snapshot: ContentSnapshot, previous: &ContentSnapshot) -> (Vec<String>, Vec<String>) {
+        let old: HashSet<&String> = previous.map.regions.keys().collect();
+        let new: HashSet<&String> = snapshot.map.regions.keys().collect();
+        let mut regions_added: Vec<String> = new.difference(&old).map(|id| id.to_string()).collect();
+        let mut regions_removed: Vec<String> = old.difference(&new).map(|id| id.to_string()).collect();
+        regions_added.sort();
+        regions_removed.sort();
+        *self.current.write().unwrap() = Arc::new(snapshot);
+        (regions_added, regions_removed)
+    }
+}
+
+fn rejected(what: &str, e: impl fmt::Display) -> ReloadOutcome {
+    ReloadOutcome::Rejected { problems: vec![format!("syncing {} failed: {}", what, e)] }
+}
+
+/// Give `snapshot` a map of every stored region, checked like the content files are, and move
+/// everyone outside those regions to the nexus, including anyone an earlier reload failed to
+/// move. Returns the players moved.
+async fn go_live(conn: &mut PgConnection, snapshot: &mut ContentSnapshot) -> Result<Vec<i32>, ContentError> {
+    let (map, fingerprint) = stored_map(conn, snapshot).await?;
+    let region_ids: Vec<String> = map.regions.keys().cloned().collect();
+    let players_moved = move_players_not_in(&mut *conn, &region_ids, NEXUS_ID)
+        .await
+        .map_err(|e| ContentError::from_error(format!("moving players to the nexus failed: {}", e)))?;
+    snapshot.map = Arc::new(map);
+    snapshot.stored_regions = fingerprint;
+    Ok(players_moved)
+}
+
+/// A map of every stored region with `snapshot`'s environment items, checked like the content
+/// files are, and the `region_fingerprint` it was built at
+async fn stored_map(conn: &mut PgConnection, snapshot: &ContentSnapshot) -> Result<(MapGraph, String), ContentError> {
+    let failed = |e: sqlx::Error| ContentError::from_error(format!("reading the stored regions failed: {}", e));
+    let stored = read_regions(conn).await.map_err(failed)?;
+    let fingerprint = region_fingerprint(&mut *conn).await.map_err(failed)?;
+    let item_ids: HashSet<String> = get_item_content_ids(&mut *conn).await.map_err(failed)?.into_iter().collect();
+
+    let map = MapGraph::new(stored).with_environment_items(snapshot.map.environment_items.clone());
+    let problems: Vec<String> = check_map(&map, &item_ids)
+        .into_iter()
+        .map(|problem| format!("stored regions: {}", problem))
+        .collect();
+    if !problems.is_empty() {
+        return Err(ContentError { problems });
+    }
+    Ok((map, fingerprint))
+}
+
+/// Apply one change, returning what the file held before
+fn write_change(change: &ContentChange) -> io::Result<Option<String>> {
+    let previous = match fs::read_to_string(&change.path) {
+        Ok(previous) => Some(previous),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    match &change.contents {
+        Some(contents) => {
+            if let Some(parent) = change.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&change.path, contents)?;
+        }
+        None if previous.is_some() => fs::remove_file(&change.path)?,
+        None => {}
+    }
+    Ok(previous)
+}
+
+/// Undo changes made by `write_change`, last first
+fn restore(backups: Vec<ContentChange>) {
+    for backup in backups.into_iter().rev() {
+        if let Err(e) = write_change(&backup) {
+            eprintln!("⚠️ Failed to restore {}: {}", backup.path.display(), e);
+        }
+    }
+}
+
+/// Poll the content directory and reload whenever something in it changes, and put regions
+/// others store in the database on the map. Content that fails to load isn't retried until it
+/// changes again.
+pub fn spawn_content_watcher(content: SharedContent, pool: DbPool, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_seen = content.snapshot().fingerprint;
+        let mut last_stored = content.snapshot().stored_regions.clone();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let dir = content.dir().to_path_buf();
+            match tokio::task::spawn_blocking(move || fingerprint(&dir)).await {
+                Ok(Ok(current)) => {
+                    // Also skip content an admin already reloaded by hand
+                    let changed = current != last_seen && current != content.snapshot().fingerprint;
+                    last_seen = current;
+                    if changed {
+                        report(&content.reload(&pool, ReloadTrigger::FileChange).await);
+                    }
+                }
+                Ok(Err(e)) => eprintln!("⚠️ Can't read content directory: {}", e),
+                Err(_) => {}
+            }
+
+            match region_fingerprint(pool.as_ref()).await {
+                Ok(stored) => {
+                    let changed = stored != last_stored && stored != content.snapshot().stored_regions;
+                    last_stored = stored;
+                    if changed {
+                        report(&content.refresh_regions(&pool, ReloadTrigger::StoredRegions).await);
+                    }
+                }
+                Err(e) => eprintln!("⚠️ Can't read the stored regions: {}", e),
+            }
+        }
+    })
+}
+
+fn report(record: &ReloadRecord) {
+    match &record.outcome {
+        ReloadOutcome::Applied { regions_added, regions_removed, players_moved, .. } => println!(
+            "🔄 Reloaded content: {} region(s) added, {} removed, {} player(s) moved to the nexus",
+            regions_added.len(),
+            regions_removed.len(),
+            players_moved.len()
+        ),
+        ReloadOutcome::Rejected { problems } => {
+            eprintln!("⚠️ Content reload rejected, keeping the running world:");
+            for problem in problems {
+                eprintln!("   {}", problem);
+            }
+        }
+    }
+}
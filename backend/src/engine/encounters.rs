@@ -0,0 +1,185 @@
+use rand::{rngs::StdRng, SeedableRng};
+use sqlx::PgPool;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::db::players::{get_player, update_player_stats};
+use crate::db::status_effects::{get_status_effects, save_status_effects};
+use crate::engine::combat::{
+    evict_abandoned, ActiveEncounter, CombatAction, CombatEncounter, CombatEvent, CombatSessions, Combatant,
+    EncounterStatus, SharedEncounter,
+};
+use crate::engine::equipment::get_equipment;
+use crate::engine::skills::SkillError;
+
+#[derive(Debug)]
+pub enum EncounterError {
+    PlayerNotFound(i32),
+    /// The player has no health left to fight with
+    PlayerDefeated,
+    /// The player is already fighting this encounter
+    AlreadyFighting(Uuid),
+    EncounterNotFound(Uuid),
+    /// The encounter exists, but the player isn't fighting in it
+    NotInEncounter,
+    Action(SkillError),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for EncounterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncounterError::PlayerNotFound(player_id) => write!(f, "Player {} not found", player_id),
+            EncounterError::PlayerDefeated => write!(f, "You are too badly hurt to fight"),
+            EncounterError::AlreadyFighting(id) => write!(f, "You are already fighting in encounter {}", id),
+            EncounterError::EncounterNotFound(id) => write!(f, "Encounter {} not found", id),
+            EncounterError::NotInEncounter => write!(f, "You are not part of that encounter"),
+            EncounterError::Action(e) => e.fmt(f),
+            EncounterError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncounterError {}
+
+impl From<sqlx::Error> for EncounterError {
+    fn from(e: sqlx::Error) -> Self {
+        EncounterError::Database(e)
+    }
+}
+
+/// Health of the wild monsters a player of `level` runs into
+fn wild_monster_health(level: i32) -> i32 {
+    20 + 10 * level
+}
+
+/// The ongoing encounter a player is fighting in, if any
+pub async fn find_player_encounter(sessions: &CombatSessions, player_id: i32) -> Option<CombatEncounter> {
+    let shared: SharedEncounter = {
+        let mut sessions = sessions.lock().unwrap();
+        evict_abandoned(&mut sessions);
+        sessions.values().find(|active| active.player_id == player_id)?.encounter.clone()
+    };
+    let encounter = shared.lock().await;
+    (encounter.status == EncounterStatus::Ongoing).then(|| encounter.clone())
+}
+
+/// Start a new encounter against a wild monster of the player's level and play the first
+/// round. Players fight one encounter at a time.
+pub async fn start_encounter(
+    pool: &PgPool,
+    sessions: &CombatSessions,
+    player_id: i32,
+) -> Result<CombatEncounter, EncounterError> {
+    let mut player = get_player(pool, player_id)
+        .await?
+        .ok_or(EncounterError::PlayerNotFound(player_id))?;
+    player.status_effects = get_status_effects(pool, player_id).await?;
+    let equipment = get_equipment(pool, player_id).await?;
+    if player.health <= 0 {
+        return Err(EncounterError::PlayerDefeated);
+    }
+
+    let mut encounter = {
+        let mut rng = StdRng::from_entropy();
+        let participants = vec![
+            Combatant::from_player(&player, &equipment),
+            Combatant::monster("Wild Monster", player.level, wild_monster_health(player.level)),
+        ];
+        let mut encounter = CombatEncounter::new(participants, &mut rng);
+        encounter.run_round(&mut rng);
+        encounter
+    };
+
+    // Taken before the first round is saved, so a second start for the same player fails
+    // instead of fighting on with its own copy of their health
+    let shared = Arc::new(tokio::sync::Mutex::new(encounter.clone()));
+    let _playing = shared.lock().await;
+    {
+        let mut sessions = sessions.lock().unwrap();
+        evict_abandoned(&mut sessions);
+        if let Some((id, _)) = sessions.iter().find(|(_, active)| active.player_id == player_id) {
+            return Err(EncounterError::AlreadyFighting(*id));
+        }
+        sessions.insert(encounter.id, ActiveEncounter::new(player_id, shared.clone()));
+    }
+
+    let saved = persist_encounter(pool, player_id, &mut encounter).await;
+    if saved.is_err() || encounter.status != EncounterStatus::Ongoing {
+        sessions.lock().unwrap().remove(&encounter.id);
+    }
+    saved?;
+    Ok(encounter)
+}
+
+/// Advance an ongoing encounter by one round, with the player's action if they chose one.
+/// The encounter stays locked until the round is saved, so two requests for the same
+/// encounter play one round after the other and the later save can't undo the earlier one.
+pub async fn advance_encounter(
+    pool: &PgPool,
+    sessions: &CombatSessions,
+    player_id: i32,
+    encounter_id: Uuid,
+    action: Option<CombatAction>,
+) -> Result<CombatEncounter, EncounterError> {
+    let shared = {
+        let mut sessions = sessions.lock().unwrap();
+        evict_abandoned(&mut sessions);
+        let active = sessions.get_mut(&encounter_id).ok_or(EncounterError::EncounterNotFound(encounter_id))?;
+        active.touched_at = Instant::now();
+        active.encounter.clone()
+    };
+    let mut current = shared.lock().await;
+    // The round we waited for may have ended the fight
+    if current.status != EncounterStatus::Ongoing {
+        return Err(EncounterError::EncounterNotFound(encounter_id));
+    }
+
+    let mut encounter = current.clone();
+    let combatant_id = encounter
+        .player_combatant(player_id)
+        .map(|c| c.id.clone())
+        .ok_or(EncounterError::NotInEncounter)?;
+    if let Some(action) = action {
+        encounter.queue_action(&combatant_id, action).map_err(EncounterError::Action)?;
+    }
+    encounter.pass_time();
+    encounter.run_round(&mut StdRng::from_entropy());
+
+    // Only keep the round once it is saved
+    persist_encounter(pool, player_id, &mut encounter).await?;
+    *current = encounter.clone();
+    if encounter.status != EncounterStatus::Ongoing {
+        sessions.lock().unwrap().remove(&encounter_id);
+    }
+    Ok(encounter)
+}
+
+/// Write the player's health, mana and lasting effects back to the DB. A won encounter also
+/// awards its experience, and the level-ups it brings are added to the log.
+async fn persist_encounter(pool: &PgPool, player_id: i32, encounter: &mut CombatEncounter) -> Result<(), EncounterError> {
+    let Some(combatant) = encounter.player_combatant(player_id).cloned() else {
+        return Ok(());
+    };
+    let mut tx = pool.begin().await?;
+    let mut player = get_player(&mut *tx, player_id)
+        .await?
+        .ok_or(EncounterError::PlayerNotFound(player_id))?;
+    player.health = combatant.health;
+    player.mana = combatant.mana;
+    let experience = encounter.experience_reward();
+    let levels = player.gain_experience(experience);
+    update_player_stats(&mut *tx, &player).await?;
+    save_status_effects(&mut tx, player_id, &combatant.effects).await?;
+    tx.commit().await?;
+
+    if experience > 0 {
+        encounter.record(CombatEvent::ExperienceGained { combatant: combatant.name.clone(), amount: experience });
+    }
+    for level in (player.level - levels + 1)..=player.level {
+        encounter.record(CombatEvent::LeveledUp { combatant: combatant.name.clone(), level });
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use crate::db::audit::record_audit_entry;
+use crate::engine::inventory_logic::{lock_player_inventory, InventoryError};
+use crate::models::{Equipment, EquipmentSlot, EquippedItem, Item, ItemType};
+use serde_json::json;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum EquipError {
+    NotInInventory(i32),
+    NotEquippable { item: String, item_type: ItemType },
+    WrongSlot { item: String, slot: EquipmentSlot },
+    ItemBroken(String),
+    SlotEmpty(EquipmentSlot),
+    Cursed(String),
+    /// Lifting a curse from an item that has none
+    NotCursed(String),
+    Inventory(InventoryError),
+}
+
+impl fmt::Display for EquipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EquipError::NotInInventory(item_id) => write!(f, "Item {} is not in your inventory", item_id),
+            EquipError::NotEquippable { item, item_type } => {
+                write!(f, "{} can't be equipped ({} items are not wearable)", item, item_type)
+            }
+            EquipError::WrongSlot { item, slot } => write!(f, "{} doesn't fit the {} slot", item, slot.as_str()),
+            EquipError::ItemBroken(item) => write!(f, "{} is broken and can't be equipped", item),
+            EquipError::SlotEmpty(slot) => write!(f, "Nothing is equipped in the {} slot", slot.as_str()),
+            EquipError::Cursed(item) => write!(f, "{} is cursed and refuses to come off", item),
+            EquipError::NotCursed(item) => write!(f, "{} isn't cursed", item),
+            EquipError::Inventory(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EquipError {}
+
+impl From<InventoryError> for EquipError {
+    fn from(e: InventoryError) -> Self {
+        EquipError::Inventory(e)
+    }
+}
+
+impl From<sqlx::Error> for EquipError {
+    fn from(e: sqlx::Error) -> Self {
+        EquipError::Inventory(InventoryError::Database(e))
+    }
+}
+
+/// An inventory row joined with its item template
+#[derive(FromRow)]
+struct InventoryEntry {
+    inventory_id: i32,
+    #[sqlx(flatten)]
+    item: Item,
+}
+
+/// Everything the player currently has equipped
+pub async fn get_equipment<'e, E: PgExecutor<'e>>(executor: E, player_id: i32) -> Result<Equipment, sqlx::Error> {
+    let items = sqlx::query_as::<_, EquippedItem>(
+        r#"
+        SELECT pe.slot, inv.id AS inventory_id, i.id, i.name, COALESCE(i.description, '') AS description,
+               i.item_type, COALESCE(i.value, 0) AS value, COALESCE(i.power, 0) AS power,
+               inv.durability, COALESCE(i.is_magical, FALSE) AS is_magical, inv.is_cursed, i.effects
+        FROM player_equipment pe
+        JOIN inventory inv ON inv.id = pe.inventory_id
+        JOIN items i ON i.id = inv.item_id
+        WHERE pe.player_id = $1
+        "#,
+    )
+    .bind(player_id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(Equipment { items })
+}
+
+/// Equip an item from the player's inventory. Without an explicit slot the item goes into the
+/// first slot that accepts its type, and a copy that isn't already worn or broken is picked
+/// over one that is. Whatever was in that slot goes back to the inventory, unless it is cursed.
+pub async fn equip_item(
+    pool: &PgPool,
+    player_id: i32,
+    item_id: i32,
+    slot: Option<EquipmentSlot>,
+) -> Result<Equipment, EquipError> {
+    let mut tx = lock_player_inventory(pool, player_id).await?;
+
+    let entry = sqlx::query_as::<_, InventoryEntry>(
+        r#"
+        SELECT inv.id AS inventory_id, i.id, i.name, COALESCE(i.description, '') AS description,
+               i.item_type, COALESCE(i.value, 0) AS value, COALESCE(i.power, 0) AS power,
+               inv.durability, COALESCE(i.is_magical, FALSE) AS is_magical, inv.is_cursed, i.effects
+        FROM inventory inv
+        JOIN items i ON i.id = inv.item_id
+        LEFT JOIN player_equipment pe ON pe.inventory_id = inv.id
+        WHERE inv.player_id = $1 AND inv.item_id = $2 AND inv.quantity > 0
+        ORDER BY pe.inventory_id IS NOT NULL, COALESCE(inv.durability, 1) <= 0, inv.id
+        LIMIT 1
+        "#,
+    )
+    .bind(player_id)
+    .bind(item_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(EquipError::NotInInventory(item_id))?;
+    let item = entry.item;
+
+    let slot = match slot {
+        Some(slot) if slot.accepts(item.item_type) => slot,
+        Some(slot) => return Err(EquipError::WrongSlot { item: item.name, slot }),
+        None => EquipmentSlot::default_for(item.item_type).ok_or_else(|| EquipError::NotEquippable {
+            item: item.name.clone(),
+            item_type: item.item_type,
+        })?,
+    };
+    if item.durability == Some(0) {
+        return Err(EquipError::ItemBroken(item.name));
+    }
+
+    // A cursed item can't be swapped out, whether from the target slot or from another slot
+    // this same inventory row is moving away from
+    let equipment = get_equipment(&mut *tx, player_id).await?;
+    for equipped in &equipment.items {
+        let same_row = equipped.inventory_id == entry.inventory_id;
+        let displaced = (equipped.slot == slot) != same_row;
+        if displaced && equipped.item.is_cursed {
+            return Err(EquipError::Cursed(equipped.item.name.clone()));
+        }
+    }
+
+    sqlx::query("DELETE FROM player_equipment WHERE player_id = $1 AND inventory_id = $2")
+        .bind(player_id)
+        .bind(entry.inventory_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO player_equipment (player_id, slot, inventory_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (player_id, slot) DO UPDATE SET inventory_id = EXCLUDED.inventory_id
+        "#,
+    )
+    .bind(player_id)
+    .bind(slot.as_str())
+    .bind(entry.inventory_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let equipment = get_equipment(&mut *tx, player_id).await?;
+    tx.commit().await?;
+    Ok(equipment)
+}
+
+/// Take off whatever is in a slot. Cursed items stay put until the curse is lifted.
+pub async fn unequip_item(pool: &PgPool, player_id: i32, slot: EquipmentSlot) -> Result<Equipment, EquipError> {
+    let mut tx = lock_player_inventory(pool, player_id).await?;
+
+    let equipment = get_equipment(&mut *tx, player_id).await?;
+    let equipped = equipment.get(slot).ok_or(EquipError::SlotEmpty(slot))?;
+    if equipped.item.is_cursed {
+        return Err(EquipError::Cursed(equipped.item.name.clone()));
+    }
+
+    sqlx::query("DELETE FROM player_equipment WHERE player_id = $1 AND slot = $2")
+        .bind(player_id)
+        .bind(slot.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    let equipment = get_equipment(&mut *tx, player_id).await?;
+    tx.commit().await?;
+    Ok(equipment)
+}
+
+/// Remove the curse from the player's copy of whatever is in a slot. Only game masters do
+/// this, so `game_master_id` is recorded in the audit log alongside the item.
+pub async fn lift_curse(pool: &PgPool, game_master_id: i32, player_id: i32, slot: EquipmentSlot) -> Result<Equipment, EquipError> {
+    let mut tx = lock_player_inventory(pool, player_id).await?;
+
+    let equipment = get_equipment(&mut *tx, player_id).await?;
+    let equipped = equipment.get(slot).ok_or(EquipError::SlotEmpty(slot))?;
+    if !equipped.item.is_cursed {
+        return Err(EquipError::NotCursed(equipped.item.name.clone()));
+    }
+    sqlx::query("UPDATE inventory SET is_cursed = FALSE WHERE id = $1")
+        .bind(equipped.inventory_id)
+        .execute(&mut *tx)
+        .await?;
+    let change = |is_cursed: bool| json!({ "player_id": player_id, "slot": slot.as_str(), "item_id": equipped.item.id, "is_cursed": is_cursed });
+    let inventory_id = equipped.inventory_id.to_string();
+    record_audit_entry(&mut *tx, game_master_id, "lift_curse", "inventory", &inventory_id, Some(&change(true)), Some(&change(false))).await?;
+
+    let equipment = get_equipment(&mut *tx, player_id).await?;
+    tx.commit().await?;
+    Ok(equipment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::items::get_item;
+    use crate::db::players::create_player;
+    use crate::engine::inventory_logic::{add_item_to_inventory, remove_item_from_inventory};
+
+    // These need a database: `DATABASE_URL=... cargo test -- --ignored`
+
+    async fn weapon(pool: &PgPool, name: &str, is_cursed: bool) -> Item {
+        let item_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO items (name, item_type, power, durability, is_cursed, effects)
+            VALUES ($1, 'Weapon', 4, NULL, $2, '[]')
+            RETURNING id
+            "#,
+        )
+        .bind(name)
+        .bind(is_cursed)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        get_item(pool, item_id).await.unwrap().unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a database"]
+    async fn cursed_items_stay_on_until_the_curse_is_lifted(pool: PgPool) {
+        let player_id = create_player(&pool, "wearer", None, "not a hash").await.unwrap();
+        let (cursed, plain) = (weapon(&pool, "Grim Blade", true).await, weapon(&pool, "Plain Sword", false).await);
+        for item in [&cursed, &plain] {
+            add_item_to_inventory(&pool, player_id, player_id, item, 1).await.unwrap();
+        }
+
+        let equipment = equip_item(&pool, player_id, cursed.id, None).await.unwrap();
+        assert_eq!(equipment.attack_bonus(), -4);
+        let slot = EquipmentSlot::MainHand;
+        assert!(matches!(unequip_item(&pool, player_id, slot).await, Err(EquipError::Cursed(_))));
+        assert!(matches!(equip_item(&pool, player_id, plain.id, Some(slot)).await, Err(EquipError::Cursed(_))));
+        assert!(matches!(
+            remove_item_from_inventory(&pool, player_id, &cursed, 1).await,
+            Err(InventoryError::CursedItemEquipped(_))
+        ));
+        assert!(matches!(
+            equip_item(&pool, player_id, plain.id, Some(EquipmentSlot::Armor)).await,
+            Err(EquipError::WrongSlot { .. })
+        ));
+
+        let equipment = lift_curse(&pool, player_id, player_id, slot).await.unwrap();
+        assert_eq!(equipment.attack_bonus(), 4);
+        assert!(matches!(lift_curse(&pool, player_id, player_id, slot).await, Err(EquipError::NotCursed(_))));
+        let equipment = equip_item(&pool, player_id, plain.id, None).await.unwrap();
+        assert_eq!(equipment.get(slot).map(|e| e.item.id), Some(plain.id));
+    }
+}
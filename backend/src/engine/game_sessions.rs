@@ -0,0 +1,366 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::db::players::{get_player, get_player_region};
+use crate::engine::interpreter::{CommandOutput, CommandResponse};
+use crate::engine::presence::{RegionEvent, SharedPresence};
+
+/// How long a session whose connection dropped can be resumed
+pub const RESUME_WINDOW: Duration = Duration::from_secs(120);
+/// Events kept per session for a client that is slow or reconnecting. Past this the oldest are
+/// dropped and the client is told how many it missed.
+pub const SESSION_BUFFER: usize = 256;
+/// How often sessions past `RESUME_WINDOW` are looked for, so their players can be seen leaving
+const SWEEP_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Something that happened in the game, as streamed to a connected client
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// The answer to a command sent on this session: room descriptions, combat rounds and the rest
+    Response(CommandResponse),
+    /// Another player in the region arrived, left, spoke or emoted
+    #[serde(untagged)]
+    Region(RegionEvent),
+}
+
+/// What a connection needs to stream a session's events. Stale once another connection
+/// resumes the session.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub session_id: Uuid,
+    /// Hand this back to pick the session up again after a disconnect
+    pub resume_token: String,
+    /// False when a new session was opened
+    pub resumed: bool,
+    generation: u64,
+    notify: Arc<Notify>,
+}
+
+impl Attachment {
+    /// Wait until there are new events, or until the attachment is replaced
+    pub async fn changed(&self) {
+        self.notify.notified().await
+    }
+}
+
+/// Events for a connection to send
+#[derive(Debug)]
+pub struct Pending {
+    pub events: Vec<(u64, GameEvent)>,
+    /// Events after the connection's cursor that were dropped before it got to them
+    pub missed: u64,
+}
+
+struct Session {
+    player_id: i32,
+    username: String,
+    resume_token: String,
+    /// The newest `SESSION_BUFFER` events, oldest first
+    events: VecDeque<(u64, GameEvent)>,
+    next_seq: u64,
+    /// Bumped whenever a connection attaches, so the one it replaced stops
+    generation: u64,
+    notify: Arc<Notify>,
+    /// Set while no connection is attached
+    detached_at: Option<Instant>,
+}
+
+impl Session {
+    fn push(&mut self, event: GameEvent) {
+        self.events.push_back((self.next_seq, event));
+        self.next_seq += 1;
+        if self.events.len() > SESSION_BUFFER {
+            self.events.pop_front();
+        }
+        self.notify.notify_one();
+    }
+
+    fn attachment(&self, session_id: Uuid, resumed: bool) -> Attachment {
+        Attachment {
+            session_id,
+            resume_token: self.resume_token.clone(),
+            resumed,
+            generation: self.generation,
+            notify: self.notify.clone(),
+        }
+    }
+}
+
+fn new_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// The game sessions of connected clients, and of clients that dropped recently enough to
+/// resume. Publishing never waits on a client; each session buffers its own events. A player
+/// with a session is present in their region until the last one expires.
+pub struct GameSessions {
+    sessions: Mutex<HashMap<Uuid, Session>>,
+    presence: SharedPresence,
+}
+
+pub type SharedGameSessions = Arc<GameSessions>;
+
+impl GameSessions {
+    /// Keep track of sessions, deliver region events to them and expire the ones nobody
+    /// resumed
+    pub fn start(presence: SharedPresence) -> SharedGameSessions {
+        // Subscribed before anything can be published, so no message goes by unseen
+        let mut messages = presence.subscribe();
+        let sessions = Arc::new(GameSessions { sessions: Mutex::new(HashMap::new()), presence });
+        let delivering = sessions.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                tokio::select! {
+                    message = messages.recv() => match message {
+                        Ok(message) => {
+                            if delivering.presence.apply(&message) {
+                                let audience = delivering.presence.audience(&message);
+                                delivering.publish_to_players(&audience, GameEvent::Region(message.event));
+                            }
+                        }
+                        Err(RecvError::Lagged(missed)) => {
+                            eprintln!("⚠️ Fell behind on region messages; {} were dropped", missed)
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = ticker.tick() => delivering.expire(),
+                }
+            }
+        });
+        sessions
+    }
+
+    /// Start a session for a player in `region`. Everyone there sees them arrive unless they
+    /// were connected already.
+    pub fn open(&self, player_id: i32, username: &str, region: Option<&str>) -> Attachment {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.sweep(&mut sessions);
+        let connected = sessions.values().any(|session| session.player_id == player_id);
+        let session_id = Uuid::new_v4();
+        let session = Session {
+            player_id,
+            username: username.to_string(),
+            resume_token: new_token(),
+            events: VecDeque::new(),
+            next_seq: 1,
+            generation: 0,
+            notify: Arc::new(Notify::new()),
+            detached_at: None,
+        };
+        let attachment = session.attachment(session_id, false);
+        sessions.insert(session_id, session);
+        if let (false, Some(region)) = (connected, region) {
+            self.presence.enter(player_id, username, region);
+        }
+        attachment
+    }
+
+    /// Pick up the player's session with `resume_token`, replacing any connection still attached
+    /// to it. The token is swapped for a new one. `None` if there is no such session or it
+    /// can't be resumed anymore.
+    pub fn resume(&self, player_id: i32, resume_token: &str) -> Option<Attachment> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.sweep(&mut sessions);
+        let (session_id, session) = sessions
+            .iter_mut()
+            .find(|(_, session)| session.player_id == player_id && session.resume_token == resume_token)?;
+
+        // Wake the old connection so it sees it was replaced
+        session.notify.notify_one();
+        session.notify = Arc::new(Notify::new());
+        session.generation += 1;
+        session.resume_token = new_token();
+        session.detached_at = None;
+        Some(session.attachment(*session_id, true))
+    }
+
+    /// Mark the session as having no connection, unless another one has taken it over since
+    pub fn detach(&self, attachment: &Attachment) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get_mut(&attachment.session_id) {
+            if session.generation == attachment.generation {
+                session.detached_at = Some(Instant::now());
+            }
+        }
+        self.sweep(&mut sessions);
+    }
+
+    /// Drop the sessions nobody resumed in time
+    pub fn expire(&self) {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.sweep(&mut sessions);
+    }
+
+    /// Drop sessions that have been without a connection for longer than `RESUME_WINDOW`, and
+    /// tell the regions of the players whose last session that was that they left
+    fn sweep(&self, sessions: &mut HashMap<Uuid, Session>) {
+        let mut expired = take_expired(sessions);
+        expired.dedup_by_key(|session| session.player_id);
+        for session in expired {
+            if !sessions.values().any(|other| other.player_id == session.player_id) {
+                self.presence.leave(session.player_id, &session.username);
+            }
+        }
+    }
+
+    /// The events after `after`, or `None` if the attachment was replaced or the session is gone
+    pub fn pending(&self, attachment: &Attachment, after: u64) -> Option<Pending> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(&attachment.session_id)?;
+        if session.generation != attachment.generation {
+            return None;
+        }
+        let oldest = session.events.front().map_or(session.next_seq, |(seq, _)| *seq);
+        let events = session.events.iter().filter(|(seq, _)| *seq > after).cloned().collect();
+        Some(Pending { events, missed: oldest.saturating_sub(after + 1) })
+    }
+
+    /// Sequence number of the newest event so far
+    pub fn last_seq(&self, attachment: &Attachment) -> u64 {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(&attachment.session_id).map_or(0, |session| session.next_seq - 1)
+    }
+
+    /// Queue an event for one session
+    pub fn publish(&self, session_id: Uuid, event: GameEvent) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&session_id) {
+            session.push(event);
+        }
+    }
+
+    /// Queue an event for every session of the given players
+    pub fn publish_to_players(&self, player_ids: &[i32], event: GameEvent) {
+        let mut sessions = self.sessions.lock().unwrap();
+        for session in sessions.values_mut().filter(|session| player_ids.contains(&session.player_id)) {
+            session.push(event.clone());
+        }
+    }
+
+    /// Tell everyone else in the region what the player's command did there: what they said
+    /// or emoted, and that they left for or arrived from another region. A `look` that finds
+    /// the player somewhere the registry doesn't, e.g. after an announcement was lost, moves
+    /// them there. Works for commands sent over HTTP too.
+    pub async fn announce(&self, pool: &PgPool, player_id: i32, response: &CommandResponse) -> Result<(), sqlx::Error> {
+        match &response.output {
+            Some(CommandOutput::Said { speaker, message }) => {
+                if let Some(region) = get_player_region(pool, player_id).await? {
+                    let event = RegionEvent::Said { speaker: speaker.clone(), message: message.clone() };
+                    self.presence.publish(&region, player_id, event);
+                }
+            }
+            Some(CommandOutput::Emoted { player, action }) => {
+                if let Some(region) = get_player_region(pool, player_id).await? {
+                    let event = RegionEvent::Emoted { player: player.clone(), action: action.clone() };
+                    self.presence.publish(&region, player_id, event);
+                }
+            }
+            Some(CommandOutput::Arrival(arrival)) => {
+                if let Some(player) = get_player(pool, player_id).await? {
+                    self.presence.travel(player_id, &player.username, &arrival.from, &arrival.location.region_id);
+                }
+            }
+            Some(CommandOutput::Location(location)) => {
+                let Some(known) = self.presence.region_of(player_id) else {
+                    return Ok(());
+                };
+                if known != location.region_id {
+                    if let Some(player) = get_player(pool, player_id).await? {
+                        self.presence.travel(player_id, &player.username, &known, &location.region_id);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Remove the sessions that have been without a connection for longer than `RESUME_WINDOW`,
+/// ordered by player
+fn take_expired(sessions: &mut HashMap<Uuid, Session>) -> Vec<Session> {
+    let expired: Vec<Uuid> = sessions
+        .iter()
+        .filter(|(_, session)| match session.detached_at {
+            Some(at) => at.elapsed() >= RESUME_WINDOW,
+            None => false,
+        })
+        .map(|(session_id, _)| *session_id)
+        .collect();
+    let mut expired: Vec<Session> = expired.iter().filter_map(|session_id| sessions.remove(session_id)).collect();
+    expired.sort_by_key(|session| session.player_id);
+    expired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::presence::{LocalBackend, Presence};
+
+    fn sessions() -> SharedGameSessions {
+        GameSessions::start(Arc::new(Presence::new(Arc::new(LocalBackend::default()))))
+    }
+
+    fn said(n: u64) -> GameEvent {
+        GameEvent::Region(RegionEvent::Said { speaker: "echo".to_string(), message: n.to_string() })
+    }
+
+    fn seqs(pending: &Pending) -> Vec<u64> {
+        pending.events.iter().map(|(seq, _)| *seq).collect()
+    }
+
+    #[tokio::test]
+    async fn a_full_buffer_reports_what_was_missed() {
+        let sessions = sessions();
+        let attachment = sessions.open(1, "wanderer", None);
+        for n in 1..=300 {
+            sessions.publish(attachment.session_id, said(n));
+        }
+        assert_eq!(sessions.last_seq(&attachment), 300);
+
+        let behind = sessions.pending(&attachment, 0).unwrap();
+        assert_eq!(behind.events.len(), SESSION_BUFFER);
+        assert_eq!(seqs(&behind).first(), Some(&45));
+        assert_eq!(behind.missed, 44);
+
+        let caught_up = sessions.pending(&attachment, 290).unwrap();
+        assert_eq!(seqs(&caught_up), (291..=300).collect::<Vec<_>>());
+        assert_eq!(caught_up.missed, 0);
+        assert_eq!(sessions.pending(&attachment, 44).unwrap().missed, 0);
+    }
+
+    #[tokio::test]
+    async fn resuming_replaces_the_old_connection() {
+        let sessions = sessions();
+        let old = sessions.open(1, "wanderer", None);
+        for n in 1..=3 {
+            sessions.publish(old.session_id, said(n));
+        }
+
+        assert!(sessions.resume(2, &old.resume_token).is_none());
+        let new = sessions.resume(1, &old.resume_token).unwrap();
+        assert!(new.resumed);
+        assert_eq!(new.session_id, old.session_id);
+        assert_ne!(new.resume_token, old.resume_token);
+        assert!(sessions.resume(1, &old.resume_token).is_none());
+
+        // The old connection is woken and finds it was replaced
+        tokio::time::timeout(Duration::from_secs(1), old.changed()).await.unwrap();
+        assert!(sessions.pending(&old, 0).is_none());
+        assert_eq!(seqs(&sessions.pending(&new, 1).unwrap()), vec![2, 3]);
+
+        // Its disconnect leaves the new one attached
+        sessions.detach(&old);
+        assert!(sessions.sessions.lock().unwrap()[&new.session_id].detached_at.is_none());
+        sessions.detach(&new);
+        assert!(sessions.sessions.lock().unwrap()[&new.session_id].detached_at.is_some());
+        assert!(sessions.resume(1, &new.resume_token).is_some());
+    }
+}
@@ -0,0 +1,409 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use std::fmt;
+
+use crate::db::items::get_item;
+use crate::db::players::{get_player, get_player_region};
+use crate::db::skills::get_player_skills;
+use crate::engine::combat::{CombatAction, CombatEncounter, CombatSessions, EncounterStatus};
+use crate::engine::commands::{find_verb, fuzzy_find, help_text, parse, Command, FuzzyMatch, ParseError, Verb, VERBS};
+use crate::engine::content::LiveContent;
+use crate::engine::encounters::{advance_encounter, find_player_encounter, start_encounter, EncounterError};
+use crate::engine::inventory_logic::{get_inventory_for_player, use_item_from_inventory, InventoryError};
+use crate::engine::map_graph::MapGraph;
+use crate::engine::presence::{Present, Presence};
+use crate::engine::rooms::{walk, RoomError, RoomView};
+use crate::engine::travel::{get_location, travel, Arrival, Location, TravelError};
+use crate::models::{Direction, Item, ItemEffect, ItemType, ItemUse, Player};
+
+/// What a command reads and changes
+pub struct CommandContext<'a> {
+    pub pool: &'a PgPool,
+    pub content: &'a LiveContent,
+    pub sessions: &'a CombatSessions,
+    pub presence: &'a Presence,
+}
+
+/// A carried item, as the terminal lists it
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryLine {
+    pub item_id: i32,
+    pub name: String,
+    pub item_type: ItemType,
+    pub quantity: i32,
+    pub durability: Option<i32>,
+    pub is_cursed: bool,
+}
+
+/// The structured result of a command, for frontends that want more than the text
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum CommandOutput {
+    Location(Location),
+    Arrival(Arrival),
+    /// The room the player walked to, in a region with rooms
+    Room(RoomView),
+    Inventory(Vec<InventoryLine>),
+    ItemUsed { item: String, outcome: ItemUse },
+    Combat(CombatEncounter),
+    Said { speaker: String, message: String },
+    Emoted { player: String, action: String },
+    /// Everyone connected in the player's region, the player included if they are
+    Who { region_id: Option<String>, players: Vec<Present> },
+    Help { verbs: Vec<Verb> },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandResponse {
+    pub input: String,
+    /// The verb the input was understood as
+    pub verb: Option<&'static str>,
+    /// False when the command couldn't be carried out; `text` says why
+    pub ok: bool,
+    pub output: Option<CommandOutput>,
+    /// What to print in the terminal
+    pub text: String,
+}
+
+/// Problems the player can't do anything about. Everything else is answered in the response.
+#[derive(Debug)]
+pub enum CommandError {
+    PlayerNotFound(i32),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::PlayerNotFound(player_id) => write!(f, "Player {} not found", player_id),
+            CommandError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Why a command didn't go through: something to tell the player, or a `CommandError`
+enum Failure {
+    Rejected(String),
+    Fatal(CommandError),
+}
+
+impl From<sqlx::Error> for Failure {
+    fn from(e: sqlx::Error) -> Self {
+        Failure::Fatal(CommandError::Database(e))
+    }
+}
+
+impl From<TravelError> for Failure {
+    fn from(e: TravelError) -> Self {
+        match e {
+            TravelError::PlayerNotFound(player_id) => Failure::Fatal(CommandError::PlayerNotFound(player_id)),
+            TravelError::Database(e) => e.into(),
+            e => Failure::Rejected(e.to_string()),
+        }
+    }
+}
+
+impl From<RoomError> for Failure {
+    fn from(e: RoomError) -> Self {
+        match e {
+            RoomError::PlayerNotFound(player_id) => Failure::Fatal(CommandError::PlayerNotFound(player_id)),
+            RoomError::Database(e) => e.into(),
+            e => Failure::Rejected(e.to_string()),
+        }
+    }
+}
+
+impl From<InventoryError> for Failure {
+    fn from(e: InventoryError) -> Self {
+        match e {
+            InventoryError::PlayerNotFound(player_id) => Failure::Fatal(CommandError::PlayerNotFound(player_id)),
+            InventoryError::Database(e) => e.into(),
+            e => Failure::Rejected(e.to_string()),
+        }
+    }
+}
+
+impl From<EncounterError> for Failure {
+    fn from(e: EncounterError) -> Self {
+        match e {
+            EncounterError::PlayerNotFound(player_id) => Failure::Fatal(CommandError::PlayerNotFound(player_id)),
+            EncounterError::Database(e) => e.into(),
+            e => Failure::Rejected(e.to_string()),
+        }
+    }
+}
+
+type Handled = Result<(CommandOutput, String), Failure>;
+
+/// Parse and carry out one line typed by a player
+pub async fn execute(ctx: &CommandContext<'_>, player_id: i32, input: &str) -> Result<CommandResponse, CommandError> {
+    let rejected = |verb, text| CommandResponse { input: input.to_string(), verb, ok: false, output: None, text };
+    let command = match parse(input) {
+        Ok(command) => command,
+        Err(e) => return Ok(rejected(None, e.to_string())),
+    };
+    let verb = Some(command.verb());
+    let player = get_player(ctx.pool, player_id)
+        .await
+        .map_err(CommandError::Database)?
+        .ok_or(CommandError::PlayerNotFound(player_id))?;
+
+    let map = ctx.content.map();
+    let handled = match command {
+        Command::Look => look(ctx, &map, player_id).await,
+        Command::Go { portal } => go(ctx, &map, player_id, &portal).await,
+        Command::Inventory => inventory(ctx, player_id).await,
+        Command::Use { item } => use_item(ctx, &map, player_id, &item).await,
+        Command::Attack { target } => attack(ctx, &player, target.as_deref()).await,
+        Command::Cast { skill, target } => cast(ctx, player_id, &skill, target.as_deref()).await,
+        Command::Say { message } => {
+            let text = format!("You say, \"{}\"", message);
+            Ok((CommandOutput::Said { speaker: player.username.clone(), message }, text))
+        }
+        Command::Emote { action } => {
+            let text = format!("{} {}", player.username, action);
+            Ok((CommandOutput::Emoted { player: player.username.clone(), action }, text))
+        }
+        Command::Who => who(ctx, player_id).await,
+        Command::Help { topic } => help(topic.as_deref()),
+    };
+    match handled {
+        Ok((output, text)) => Ok(CommandResponse { input: input.to_string(), verb, ok: true, output: Some(output), text }),
+        Err(Failure::Rejected(text)) => Ok(rejected(verb, text)),
+        Err(Failure::Fatal(e)) => Err(e),
+    }
+}
+
+/// Pick one of `items` by what the player typed, or say why not. `what` names the kind of
+/// thing, e.g. "portal".
+fn choose<'a, T>(query: &str, items: &'a [T], what: &str, names: impl Fn(&T) -> Vec<&str>) -> Result<&'a T, Failure> {
+    match fuzzy_find(query, items, &names) {
+        FuzzyMatch::One(item) => Ok(item),
+        FuzzyMatch::Many(matches) => {
+            let names: Vec<&str> = matches.iter().map(|item| names(*item)[0]).collect();
+            Err(Failure::Rejected(format!("Which {} do you mean: {}?", what, names.join(", "))))
+        }
+        FuzzyMatch::None => Err(Failure::Rejected(format!("You don't see any {} like '{}'.", what, query))),
+    }
+}
+
+fn render_location(location: &Location) -> String {
+    let mut text = format!("{}\n{}", location.name, location.description);
+    if location.portals.is_empty() {
+        text.push_str("\nThere are no portals here.");
+        return text;
+    }
+    text.push_str("\nPortals:");
+    for option in &location.portals {
+        let destination = option.destination.as_deref().unwrap_or("nowhere");
+        text.push_str(&format!("\n  {} → {}", option.portal.name, destination));
+        if let Some(blocked) = &option.blocked {
+            text.push_str(&format!(" ({})", blocked));
+        }
+    }
+    text
+}
+
+async fn look(ctx: &CommandContext<'_>, map: &MapGraph, player_id: i32) -> Handled {
+    let location = get_location(ctx.pool, map, player_id).await?;
+    let text = render_location(&location);
+    Ok((CommandOutput::Location(location), text))
+}
+
+fn render_room(room: &RoomView) -> String {
+    let mut text = room.map.join("\n");
+    if room.exits.is_empty() {
+        text.push_str("\nThere is no way on from here.");
+    } else {
+        let exits: Vec<&str> = room.exits.iter().map(Direction::as_str).collect();
+        text.push_str(&format!("\nExits: {}", exits.join(", ")));
+    }
+    for portal in &room.portals {
+        text.push_str(&format!("\n{} is here.", portal.name));
+    }
+    text
+}
+
+async fn go(ctx: &CommandContext<'_>, map: &MapGraph, player_id: i32, query: &str) -> Handled {
+    // In a region with rooms a direction walks to the next room; elsewhere it may name a portal
+    if let Ok(direction) = Direction::try_from(query.to_string()) {
+        match walk(ctx.pool, map, player_id, direction).await {
+            Ok(room) => {
+                let text = format!("You walk {}.\n{}", direction, render_room(&room));
+                return Ok((CommandOutput::Room(room), text));
+            }
+            Err(RoomError::NoLayout(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let location = get_location(ctx.pool, map, player_id).await?;
+    let option = choose(query, &location.portals, "portal", |option| {
+        let mut names = vec![option.portal.name.as_str(), option.portal.id.as_str()];
+        names.extend(option.destination.as_deref());
+        names
+    })?;
+    let arrival = travel(ctx.pool, map, player_id, &option.portal.id).await?;
+    let text = format!(
+        "You step through {} and arrive in {}.\n\n{}",
+        option.portal.name,
+        arrival.location.name,
+        render_location(&arrival.location)
+    );
+    Ok((CommandOutput::Arrival(arrival), text))
+}
+
+/// Everything the player carries, with the item templates
+async fn carried_items(ctx: &CommandContext<'_>, player_id: i32) -> Result<Vec<(InventoryLine, Item)>, Failure> {
+    let mut carried = Vec::new();
+    for stack in get_inventory_for_player(ctx.pool, player_id).await? {
+        if stack.quantity <= 0 {
+            continue;
+        }
+        let Some(item) = get_item(ctx.pool, stack.item_id).await? else {
+            continue;
+        };
+        let line = InventoryLine {
+            item_id: item.id,
+            name: item.name.clone(),
+            item_type: item.item_type,
+            quantity: stack.quantity,
+            durability: stack.durability,
+            is_cursed: stack.is_cursed,
+        };
+        carried.push((line, item));
+    }
+    Ok(carried)
+}
+
+async fn inventory(ctx: &CommandContext<'_>, player_id: i32) -> Handled {
+    let lines: Vec<InventoryLine> = carried_items(ctx, player_id).await?.into_iter().map(|(line, _)| line).collect();
+    let mut text = if lines.is_empty() {
+        "You aren't carrying anything.".to_string()
+    } else {
+        "You are carrying:".to_string()
+    };
+    for line in &lines {
+        text.push_str(&format!("\n  {} × {}", line.quantity, line.name));
+        if let Some(durability) = line.durability {
+            text.push_str(&format!(" (durability {})", durability));
+        }
+    }
+    Ok((CommandOutput::Inventory(lines), text))
+}
+
+async fn use_item(ctx: &CommandContext<'_>, map: &MapGraph, player_id: i32, query: &str) -> Handled {
+    let mut carried = carried_items(ctx, player_id).await?;
+    // Copies held one row each are still one item to choose from
+    carried.dedup_by_key(|(line, _)| line.item_id);
+    let (_, item) = choose(query, &carried, "item", |(line, _)| vec![line.name.as_str()])?;
+    let outcome = use_item_from_inventory(ctx.pool, player_id, item).await?;
+
+    let mut text = format!("You use the {}.", item.name);
+    if outcome.healed > 0 {
+        text.push_str(&format!(" You recover {} health.", outcome.healed));
+    }
+    for effect in &outcome.applied {
+        text.push_str(&format!(" {} takes hold ({}).", effect.name, effect.kind.as_str()));
+    }
+    for effect in &outcome.pending {
+        match effect {
+            ItemEffect::Heal { .. } | ItemEffect::ApplyEffect { .. } => {}
+            ItemEffect::GrantSkill { skill } => text.push_str(&format!(" You learn {}.", skill)),
+            ItemEffect::UnlockPortal { portal_id } => {
+                let portal = map
+                    .regions
+                    .values()
+                    .flat_map(|region| &region.portals)
+                    .find(|portal| &portal.id == portal_id)
+                    .map_or(portal_id.as_str(), |portal| portal.name.as_str());
+                text.push_str(&format!(" {} is now open to you.", portal));
+            }
+            ItemEffect::SummonMinion { minion_type, .. } => {
+                text.push_str(&format!(" A {} answers your call.", minion_type))
+            }
+        }
+    }
+    Ok((CommandOutput::ItemUsed { item: item.name.clone(), outcome }, text))
+}
+
+/// The ID of the living combatant the player means; `me` is the player
+fn combat_target(encounter: &CombatEncounter, player_id: i32, query: &str) -> Result<String, Failure> {
+    if ["me", "self", "myself"].contains(&query.to_lowercase().as_str()) {
+        if let Some(combatant) = encounter.player_combatant(player_id) {
+            return Ok(combatant.id.clone());
+        }
+    }
+    let living: Vec<_> = encounter.participants.iter().filter(|c| c.is_alive()).collect();
+    let target = choose(query, &living, "target", |c| vec![c.name.as_str(), c.id.as_str()])?;
+    Ok(target.id.clone())
+}
+
+/// The log of the rounds after the first `from` entries, and how the fight stands
+fn render_combat(encounter: &CombatEncounter, from: usize, player_id: i32) -> String {
+    let mut lines: Vec<String> = encounter.log[from.min(encounter.log.len())..].iter().map(|e| e.message.clone()).collect();
+    let team = encounter.player_combatant(player_id).map(|c| c.team);
+    match &encounter.status {
+        EncounterStatus::Finished { winner } if Some(*winner) == team => lines.push("You are victorious!".to_string()),
+        EncounterStatus::Finished { .. } => lines.push("You have been defeated.".to_string()),
+        EncounterStatus::Ongoing => {
+            for combatant in &encounter.participants {
+                lines.push(format!("  {}: {}/{} HP", combatant.name, combatant.health, combatant.max_health));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+async fn attack(ctx: &CommandContext<'_>, player: &Player, target: Option<&str>) -> Handled {
+    let Some(encounter) = find_player_encounter(ctx.sessions, player.id).await else {
+        let encounter = start_encounter(ctx.pool, ctx.sessions, player.id).await?;
+        let text = format!("A wild monster attacks!\n{}", render_combat(&encounter, 0, player.id));
+        return Ok((CommandOutput::Combat(encounter), text));
+    };
+    let target = target.map(|query| combat_target(&encounter, player.id, query)).transpose()?;
+    let before = encounter.log.len();
+    let encounter =
+        advance_encounter(ctx.pool, ctx.sessions, player.id, encounter.id, Some(CombatAction::Attack { target })).await?;
+    let text = render_combat(&encounter, before, player.id);
+    Ok((CommandOutput::Combat(encounter), text))
+}
+
+async fn cast(ctx: &CommandContext<'_>, player_id: i32, skill: &str, target: Option<&str>) -> Handled {
+    let Some(encounter) = find_player_encounter(ctx.sessions, player_id).await else {
+        return Err(Failure::Rejected("You aren't fighting anything. Type `attack` to start a fight.".to_string()));
+    };
+    let skills = get_player_skills(ctx.pool, player_id).await?;
+    let skill = choose(skill, &skills, "skill", |s| vec![s.skill.name.as_str()])?.clone();
+    let target = target.map(|query| combat_target(&encounter, player_id, query)).transpose()?;
+
+    let before = encounter.log.len();
+    let action = CombatAction::UseSkill { skill, target };
+    let encounter = advance_encounter(ctx.pool, ctx.sessions, player_id, encounter.id, Some(action)).await?;
+    let text = render_combat(&encounter, before, player_id);
+    Ok((CommandOutput::Combat(encounter), text))
+}
+
+async fn who(ctx: &CommandContext<'_>, player_id: i32) -> Handled {
+    let region_id = get_player_region(ctx.pool, player_id).await?;
+    let players = region_id.as_deref().map(|region_id| ctx.presence.who(region_id)).unwrap_or_default();
+    let others: Vec<&str> =
+        players.iter().filter(|p| p.player_id != player_id).map(|p| p.username.as_str()).collect();
+    let text = if others.is_empty() {
+        "Nobody else is here.".to_string()
+    } else {
+        format!("Here with you: {}.", others.join(", "))
+    };
+    Ok((CommandOutput::Who { region_id, players }, text))
+}
+
+fn help(topic: Option<&str>) -> Handled {
+    let rejected = |e: ParseError| Failure::Rejected(e.to_string());
+    let verbs = match topic {
+        Some(topic) => vec![*find_verb(topic).map_err(rejected)?],
+        None => VERBS.to_vec(),
+    };
+    let text = help_text(topic).map_err(rejected)?;
+    Ok((CommandOutput::Help { verbs }, text))
+}
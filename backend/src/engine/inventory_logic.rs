@@ -1,43 +1,421 @@
-use crate::models::{Player, Item, Inventory};
-use sqlx::PgPool;
+use crate::db::audit::record_audit_entry;
+use crate::db::players::{get_player, update_player_stats};
+use crate::db::skills::learn_skill;
+use crate::db::status_effects::{get_status_effects, save_status_effects};
+use crate::models::{Item, ItemEffect, ItemUse, ItemUseError, Inventory};
+use serde_json::json;
+use sqlx::{FromRow, PgExecutor, PgPool, Postgres, Transaction};
+use std::fmt;
 
-pub async fn add_item_to_inventory(pool: &PgPool, player_id: i32, item: Item, quantity: i32) {
-    let mut inventory = get_inventory_for_player(pool, player_id).await;
-    match inventory.iter_mut().find(|i| i.item_id == item.id) {
-        Some(existing_item) => existing_item.add_item(quantity),
-        None => {
-            let new_item = Inventory {
-                player_id,
-                item_id: item.id,
-                quantity,
-            };
-            inventory.push(new_item);
+/// Most copies one request may add or remove. Copies that don't stack get a row each, so this
+/// also bounds the rows written while the player is locked.
+pub const MAX_QUANTITY: i32 = 100;
+/// Most copies one stack may hold
+pub const MAX_STACK: i32 = 999;
+
+#[derive(Debug)]
+pub enum InventoryError {
+    /// Not positive, over `MAX_QUANTITY`, or more than the stack has room for
+    InvalidQuantity(i32),
+    NotEnoughQuantity { item_id: i32, requested: i32, available: i32 },
+    PlayerNotFound(i32),
+    CursedItemEquipped(i32),
+    Unusable(ItemUseError),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InventoryError::InvalidQuantity(quantity) => write!(
+                f,
+                "Quantity must be between 1 and {}, and stacks hold at most {}; got {}",
+                MAX_QUANTITY, MAX_STACK, quantity
+            ),
+            InventoryError::NotEnoughQuantity { item_id, requested, available } => write!(
+                f,
+                "Not enough quantity of item {} (requested {}, have {})",
+                item_id, requested, available
+            ),
+            InventoryError::PlayerNotFound(player_id) => write!(f, "Player {} not found", player_id),
+            InventoryError::CursedItemEquipped(item_id) => {
+                write!(f, "Item {} is cursed and equipped, so it can't be removed", item_id)
+            }
+            InventoryError::Unusable(e) => write!(f, "{}", e),
+            InventoryError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for InventoryError {}
+
+impl From<sqlx::Error> for InventoryError {
+    fn from(e: sqlx::Error) -> Self {
+        InventoryError::Database(e)
+    }
+}
+
+/// Give a player copies of an item on a game master's say-so. Plain items go onto the player's
+/// stack; items with durability or a curse get a row per copy, starting from the item's own
+/// durability and curse. Returns every row the player now holds of the item.
+pub async fn add_item_to_inventory(
+    pool: &PgPool,
+    game_master_id: i32,
+    player_id: i32,
+    item: &Item,
+    quantity: i32,
+) -> Result<Vec<Inventory>, InventoryError> {
+    if !(1..=MAX_QUANTITY).contains(&quantity) {
+        return Err(InventoryError::InvalidQuantity(quantity));
+    }
+
+    let mut tx = lock_player_inventory(pool, player_id).await?;
+    if item.stacks() {
+        let added = sqlx::query(
+            r#"
+            INSERT INTO inventory (player_id, item_id, quantity, durability, is_cursed, stacked)
+            VALUES ($1, $2, $3, NULL, FALSE, TRUE)
+            ON CONFLICT (player_id, item_id) WHERE stacked
+            DO UPDATE SET quantity = inventory.quantity + EXCLUDED.quantity
+            WHERE inventory.quantity + EXCLUDED.quantity <= $4
+            "#,
+        )
+        .bind(player_id)
+        .bind(item.id)
+        .bind(quantity)
+        .bind(MAX_STACK)
+        .execute(&mut *tx)
+        .await?;
+        if added.rows_affected() == 0 {
+            return Err(InventoryError::InvalidQuantity(quantity));
         }
+    } else {
+        sqlx::query(
+            r#"
+            INSERT INTO inventory (player_id, item_id, quantity, durability, is_cursed, stacked)
+            SELECT $1, $2, 1, $3, $4, FALSE
+            FROM generate_series(1, $5)
+            "#,
+        )
+        .bind(player_id)
+        .bind(item.id)
+        .bind(item.durability)
+        .bind(item.is_cursed)
+        .bind(quantity)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let grant = json!({ "item_id": item.id, "quantity": quantity });
+    record_audit_entry(&mut *tx, game_master_id, "grant_item", "player", &player_id.to_string(), None, Some(&grant)).await?;
+
+    let rows = held_rows(&mut *tx, player_id, item.id).await?.into_iter().map(|held| held.row).collect();
+    tx.commit().await?;
+    Ok(rows)
+}
+
+/// Take copies of an item away, unequipped copies first. Copies that are cursed and equipped
+/// stay put. Returns every row the player still holds of the item.
+pub async fn remove_item_from_inventory(
+    pool: &PgPool,
+    player_id: i32,
+    item: &Item,
+    quantity: i32,
+) -> Result<Vec<Inventory>, InventoryError> {
+    if !(1..=MAX_QUANTITY).contains(&quantity) {
+        return Err(InventoryError::InvalidQuantity(quantity));
+    }
+
+    let mut tx = lock_player_inventory(pool, player_id).await?;
+    let held = held_rows(&mut *tx, player_id, item.id).await?;
+    let available: i32 = held.iter().map(|h| h.row.quantity).sum();
+    if available < quantity {
+        return Err(InventoryError::NotEnoughQuantity { item_id: item.id, requested: quantity, available });
+    }
+    let removable: i32 = held.iter().filter(|h| !h.stuck()).map(|h| h.row.quantity).sum();
+    if removable < quantity {
+        return Err(InventoryError::CursedItemEquipped(item.id));
     }
 
-    // Update inventory in DB
-    update_inventory_in_db(pool, inventory).await;
+    let mut remaining = quantity;
+    let mut rows = Vec::new();
+    for mut held in held {
+        if remaining > 0 && !held.stuck() {
+            let taken = remaining.min(held.row.quantity);
+            held.row.remove_item(taken);
+            remaining -= taken;
+            save_inventory_row(&mut tx, &held.row).await?;
+        }
+        if held.row.quantity > 0 {
+            rows.push(held.row);
+        }
+    }
+    tx.commit().await?;
+    Ok(rows)
 }
 
-pub async fn remove_item_from_inventory(pool: &PgPool, player_id: i32, item: Item, quantity: i32) {
-    let mut inventory = get_inventory_for_player(pool, player_id).await;
-    if let Some(existing_item) = inventory.iter_mut().find(|i| i.item_id == item.id) {
-        existing_item.remove_item(quantity);
+/// Use one of the player's items. The player's own copy is used, an unbroken one if there is
+/// one and the equipped one among those, so durability wears down on that copy, and
+/// consumables are removed once used.
+pub async fn use_item_from_inventory(
+    pool: &PgPool,
+    player_id: i32,
+    item: &Item,
+) -> Result<ItemUse, InventoryError> {
+    let mut tx = lock_player_inventory(pool, player_id).await?;
+    let held = held_rows(&mut *tx, player_id, item.id).await?;
+    let mut stack = copy_to_use(&held)
+        .map(|h| h.row.clone())
+        .ok_or(InventoryError::NotEnoughQuantity { item_id: item.id, requested: 1, available: 0 })?;
+    let mut player = get_player(&mut *tx, player_id)
+        .await?
+        .ok_or(InventoryError::PlayerNotFound(player_id))?;
+    player.status_effects = get_status_effects(&mut *tx, player_id).await?;
+
+    let mut copy = Item { durability: stack.durability, is_cursed: stack.is_cursed, ..item.clone() };
+    let outcome = copy.use_item(&mut player).map_err(InventoryError::Unusable)?;
+    for effect in &outcome.pending {
+        apply_item_effect(&mut tx, player_id, &item.name, effect).await?;
     }
+    update_player_stats(&mut *tx, &player).await?;
+    save_status_effects(&mut tx, player_id, &player.status_effects).await?;
 
-    // Update inventory in DB
-    update_inventory_in_db(pool, inventory).await;
+    stack.durability = copy.durability;
+    if outcome.consumed {
+        stack.remove_item(1);
+    }
+    save_inventory_row(&mut tx, &stack).await?;
+    tx.commit().await?;
+    Ok(outcome)
+}
+
+/// The copy `use` picks: anything that isn't broken before broken copies, and equipped copies
+/// before the rest
+fn copy_to_use(held: &[HeldRow]) -> Option<&HeldRow> {
+    held.iter()
+        .filter(|h| h.row.quantity > 0)
+        .min_by_key(|h| (h.row.durability.is_some_and(|d| d <= 0), !h.equipped))
+}
+
+/// Persist an item effect that reaches beyond the player's own stats
+async fn apply_item_effect(
+    tx: &mut Transaction<'static, Postgres>,
+    player_id: i32,
+    item_name: &str,
+    effect: &ItemEffect,
+) -> Result<(), InventoryError> {
+    match effect {
+        ItemEffect::Heal { .. } | ItemEffect::ApplyEffect { .. } => {}
+        ItemEffect::GrantSkill { skill } => {
+            let skill_id: i32 = sqlx::query_scalar("SELECT id FROM skills WHERE name = $1")
+                .bind(skill)
+                .fetch_optional(&mut **tx)
+                .await?
+                .ok_or_else(|| InventoryError::Unusable(ItemUseError::UnknownSkill(skill.clone())))?;
+            learn_skill(&mut **tx, player_id, skill_id).await?;
+        }
+        ItemEffect::UnlockPortal { portal_id } => {
+            sqlx::query(
+                r#"
+                INSERT INTO player_unlocked_portals (player_id, portal_id)
+                VALUES ($1, $2)
+                ON CONFLICT (player_id, portal_id) DO NOTHING
+                "#,
+            )
+            .bind(player_id)
+            .bind(portal_id)
+            .execute(&mut **tx)
+            .await?;
+        }
+        ItemEffect::SummonMinion { minion_type, health, power } => {
+            sqlx::query(
+                r#"
+                INSERT INTO minions (name, owner_id, minion_type, health, power, abilities)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+            )
+            .bind(minion_type)
+            .bind(player_id)
+            .bind(minion_type)
+            .bind(health)
+            .bind(power)
+            .bind(format!("Summoned with {}", item_name))
+            .execute(&mut **tx)
+            .await?;
+        }
+    }
+    Ok(())
 }
 
 // Helper functions to interact with DB
-async fn get_inventory_for_player(pool: &PgPool, player_id: i32) -> Vec<Inventory> {
-    sqlx::query_as::<_, Inventory>("SELECT * FROM inventory WHERE player_id = $1")
+
+/// Open a transaction holding a row lock on the player, so concurrent inventory changes for
+/// the same player run one after another instead of overwriting each other
+pub async fn lock_player_inventory(
+    pool: &PgPool,
+    player_id: i32,
+) -> Result<Transaction<'static, Postgres>, InventoryError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT id FROM players WHERE id = $1 FOR UPDATE")
         .bind(player_id)
-        .fetch_all(pool)
-        .await
-        .unwrap_or_default()
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(InventoryError::PlayerNotFound(player_id))?;
+    Ok(tx)
+}
+
+pub async fn get_inventory_for_player<'e, E: PgExecutor<'e>>(
+    executor: E,
+    player_id: i32,
+) -> Result<Vec<Inventory>, sqlx::Error> {
+    sqlx::query_as::<_, Inventory>(
+        r#"
+        SELECT id, player_id, item_id, quantity, durability, is_cursed, stacked
+        FROM inventory
+        WHERE player_id = $1
+        ORDER BY item_id, id
+        "#,
+    )
+    .bind(player_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// An inventory row, and whether it is worn in an equipment slot
+#[derive(FromRow)]
+struct HeldRow {
+    #[sqlx(flatten)]
+    row: Inventory,
+    equipped: bool,
+}
+
+impl HeldRow {
+    /// Cursed copies can't leave the inventory while they are worn
+    fn stuck(&self) -> bool {
+        self.equipped && self.row.is_cursed
+    }
 }
 
-async fn update_inventory_in_db(pool: &PgPool, inventory: Vec<Inventory>) {
-    // Update DB logic here (upsert or delete if item quantity is zero)
+/// The player's rows of one item, unequipped rows first
+async fn held_rows<'e, E: PgExecutor<'e>>(executor: E, player_id: i32, item_id: i32) -> Result<Vec<HeldRow>, sqlx::Error> {
+    sqlx::query_as::<_, HeldRow>(
+        r#"
+        SELECT inv.id, inv.player_id, inv.item_id, inv.quantity, inv.durability, inv.is_cursed, inv.stacked,
+               pe.inventory_id IS NOT NULL AS equipped
+        FROM inventory inv
+        LEFT JOIN player_equipment pe ON pe.inventory_id = inv.id
+        WHERE inv.player_id = $1 AND inv.item_id = $2
+        ORDER BY equipped, inv.id
+        "#,
+    )
+    .bind(player_id)
+    .bind(item_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// Write a row back by ID, deleting it once it has run out
+async fn save_inventory_row(tx: &mut Transaction<'static, Postgres>, row: &Inventory) -> Result<(), sqlx::Error> {
+    if row.quantity <= 0 {
+        sqlx::query("DELETE FROM inventory WHERE id = $1")
+            .bind(row.id)
+            .execute(&mut **tx)
+            .await?;
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE inventory SET quantity = $2, durability = $3, is_cursed = $4 WHERE id = $1")
+        .bind(row.id)
+        .bind(row.quantity)
+        .bind(row.durability)
+        .bind(row.is_cursed)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::items::get_item;
+    use crate::db::players::create_player;
+
+    fn held(id: i32, durability: Option<i32>, equipped: bool) -> HeldRow {
+        let row = Inventory { id, player_id: 1, item_id: 1, quantity: 1, durability, is_cursed: false, stacked: false };
+        HeldRow { row, equipped }
+    }
+
+    #[test]
+    fn use_picks_an_unbroken_copy_and_the_equipped_one_first() {
+        let rows = [held(1, Some(0), true), held(2, Some(5), false), held(3, Some(5), true)];
+        assert_eq!(copy_to_use(&rows).map(|h| h.row.id), Some(3));
+
+        let rows = [held(1, Some(0), true), held(2, Some(5), false)];
+        assert_eq!(copy_to_use(&rows).map(|h| h.row.id), Some(2));
+
+        let rows = [held(1, Some(0), false), held(2, Some(0), true)];
+        assert_eq!(copy_to_use(&rows).map(|h| h.row.id), Some(2));
+
+        let mut empty = held(1, None, true);
+        empty.row.quantity = 0;
+        assert!(copy_to_use(&[empty]).is_none());
+    }
+
+    #[test]
+    fn only_equipped_cursed_copies_are_stuck() {
+        let mut cursed = held(1, Some(5), false);
+        cursed.row.is_cursed = true;
+        assert!(!cursed.stuck());
+        cursed.equipped = true;
+        assert!(cursed.stuck());
+        assert!(!held(2, Some(5), true).stuck());
+    }
+
+    // The rest need a database: `DATABASE_URL=... cargo test -- --ignored`
+
+    async fn setup(pool: &PgPool, durability: Option<i32>) -> (i32, Item) {
+        let player_id = create_player(pool, "hoarder", None, "not a hash").await.unwrap();
+        let item_id: i32 = sqlx::query_scalar(
+            "INSERT INTO items (name, item_type, durability, effects) VALUES ('Pebble', 'Tool', $1, '[]') RETURNING id",
+        )
+        .bind(durability)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (player_id, get_item(pool, item_id).await.unwrap().unwrap())
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a database"]
+    async fn stacks_stop_at_the_cap(pool: PgPool) {
+        let (player_id, item) = setup(&pool, None).await;
+        for quantity in [0, MAX_QUANTITY + 1] {
+            let result = add_item_to_inventory(&pool, player_id, player_id, &item, quantity).await;
+            assert!(matches!(result, Err(InventoryError::InvalidQuantity(q)) if q == quantity));
+        }
+
+        for _ in 0..MAX_STACK / MAX_QUANTITY {
+            add_item_to_inventory(&pool, player_id, player_id, &item, MAX_QUANTITY).await.unwrap();
+        }
+        let rest = MAX_STACK % MAX_QUANTITY;
+        let overflow = add_item_to_inventory(&pool, player_id, player_id, &item, rest + 1).await;
+        assert!(matches!(overflow, Err(InventoryError::InvalidQuantity(_))));
+        let rows = add_item_to_inventory(&pool, player_id, player_id, &item, rest).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].quantity, MAX_STACK);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a database"]
+    async fn copies_with_durability_get_a_row_each(pool: PgPool) {
+        let (player_id, item) = setup(&pool, Some(10)).await;
+        let rows = add_item_to_inventory(&pool, player_id, player_id, &item, 3).await.unwrap();
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|row| row.quantity == 1 && row.durability == Some(10) && !row.stacked));
+
+        let rows = remove_item_from_inventory(&pool, player_id, &item, 2).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        let short = remove_item_from_inventory(&pool, player_id, &item, 2).await;
+        assert!(matches!(short, Err(InventoryError::NotEnoughQuantity { requested: 2, available: 1, .. })));
+    }
 }
@@ -1,14 +1,62 @@
-use std::collections::HashMap;
-use crate::models::{DungeonRegion, Portal};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use crate::models::{EnvironmentType, Region, Portal};
+
+/// Every player starts out in the nexus
+pub const NEXUS_ID: &str = "nexus";
+
+/// One portal hop along a route
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteStep {
+    pub portal_id: String,
+    pub portal_name: String,
+    pub from: String,
+    pub to: String,
+    pub required_level: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Route {
+    pub from: String,
+    pub to: String,
+    pub steps: Vec<RouteStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    UnknownRegion(String),
+    /// No chain of portals connects the regions at any level
+    Unreachable { from: String, to: String },
+    /// A route exists, but only from `required_level` on
+    LevelTooLow { required_level: i32 },
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::UnknownRegion(id) => write!(f, "There is no region '{}'", id),
+            RouteError::Unreachable { from, to } => write!(f, "No portals lead from '{}' to '{}'", from, to),
+            RouteError::LevelTooLow { required_level } => {
+                write!(f, "The way there opens up at level {}", required_level)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
 
 #[derive(Debug)]
 pub struct MapGraph {
-    pub regions: HashMap<String, DungeonRegion>,
+    pub regions: HashMap<String, Region>,
     pub connections: HashMap<String, Vec<Portal>>, // region_id -> portals
+    /// Content ID of the item travellers need to get around each kind of world, from
+    /// `content/environments.toml`
+    pub environment_items: HashMap<EnvironmentType, String>,
 }
 
 impl MapGraph {
-    pub fn new(regions: Vec<DungeonRegion>) -> Self {
+    pub fn new(regions: Vec<Region>) -> Self {
         let mut region_map = HashMap::new();
         let mut conn_map = HashMap::new();
 
@@ -20,16 +68,27 @@ impl MapGraph {
         MapGraph {
             regions: region_map,
             connections: conn_map,
+            environment_items: HashMap::new(),
         }
     }
 
+    pub fn with_environment_items(mut self, environment_items: HashMap<EnvironmentType, String>) -> Self {
+        self.environment_items = environment_items;
+        self
+    }
+
+    /// The item travellers need to find their way around regions of `environment`, if any
+    pub fn environment_item(&self, environment: EnvironmentType) -> Option<&str> {
+        self.environment_items.get(&environment).map(String::as_str)
+    }
+
     /// Returns the portals (exits) from a given region
     pub fn get_portals(&self, from_region: &str) -> Option<&Vec<Portal>> {
         self.connections.get(from_region)
     }
 
-    /// Returns a reference to a DungeonRegion by ID
-    pub fn get_region(&self, id: &str) -> Option<&DungeonRegion> {
+    /// Returns a reference to a Region by ID
+    pub fn get_region(&self, id: &str) -> Option<&Region> {
         self.regions.get(id)
     }
 
@@ -50,4 +109,121 @@ impl MapGraph {
 
         broken_links
     }
+
+    /// Portals out of a region that a player of the given level may use and that lead somewhere
+    fn open_portals<'a>(&'a self, from_region: &str, player_level: i32) -> impl Iterator<Item = &'a Portal> {
+        self.connections
+            .get(from_region)
+            .into_iter()
+            .flatten()
+            .filter(move |p| p.required_level <= player_level && self.regions.contains_key(&p.leads_to))
+    }
+
+    /// The route with the fewest portal hops that a player of the given level can take
+    pub fn find_path(&self, from: &str, to: &str, player_level: i32) -> Result<Route, RouteError> {
+        for id in [from, to] {
+            if !self.regions.contains_key(id) {
+                return Err(RouteError::UnknownRegion(id.to_string()));
+            }
+        }
+
+        match self.bfs_path(from, to, player_level) {
+            Some(steps) => Ok(Route { from: from.to_string(), to: to.to_string(), steps }),
+            None => {
+                // Find the lowest level any route opens up at, to tell the player what to aim for
+                let mut levels: Vec<i32> = self
+                    .connections
+                    .values()
+                    .flatten()
+                    .map(|p| p.required_level)
+                    .filter(|&level| level > player_level)
+                    .collect();
+                levels.sort_unstable();
+                levels.dedup();
+                match levels.into_iter().find(|&level| self.bfs_path(from, to, level).is_some()) {
+                    Some(required_level) => Err(RouteError::LevelTooLow { required_level }),
+                    None => Err(RouteError::Unreachable { from: from.to_string(), to: to.to_string() }),
+                }
+            }
+        }
+    }
+
+    fn bfs_path(&self, from: &str, to: &str, player_level: i32) -> Option<Vec<RouteStep>> {
+        // Remember the portal each region was first reached through, then walk back from `to`
+        let mut came_from: HashMap<&str, (&str, &Portal)> = HashMap::new();
+        let mut visited: HashSet<&str> = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut steps = Vec::new();
+                let mut region = to;
+                while let Some(&(prev, portal)) = came_from.get(region) {
+                    steps.push(RouteStep {
+                        portal_id: portal.id.clone(),
+                        portal_name: portal.name.clone(),
+                        from: prev.to_string(),
+                        to: region.to_string(),
+                        required_level: portal.required_level,
+                    });
+                    region = prev;
+                }
+                steps.reverse();
+                return Some(steps);
+            }
+
+            for portal in self.open_portals(current, player_level) {
+                if visited.insert(portal.leads_to.as_str()) {
+                    came_from.insert(portal.leads_to.as_str(), (current, portal));
+                    queue.push_back(portal.leads_to.as_str());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Every region a player of the given level can get to from `start`, including `start`
+    pub fn reachable_from(&self, start: &str, player_level: i32) -> Vec<String> {
+        if !self.regions.contains_key(start) {
+            return Vec::new();
+        }
+
+        let mut visited: HashSet<&str> = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(current) = queue.pop_front() {
+            for portal in self.open_portals(current, player_level) {
+                if visited.insert(portal.leads_to.as_str()) {
+                    queue.push_back(portal.leads_to.as_str());
+                }
+            }
+        }
+
+        let mut reachable: Vec<String> = visited.into_iter().map(str::to_string).collect();
+        reachable.sort();
+        reachable
+    }
+
+    pub fn reachable_from_nexus(&self, player_level: i32) -> Vec<String> {
+        self.reachable_from(NEXUS_ID, player_level)
+    }
+
+    /// Regions other than the nexus that no portal leads into
+    pub fn orphan_regions(&self) -> Vec<String> {
+        let targets: HashSet<&str> = self
+            .connections
+            .iter()
+            .flat_map(|(from, portals)| portals.iter().filter(move |p| p.leads_to != *from))
+            .map(|p| p.leads_to.as_str())
+            .collect();
+
+        let mut orphans: Vec<String> = self
+            .regions
+            .keys()
+            .filter(|id| id.as_str() != NEXUS_ID && !targets.contains(id.as_str()))
+            .cloned()
+            .collect();
+        orphans.sort();
+        orphans
+    }
 }
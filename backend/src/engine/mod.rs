@@ -1,2 +1,18 @@
+pub mod admin;
+pub mod auth;
+pub mod combat;
+pub mod commands;
+pub mod content;
+pub mod encounters;
+pub mod equipment;
+pub mod game_sessions;
+pub mod game_logic;
+pub mod interpreter;
+pub mod inventory_logic;
 pub mod map_graph;
+pub mod oidc;
+pub mod presence;
+pub mod rooms;
+pub mod skills;
+pub mod travel;
 pub mod worldgen;
@@ -0,0 +1,371 @@
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::{FromRow, PgPool};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use uuid::Uuid;
+
+/// Postgres channel the `postgres` backend sends region messages on
+pub const NOTIFY_CHANNEL: &str = "region_presence";
+/// Messages a subscriber can fall behind by before it starts missing some
+const BACKLOG: usize = 1024;
+/// How often a replica stores who it has connected and catches up on the other replicas
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// Snapshots older than this belong to a replica that stopped, and its players are gone
+const HEARTBEAT_TIMEOUT_SECS: i32 = 30;
+
+/// Something that happened in a region, for everyone there to see
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RegionEvent {
+    /// A player came in from the region `from`, or connected here if there is none
+    Arrived { player: String, from: Option<String> },
+    /// A player left for the region `to`, or disconnected if there is none
+    Left { player: String, to: Option<String> },
+    Said { speaker: String, message: String },
+    Emoted { player: String, action: String },
+}
+
+/// A region event on its way to every replica of the backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionMessage {
+    /// The replica that sent the message
+    pub replica: Uuid,
+    pub region_id: String,
+    /// The player the event is about. Everyone else in the region gets it.
+    pub player_id: i32,
+    pub event: RegionEvent,
+}
+
+/// A player connected on one replica, and the region they are in
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct Placement {
+    pub replica_id: Uuid,
+    pub player_id: i32,
+    pub username: String,
+    pub region_id: String,
+}
+
+/// Carries region messages to every replica of the backend, the one sending them included
+#[async_trait]
+pub trait PresenceBackend: Send + Sync {
+    /// Send a message to every subscriber on every replica, without waiting for it to arrive
+    fn publish(&self, message: RegionMessage);
+    /// The messages of every replica, each replica's in the order it sent them
+    fn subscribe(&self) -> broadcast::Receiver<RegionMessage>;
+    /// Record everyone connected on `replica`, replacing what it stored last time
+    async fn store_snapshot(&self, replica: Uuid, placements: &[Placement]) -> Result<(), sqlx::Error>;
+    /// Everyone connected on the other replicas that are still storing snapshots
+    async fn load_snapshot(&self, replica: Uuid) -> Result<Vec<Placement>, sqlx::Error>;
+}
+
+/// Delivers messages within this process, which is all a single replica needs
+pub struct LocalBackend {
+    sender: broadcast::Sender<RegionMessage>,
+}
+
+impl Default for LocalBackend {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(BACKLOG);
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl PresenceBackend for LocalBackend {
+    fn publish(&self, message: RegionMessage) {
+        // Nobody subscribed means nobody to tell
+        let _ = self.sender.send(message);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<RegionMessage> {
+        self.sender.subscribe()
+    }
+
+    // There are no other replicas to tell or hear from
+    async fn store_snapshot(&self, _replica: Uuid, _placements: &[Placement]) -> Result<(), sqlx::Error> {
+        Ok(())
+    }
+
+    async fn load_snapshot(&self, _replica: Uuid) -> Result<Vec<Placement>, sqlx::Error> {
+        Ok(Vec::new())
+    }
+}
+
+/// Sends messages with `NOTIFY` and `LISTEN`s for them, so every replica using the same
+/// database hears about every region. Messages go out one at a time in the order they were
+/// published; Postgres turns down payloads over 8000 bytes. Snapshots go in the
+/// `region_presence` table.
+pub struct PostgresBackend {
+    pool: PgPool,
+    outgoing: mpsc::UnboundedSender<RegionMessage>,
+    incoming: broadcast::Sender<RegionMessage>,
+}
+
+impl PostgresBackend {
+    /// Start listening on `NOTIFY_CHANNEL`. Messages sent while the listener is reconnecting
+    /// are lost.
+    pub async fn start(pool: PgPool) -> Result<Self, sqlx::Error> {
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen(NOTIFY_CHANNEL).await?;
+
+        let (incoming, _) = broadcast::channel(BACKLOG);
+        let received = incoming.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    Ok(notification) => match serde_json::from_str(notification.payload()) {
+                        Ok(message) => {
+                            let _ = received.send(message);
+                        }
+                        Err(e) => eprintln!("⚠️ Ignoring a malformed region message: {}", e),
+                    },
+                    // The listener reconnects on the next `recv`
+                    Err(e) => {
+                        eprintln!("⚠️ Lost the connection listening for region messages: {}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+
+        let (outgoing, mut queue) = mpsc::unbounded_channel::<RegionMessage>();
+        let sending = pool.clone();
+        tokio::spawn(async move {
+            while let Some(message) = queue.recv().await {
+                let Ok(payload) = serde_json::to_string(&message) else {
+                    continue;
+                };
+                let sent = sqlx::query("SELECT pg_notify($1, $2)")
+                    .bind(NOTIFY_CHANNEL)
+                    .bind(payload)
+                    .execute(&sending)
+                    .await;
+                if let Err(e) = sent {
+                    eprintln!("⚠️ Failed to send a region message: {}", e);
+                }
+            }
+        });
+
+        Ok(Self { pool, outgoing, incoming })
+    }
+}
+
+#[async_trait]
+impl PresenceBackend for PostgresBackend {
+    fn publish(&self, message: RegionMessage) {
+        let _ = self.outgoing.send(message);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<RegionMessage> {
+        self.incoming.subscribe()
+    }
+
+    async fn store_snapshot(&self, replica: Uuid, placements: &[Placement]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM region_presence WHERE replica_id = $1 OR seen_at < NOW() - make_interval(secs => $2)")
+            .bind(replica)
+            .bind(HEARTBEAT_TIMEOUT_SECS)
+            .execute(&mut *tx)
+            .await?;
+        for placement in placements {
+            sqlx::query(
+                r#"
+                INSERT INTO region_presence (replica_id, player_id, username, region_id)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(replica)
+            .bind(placement.player_id)
+            .bind(&placement.username)
+            .bind(&placement.region_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    async fn load_snapshot(&self, replica: Uuid) -> Result<Vec<Placement>, sqlx::Error> {
+        sqlx::query_as::<_, Placement>(
+            r#"
+            SELECT replica_id, player_id, username, region_id
+            FROM region_presence
+            WHERE replica_id <> $1 AND seen_at >= NOW() - make_interval(secs => $2)
+            "#,
+        )
+        .bind(replica)
+        .bind(HEARTBEAT_TIMEOUT_SECS)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+/// A connected player, as `who` lists them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Present {
+    pub player_id: i32,
+    pub username: String,
+}
+
+/// Who is connected in which region, and the way events in a region get out. The registry
+/// follows the `Arrived` and `Left` messages of every replica, so it only changes once
+/// they come back from the backend and are applied. Each replica's players are kept apart, so
+/// a player connected on two replicas stays until both have seen them leave, and the
+/// heartbeat replaces the other replicas' players with their latest snapshot.
+pub struct Presence {
+    backend: Arc<dyn PresenceBackend>,
+    /// This process among the backend's replicas
+    replica: Uuid,
+    /// (replica ID, player ID) → where the player is connected on that replica
+    placements: Mutex<HashMap<(Uuid, i32), Placement>>,
+}
+
+pub type SharedPresence = Arc<Presence>;
+
+impl Presence {
+    pub fn new(backend: Arc<dyn PresenceBackend>) -> Self {
+        Self { backend, replica: Uuid::new_v4(), placements: Mutex::new(HashMap::new()) }
+    }
+
+    /// Store this replica's players and catch up on the others' every `HEARTBEAT_INTERVAL`,
+    /// starting straight away so a new replica knows who is already connected
+    pub fn start_heartbeat(self: &Arc<Self>) {
+        let presence = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = presence.resync().await {
+                    eprintln!("⚠️ Failed to sync presence with the other replicas: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Store this replica's players, and take the other replicas' from their latest snapshots.
+    /// Players of replicas that stopped sending them drop out.
+    pub async fn resync(&self) -> Result<(), sqlx::Error> {
+        let own: Vec<Placement> = {
+            let placements = self.placements.lock().unwrap();
+            placements.values().filter(|p| p.replica_id == self.replica).cloned().collect()
+        };
+        self.backend.store_snapshot(self.replica, &own).await?;
+        let others = self.backend.load_snapshot(self.replica).await?;
+
+        let mut placements = self.placements.lock().unwrap();
+        placements.retain(|(replica_id, _), _| *replica_id == self.replica);
+        for placement in others {
+            placements.insert((placement.replica_id, placement.player_id), placement);
+        }
+        Ok(())
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RegionMessage> {
+        self.backend.subscribe()
+    }
+
+    /// Tell everyone else in `region_id` what the player did
+    pub fn publish(&self, region_id: &str, player_id: i32, event: RegionEvent) {
+        let message = RegionMessage { replica: self.replica, region_id: region_id.to_string(), player_id, event };
+        self.backend.publish(message);
+    }
+
+    /// The player connected to this replica in `region_id`
+    pub fn enter(&self, player_id: i32, username: &str, region_id: &str) {
+        let event = RegionEvent::Arrived { player: username.to_string(), from: None };
+        self.publish(region_id, player_id, event);
+    }
+
+    /// The player went from one region to another
+    pub fn travel(&self, player_id: i32, username: &str, from: &str, to: &str) {
+        let left = RegionEvent::Left { player: username.to_string(), to: Some(to.to_string()) };
+        self.publish(from, player_id, left);
+        let arrived = RegionEvent::Arrived { player: username.to_string(), from: Some(from.to_string()) };
+        self.publish(to, player_id, arrived);
+    }
+
+    /// The player's last connection to this replica is gone
+    pub fn leave(&self, player_id: i32, username: &str) {
+        let region_id = self.placements.lock().unwrap().get(&(self.replica, player_id)).map(|p| p.region_id.clone());
+        if let Some(region_id) = region_id {
+            self.publish(&region_id, player_id, RegionEvent::Left { player: username.to_string(), to: None });
+        }
+    }
+
+    /// Update the registry with a message from the backend. Whoever delivers the messages
+    /// applies each one once. False when the message changes nothing anyone can see: a player
+    /// connecting where they are already connected through another replica, or leaving one
+    /// replica while still connected on another.
+    pub fn apply(&self, message: &RegionMessage) -> bool {
+        let mut placements = self.placements.lock().unwrap();
+        let connected = |placements: &HashMap<(Uuid, i32), Placement>| {
+            placements.values().any(|p| p.player_id == message.player_id && p.region_id == message.region_id)
+        };
+        let was_connected = connected(&placements);
+        match &message.event {
+            // Connecting places the player on the replica they connected to
+            RegionEvent::Arrived { player, from: None } => {
+                let placement = Placement {
+                    replica_id: message.replica,
+                    player_id: message.player_id,
+                    username: player.clone(),
+                    region_id: message.region_id.clone(),
+                };
+                placements.insert((message.replica, message.player_id), placement);
+            }
+            // Travel, sent by whichever replica handled it, moves every connection of the
+            // player. Players who aren't connected anywhere can still travel, but stay absent.
+            RegionEvent::Arrived { from: Some(_), .. } => {
+                for placement in placements.values_mut().filter(|p| p.player_id == message.player_id) {
+                    placement.region_id = message.region_id.clone();
+                }
+            }
+            RegionEvent::Left { to: None, .. } => {
+                placements.remove(&(message.replica, message.player_id));
+            }
+            _ => {}
+        }
+        match &message.event {
+            RegionEvent::Arrived { from: None, .. } => !was_connected,
+            RegionEvent::Left { to: None, .. } => !connected(&placements),
+            _ => true,
+        }
+    }
+
+    /// Everyone connected in `region_id` on any replica, by name
+    pub fn who(&self, region_id: &str) -> Vec<Present> {
+        let placements = self.placements.lock().unwrap();
+        let present: BTreeMap<i32, &str> = placements
+            .values()
+            .filter(|p| p.region_id == region_id)
+            .map(|p| (p.player_id, p.username.as_str()))
+            .collect();
+        let mut present: Vec<Present> = present
+            .into_iter()
+            .map(|(player_id, username)| Present { player_id, username: username.to_string() })
+            .collect();
+        present.sort_by(|a, b| a.username.cmp(&b.username));
+        present
+    }
+
+    /// The region the player is connected in, on this replica if they are connected here
+    pub fn region_of(&self, player_id: i32) -> Option<String> {
+        let placements = self.placements.lock().unwrap();
+        placements
+            .get(&(self.replica, player_id))
+            .or_else(|| placements.values().find(|p| p.player_id == player_id))
+            .map(|p| p.region_id.clone())
+    }
+
+    /// The players a message is for: everyone in its region but the one it is about
+    pub fn audience(&self, message: &RegionMessage) -> Vec<i32> {
+        self.who(&message.region_id)
+            .into_iter()
+            .map(|present| present.player_id)
+            .filter(|player_id| *player_id != message.player_id)
+            .collect()
+    }
+}
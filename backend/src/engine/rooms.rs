@@ -0,0 +1,108 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use std::fmt;
+use crate::db::players::{get_player_room, set_player_room};
+use crate::engine::map_graph::MapGraph;
+use crate::models::{Direction, MoveError, Portal, Position, Region, RoomLayout};
+
+/// What a player standing in a room can see and do
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomView {
+    pub region_id: String,
+    pub position: Position,
+    pub exits: Vec<Direction>,
+    /// Portals that can be taken from here
+    pub portals: Vec<Portal>,
+    /// The layout with the player marked `@`
+    pub map: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum RoomError {
+    PlayerNotFound(i32),
+    UnknownRegion(String),
+    NoLayout(String),
+    Move(MoveError),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for RoomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoomError::PlayerNotFound(player_id) => write!(f, "Player {} not found", player_id),
+            RoomError::UnknownRegion(id) => write!(f, "There is no region '{}'", id),
+            RoomError::NoLayout(id) => write!(f, "Region '{}' has no rooms to explore", id),
+            RoomError::Move(e) => e.fmt(f),
+            RoomError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RoomError {}
+
+impl From<MoveError> for RoomError {
+    fn from(e: MoveError) -> Self {
+        RoomError::Move(e)
+    }
+}
+
+impl From<sqlx::Error> for RoomError {
+    fn from(e: sqlx::Error) -> Self {
+        RoomError::Database(e)
+    }
+}
+
+/// A region and its layout, or why it has none
+pub fn region_layout<'a>(map: &'a MapGraph, region_id: &str) -> Result<(&'a Region, &'a RoomLayout), RoomError> {
+    let region = map.get_region(region_id).ok_or_else(|| RoomError::UnknownRegion(region_id.to_string()))?;
+    let layout = region.layout.as_ref().ok_or_else(|| RoomError::NoLayout(region_id.to_string()))?;
+    Ok((region, layout))
+}
+
+pub fn view_room(map: &MapGraph, region_id: &str, position: Position) -> Result<RoomView, RoomError> {
+    let (region, layout) = region_layout(map, region_id)?;
+    if !layout.is_room(position) {
+        return Err(MoveError::NotARoom(position).into());
+    }
+    Ok(RoomView {
+        region_id: region.id.clone(),
+        position,
+        exits: layout.exits(position),
+        portals: layout.portals_at(&region.portals, position).into_iter().cloned().collect(),
+        map: layout.render(Some(position)),
+    })
+}
+
+/// The room a player is in: the one stored for them while it is still a room of the layout,
+/// otherwise the start
+pub fn current_room(layout: &RoomLayout, stored: Option<Position>) -> Position {
+    stored.filter(|&room| layout.is_room(room)).unwrap_or(layout.start)
+}
+
+/// The room the player stands in
+pub async fn player_room(pool: &PgPool, map: &MapGraph, player_id: i32) -> Result<RoomView, RoomError> {
+    let (region_id, stored) = get_player_room(pool, player_id)
+        .await?
+        .ok_or(RoomError::PlayerNotFound(player_id))?;
+    let (_, layout) = region_layout(map, &region_id)?;
+    view_room(map, &region_id, current_room(layout, stored))
+}
+
+/// Walk the player one room over in their region
+pub async fn walk(pool: &PgPool, map: &MapGraph, player_id: i32, direction: Direction) -> Result<RoomView, RoomError> {
+    let mut tx = pool.begin().await?;
+    // Locked, so two steps at once both count
+    sqlx::query("SELECT id FROM players WHERE id = $1 FOR UPDATE")
+        .bind(player_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RoomError::PlayerNotFound(player_id))?;
+    let (region_id, stored) = get_player_room(&mut *tx, player_id)
+        .await?
+        .ok_or(RoomError::PlayerNotFound(player_id))?;
+    let (_, layout) = region_layout(map, &region_id)?;
+    let next = layout.step(current_room(layout, stored), direction)?;
+    set_player_room(&mut *tx, player_id, next).await?;
+    tx.commit().await?;
+    view_room(map, &region_id, next)
+}
@@ -0,0 +1,309 @@
+use rand::Rng;
+use std::fmt;
+
+use crate::engine::combat::{CombatEncounter, CombatEvent};
+use crate::models::{EffectDuration, EffectKind, PlayerSkill, SkillType, StatusEffect, TargetType};
+
+/// Skills seeded without a power value (buffs, debuffs, regeneration) scale from this base instead
+const BASE_EFFECT_MAGNITUDE: i32 = 5;
+/// How long a skill's status effect lasts when the skill doesn't say
+const DEFAULT_EFFECT_TURNS: i32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkillError {
+    UnknownSkill,
+    NotEnoughMana { needed: i32, available: i32 },
+    OnCooldown { rounds_remaining: u32 },
+    InvalidTarget,
+    CasterDefeated,
+    EncounterFinished,
+}
+
+impl fmt::Display for SkillError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkillError::UnknownSkill => write!(f, "You don't know that skill"),
+            SkillError::NotEnoughMana { needed, available } => {
+                write!(f, "Not enough mana ({} needed, {} available)", needed, available)
+            }
+            SkillError::OnCooldown { rounds_remaining } => {
+                write!(f, "That skill is on cooldown for {} more round(s)", rounds_remaining)
+            }
+            SkillError::InvalidTarget => write!(f, "That is not a valid target for this skill"),
+            SkillError::CasterDefeated => write!(f, "You can't act while defeated"),
+            SkillError::EncounterFinished => write!(f, "The encounter is already over"),
+        }
+    }
+}
+
+impl std::error::Error for SkillError {}
+
+/// Check mana, cooldown and target before a skill is queued
+pub fn validate_skill(
+    encounter: &CombatEncounter,
+    caster: usize,
+    skill: &PlayerSkill,
+    target: Option<&str>,
+) -> Result<Vec<usize>, SkillError> {
+    let combatant = &encounter.participants[caster];
+    if !combatant.is_alive() {
+        return Err(SkillError::CasterDefeated);
+    }
+    if combatant.mana < skill.skill.mana_cost {
+        return Err(SkillError::NotEnoughMana {
+            needed: skill.skill.mana_cost,
+            available: combatant.mana,
+        });
+    }
+    if let Some(&rounds_remaining) = combatant.cooldowns.get(&skill.skill.id) {
+        if rounds_remaining > 0 {
+            return Err(SkillError::OnCooldown { rounds_remaining });
+        }
+    }
+
+    resolve_targets(encounter, caster, skill.skill.target_type, target)
+}
+
+/// Resolve who a skill lands on. Without an explicit target, enemy skills pick the weakest
+/// opponent and ally skills fall back to the caster.
+pub fn resolve_targets(
+    encounter: &CombatEncounter,
+    caster: usize,
+    target_type: TargetType,
+    requested: Option<&str>,
+) -> Result<Vec<usize>, SkillError> {
+    let team = encounter.participants[caster].team;
+    let requested_idx = match requested {
+        Some(id) => Some(
+            encounter
+                .participants
+                .iter()
+                .position(|c| c.id == id && c.is_alive())
+                .ok_or(SkillError::InvalidTarget)?,
+        ),
+        None => None,
+    };
+
+    let target = match (target_type, requested_idx) {
+        (TargetType::Enemy, Some(idx)) if encounter.participants[idx].team != team => idx,
+        (TargetType::Enemy, None) => encounter.pick_target(caster).ok_or(SkillError::InvalidTarget)?,
+        (TargetType::Ally, Some(idx)) if encounter.participants[idx].team == team => idx,
+        (TargetType::Ally, None) => caster,
+        (TargetType::Caster, Some(idx)) if idx == caster => caster,
+        (TargetType::Caster, None) => caster,
+        _ => return Err(SkillError::InvalidTarget),
+    };
+
+    Ok(vec![target])
+}
+
+/// Spend the skill's cost and apply its effect. Called on the caster's turn, so the target is
+/// re-resolved in case the original one fell earlier in the round.
+pub fn use_skill<R: Rng>(
+    encounter: &mut CombatEncounter,
+    caster: usize,
+    skill: &PlayerSkill,
+    target: Option<&str>,
+    rng: &mut R,
+) {
+    let targets = match validate_skill(encounter, caster, skill, target) {
+        Ok(targets) => targets,
+        Err(SkillError::InvalidTarget) if target.is_some() => {
+            match validate_skill(encounter, caster, skill, None) {
+                Ok(targets) => targets,
+                Err(_) => return,
+            }
+        }
+        Err(_) => return,
+    };
+
+    let caster_name = {
+        let combatant = &mut encounter.participants[caster];
+        combatant.mana -= skill.skill.mana_cost;
+        combatant.cooldowns.insert(skill.skill.id, skill.skill.cooldown.max(0) as u32);
+        combatant.name.clone()
+    };
+    encounter.record(CombatEvent::SkillUsed {
+        caster: caster_name.clone(),
+        skill: skill.skill.name.clone(),
+        targets: targets.iter().map(|&t| encounter.participants[t].name.clone()).collect(),
+    });
+
+    let magnitude = effect_magnitude(skill, encounter.participants[caster].level);
+    for target in targets {
+        let target_name = encounter.participants[target].name.clone();
+        let landed = match skill.skill.skill_type {
+            SkillType::Magic => {
+                // Spells always land; physical skills still have to roll to hit
+                let amount = skill.effective_power() + encounter.participants[caster].attack / 2;
+                encounter.participants[target].take_damage(amount);
+                encounter.record(CombatEvent::Damaged {
+                    source: skill.skill.name.clone(),
+                    target: target_name.clone(),
+                    amount,
+                });
+                if !encounter.participants[target].is_alive() {
+                    encounter.record(CombatEvent::Defeated { combatant: target_name });
+                }
+                true
+            }
+            SkillType::Physical => {
+                let bonus = skill.effective_power() + encounter.participants[caster].attack;
+                encounter.resolve_strike(caster, target, bonus, rng)
+            }
+            SkillType::Support => {
+                // Support skills without power (e.g. Regeneration) only work through their effect
+                if skill.skill.power > 0 {
+                    let amount = encounter.participants[target].heal(magnitude);
+                    encounter.record(CombatEvent::Healed {
+                        source: caster_name.clone(),
+                        target: target_name,
+                        amount,
+                    });
+                }
+                true
+            }
+            SkillType::Buff | SkillType::Debuff => true,
+        };
+
+        if landed && encounter.participants[target].is_alive() {
+            if let Some(effect) = status_effect_for(skill, magnitude) {
+                encounter.apply_effect(target, effect);
+            }
+        }
+    }
+}
+
+/// The lingering effect a skill leaves on its targets. Buffs and debuffs without an explicit
+/// effect fall back to raising attack or lowering accuracy.
+fn status_effect_for(skill: &PlayerSkill, magnitude: i32) -> Option<StatusEffect> {
+    let kind = match (skill.skill.status_effect, skill.skill.skill_type) {
+        (Some(kind), _) => kind,
+        (None, SkillType::Buff) => EffectKind::AttackUp,
+        (None, SkillType::Debuff) => EffectKind::AccuracyDown,
+        (None, _) => return None,
+    };
+    let duration = match skill.skill.effect_seconds {
+        Some(seconds) => EffectDuration::Seconds(seconds.max(1) as u32),
+        None => EffectDuration::Turns(skill.skill.effect_duration.unwrap_or(DEFAULT_EFFECT_TURNS).max(1) as u32),
+    };
+
+    Some(StatusEffect::new(
+        &format!("skill_{}", skill.skill.id),
+        &skill.skill.name,
+        kind,
+        magnitude,
+        duration,
+        &format!("skill:{}", skill.skill.id),
+    ))
+}
+
+fn effect_magnitude(skill: &PlayerSkill, caster_level: i32) -> i32 {
+    if skill.skill.power > 0 {
+        skill.effective_power()
+    } else {
+        BASE_EFFECT_MAGNITUDE + caster_level + (skill.level - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::engine::combat::{CombatAction, Combatant, Team};
+    use crate::models::Skill;
+
+    fn skill(skill_type: SkillType, target_type: TargetType, power: i32, mana_cost: i32) -> PlayerSkill {
+        PlayerSkill {
+            skill: Skill {
+                id: 1,
+                name: "Test Skill".to_string(),
+                description: String::new(),
+                skill_type,
+                power,
+                cooldown: 2,
+                mana_cost,
+                target_type,
+                status_effect: None,
+                effect_duration: None,
+                effect_seconds: None,
+            },
+            level: 1,
+        }
+    }
+
+    /// The hero acts first, against a goblin and a weaker rat
+    fn encounter() -> CombatEncounter {
+        let hero = Combatant {
+            id: "player_1".to_string(),
+            team: Team::Players,
+            player_id: Some(1),
+            mana: 20,
+            max_mana: 20,
+            ..Combatant::monster("Hero", 5, 100)
+        };
+        let participants = vec![hero, Combatant::monster("Goblin", 1, 30), Combatant::monster("Rat", 1, 10)];
+        let mut encounter = CombatEncounter::new(participants, &mut StdRng::seed_from_u64(0));
+        encounter.turn_order = vec![0, 1, 2];
+        encounter
+    }
+
+    #[test]
+    fn targets_are_resolved_by_target_type() {
+        let encounter = encounter();
+        let goblin = encounter.participants[1].id.clone();
+        assert_eq!(resolve_targets(&encounter, 0, TargetType::Enemy, None), Ok(vec![2]));
+        assert_eq!(resolve_targets(&encounter, 0, TargetType::Enemy, Some(&goblin)), Ok(vec![1]));
+        assert_eq!(resolve_targets(&encounter, 0, TargetType::Enemy, Some("player_1")), Err(SkillError::InvalidTarget));
+        assert_eq!(resolve_targets(&encounter, 0, TargetType::Ally, None), Ok(vec![0]));
+        assert_eq!(resolve_targets(&encounter, 0, TargetType::Ally, Some(&goblin)), Err(SkillError::InvalidTarget));
+        assert_eq!(resolve_targets(&encounter, 0, TargetType::Caster, Some(&goblin)), Err(SkillError::InvalidTarget));
+    }
+
+    #[test]
+    fn skills_need_mana_and_to_be_off_cooldown() {
+        let mut encounter = encounter();
+        let fireball = skill(SkillType::Magic, TargetType::Enemy, 10, 25);
+        assert_eq!(
+            validate_skill(&encounter, 0, &fireball, None),
+            Err(SkillError::NotEnoughMana { needed: 25, available: 20 })
+        );
+
+        let fireball = skill(SkillType::Magic, TargetType::Enemy, 10, 5);
+        encounter.participants[0].cooldowns.insert(fireball.skill.id, 1);
+        assert_eq!(
+            validate_skill(&encounter, 0, &fireball, None),
+            Err(SkillError::OnCooldown { rounds_remaining: 1 })
+        );
+    }
+
+    #[test]
+    fn casting_spends_mana_and_starts_the_cooldown() {
+        let mut encounter = encounter();
+        let fireball = skill(SkillType::Magic, TargetType::Enemy, 10, 5);
+        let goblin = encounter.participants[1].id.clone();
+        use_skill(&mut encounter, 0, &fireball, Some(&goblin), &mut StdRng::seed_from_u64(0));
+
+        let damage = 10 + encounter.participants[0].attack / 2;
+        assert_eq!(encounter.participants[1].health, 30 - damage);
+        assert_eq!(encounter.participants[0].mana, 15);
+        assert_eq!(encounter.participants[0].cooldowns.get(&fireball.skill.id), Some(&2));
+    }
+
+    #[test]
+    fn buffs_cast_on_yourself_last_their_full_duration() {
+        let mut encounter = encounter();
+        let mut battle_cry = skill(SkillType::Buff, TargetType::Caster, 0, 0);
+        battle_cry.skill.effect_duration = Some(1);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let action = CombatAction::UseSkill { skill: battle_cry, target: None };
+        encounter.queue_action("player_1", action).unwrap();
+        encounter.run_round(&mut rng);
+        assert!(encounter.participants[0].effects.attack_modifier() > 0);
+
+        encounter.run_round(&mut rng);
+        assert_eq!(encounter.participants[0].effects.attack_modifier(), 0);
+    }
+}
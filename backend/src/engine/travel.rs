@@ -0,0 +1,338 @@
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use crate::db::items::{get_carried_content_ids, get_item_definitions};
+use crate::db::players::{get_player, get_player_region, get_player_room, get_unlocked_portals, set_player_region};
+use crate::engine::map_graph::MapGraph;
+use crate::engine::rooms::current_room;
+use crate::models::{EnvironmentType, Portal, Position, Region};
+
+/// Why a portal won't let a player through, worded for the player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TravelBlock {
+    LevelTooLow { portal: String, required_level: i32, level: i32 },
+    MissingItem { portal: String, item: String },
+    MissingEnvironmentItem { region: String, environment: EnvironmentType, item: String },
+    /// The region the portal leads to is gone
+    LeadsNowhere { portal: String },
+    /// The portal is taken from another room of the region
+    NotAtEntrance { portal: String, entrance: Position },
+}
+
+impl fmt::Display for TravelBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TravelBlock::LevelTooLow { portal, required_level, level } => write!(
+                f,
+                "{} only lets travelers of level {} or higher through; you are level {}.",
+                portal, required_level, level
+            ),
+            TravelBlock::MissingItem { portal, item } => {
+                write!(f, "{} won't open unless you carry the {}.", portal, item)
+            }
+            TravelBlock::MissingEnvironmentItem { region, environment, item } => write!(
+                f,
+                "{} is a {} world; you can't find your way there without the {}.",
+                region, environment, item
+            ),
+            TravelBlock::LeadsNowhere { portal } => write!(f, "{} flickers, but leads nowhere right now.", portal),
+            TravelBlock::NotAtEntrance { portal, entrance } => write!(
+                f,
+                "{} is taken from the room at ({}, {}); walk there first.",
+                portal, entrance.x, entrance.y
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TravelError {
+    PlayerNotFound(i32),
+    /// The player's region is no longer on the map; reloading content moves them to the nexus
+    Lost(String),
+    NoSuchPortal { portal_id: String, region: String },
+    Blocked(TravelBlock),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for TravelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TravelError::PlayerNotFound(player_id) => write!(f, "Player {} not found", player_id),
+            TravelError::Lost(region_id) => {
+                write!(f, "The region '{}' you are in no longer exists; you'll be returned to the nexus", region_id)
+            }
+            TravelError::NoSuchPortal { portal_id, region } => {
+                write!(f, "There is no portal '{}' in {}.", portal_id, region)
+            }
+            TravelError::Blocked(block) => block.fmt(f),
+            TravelError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TravelError {}
+
+impl From<sqlx::Error> for TravelError {
+    fn from(e: sqlx::Error) -> Self {
+        TravelError::Database(e)
+    }
+}
+
+/// What portals check about a player
+#[derive(Debug, Clone, Default)]
+pub struct Traveler {
+    pub level: i32,
+    /// Content IDs of the items carried
+    pub items: HashSet<String>,
+    pub unlocked_portals: HashSet<String>,
+    /// Names of the items gates may ask for, by content ID
+    pub item_names: HashMap<String, String>,
+    /// The room the player stands in, in regions with rooms
+    pub room: Option<Position>,
+}
+
+impl Traveler {
+    fn item_name(&self, content_id: &str) -> String {
+        self.item_names.get(content_id).cloned().unwrap_or_else(|| content_id.to_string())
+    }
+}
+
+/// Why `traveler` can't take `portal` out of `from`, if they can't. An item that unlocked the
+/// portal stands in for carrying its required item, but not for an environment's item. In
+/// regions with rooms the traveler has to stand at the portal's entrance.
+pub fn portal_block(map: &MapGraph, from: &Region, portal: &Portal, traveler: &Traveler) -> Option<TravelBlock> {
+    let Some(destination) = map.get_region(&portal.leads_to) else {
+        return Some(TravelBlock::LeadsNowhere { portal: portal.name.clone() });
+    };
+    if traveler.level < portal.required_level {
+        return Some(TravelBlock::LevelTooLow {
+            portal: portal.name.clone(),
+            required_level: portal.required_level,
+            level: traveler.level,
+        });
+    }
+    if let Some(item) = &portal.required_item {
+        if !traveler.items.contains(item) && !traveler.unlocked_portals.contains(&portal.id) {
+            return Some(TravelBlock::MissingItem { portal: portal.name.clone(), item: traveler.item_name(item) });
+        }
+    }
+    if let Some(item) = map.environment_item(destination.environment) {
+        if !traveler.items.contains(item) {
+            return Some(TravelBlock::MissingEnvironmentItem {
+                region: destination.name.clone(),
+                environment: destination.environment,
+                item: traveler.item_name(item),
+            });
+        }
+    }
+    if let (Some(layout), Some(room)) = (&from.layout, traveler.room) {
+        let entrance = layout.portal_position(&portal.id);
+        if entrance != room {
+            return Some(TravelBlock::NotAtEntrance { portal: portal.name.clone(), entrance });
+        }
+    }
+    None
+}
+
+/// A portal out of the player's region and whether they can take it
+#[derive(Debug, Clone, Serialize)]
+pub struct PortalOption {
+    #[serde(flatten)]
+    pub portal: Portal,
+    /// Name of the region it leads to
+    pub destination: Option<String>,
+    pub blocked: Option<String>,
+}
+
+/// Where a player is and the ways out
+#[derive(Debug, Clone, Serialize)]
+pub struct Location {
+    pub region_id: String,
+    pub name: String,
+    pub description: String,
+    pub environment: EnvironmentType,
+    pub portals: Vec<PortalOption>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Arrival {
+    pub from: String,
+    pub portal_id: String,
+    pub location: Location,
+    /// The room the player arrives in, for regions with rooms
+    pub position: Option<Position>,
+}
+
+/// Load what the portals of `region` check about a player
+async fn load_traveler(
+    conn: &mut PgConnection,
+    pool: &PgPool,
+    map: &MapGraph,
+    region: &Region,
+    player_id: i32,
+) -> Result<Traveler, TravelError> {
+    let player = get_player(&mut *conn, player_id)
+        .await?
+        .ok_or(TravelError::PlayerNotFound(player_id))?;
+    let items = get_carried_content_ids(&mut *conn, player_id).await?;
+    let unlocked_portals = get_unlocked_portals(&mut *conn, player_id).await?;
+    let (_, stored_room) = get_player_room(&mut *conn, player_id)
+        .await?
+        .ok_or(TravelError::PlayerNotFound(player_id))?;
+
+    let mut gate_items: Vec<String> = region.portals.iter().filter_map(|p| p.required_item.clone()).collect();
+    gate_items.extend(
+        region
+            .portals
+            .iter()
+            .filter_map(|p| map.get_region(&p.leads_to))
+            .filter_map(|r| map.environment_item(r.environment))
+            .map(str::to_string),
+    );
+    gate_items.sort();
+    gate_items.dedup();
+    let item_names = get_item_definitions(pool, &gate_items)
+        .await?
+        .into_iter()
+        .map(|item| (item.id, item.name))
+        .collect();
+
+    Ok(Traveler {
+        level: player.level,
+        items: items.into_iter().collect(),
+        unlocked_portals: unlocked_portals.into_iter().collect(),
+        item_names,
+        room: region.layout.as_ref().map(|layout| current_room(layout, stored_room)),
+    })
+}
+
+fn location(map: &MapGraph, region: &Region, traveler: &Traveler) -> Location {
+    Location {
+        region_id: region.id.clone(),
+        name: region.name.clone(),
+        description: region.description.clone(),
+        environment: region.environment,
+        portals: region
+            .portals
+            .iter()
+            .map(|portal| PortalOption {
+                portal: portal.clone(),
+                destination: map.get_region(&portal.leads_to).map(|r| r.name.clone()),
+                blocked: portal_block(map, region, portal, traveler).map(|block| block.to_string()),
+            })
+            .collect(),
+    }
+}
+
+/// The region a player is in, with every portal out of it and what stops them taking it
+pub async fn get_location(pool: &PgPool, map: &MapGraph, player_id: i32) -> Result<Location, TravelError> {
+    let mut conn = pool.acquire().await?;
+    let region_id = get_player_region(&mut *conn, player_id)
+        .await?
+        .ok_or(TravelError::PlayerNotFound(player_id))?;
+    let region = map.get_region(&region_id).ok_or(TravelError::Lost(region_id))?;
+    let traveler = load_traveler(&mut conn, pool, map, region, player_id).await?;
+    Ok(location(map, region, &traveler))
+}
+
+/// Take a portal out of the player's current region, if its level and item gates let them and
+/// they stand at its entrance
+pub async fn travel(pool: &PgPool, map: &MapGraph, player_id: i32, portal_id: &str) -> Result<Arrival, TravelError> {
+    let mut tx = pool.begin().await?;
+    // Locked, so two trips at once can't both start from the same region
+    let region_id: String = sqlx::query_scalar("SELECT region_id FROM players WHERE id = $1 FOR UPDATE")
+        .bind(player_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(TravelError::PlayerNotFound(player_id))?;
+    let from = map.get_region(&region_id).ok_or(TravelError::Lost(region_id))?;
+    let portal = from.portals.iter().find(|p| p.id == portal_id).ok_or_else(|| TravelError::NoSuchPortal {
+        portal_id: portal_id.to_string(),
+        region: from.name.clone(),
+    })?;
+
+    let traveler = load_traveler(&mut tx, pool, map, from, player_id).await?;
+    if let Some(block) = portal_block(map, from, portal, &traveler) {
+        return Err(TravelError::Blocked(block));
+    }
+    // Checked by `portal_block`
+    let destination = map.get_region(&portal.leads_to).unwrap();
+
+    let position = destination.layout.as_ref().map(|layout| layout.arrival(&destination.portals, &from.id));
+    set_player_region(&mut *tx, player_id, &destination.id, position).await?;
+    tx.commit().await?;
+
+    // Gates are re-checked from where the player now stands
+    let arrived = load_traveler(&mut *pool.acquire().await?, pool, map, destination, player_id).await?;
+    Ok(Arrival {
+        from: from.id.clone(),
+        portal_id: portal.id.clone(),
+        location: location(map, destination, &arrived),
+        position,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Entrance, RoomLayout};
+
+    fn region(id: &str, portals: Vec<Portal>, layout: Option<RoomLayout>) -> Region {
+        Region {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            environment: EnvironmentType::Fantasy,
+            portals,
+            anchor_point: None,
+            generated: None,
+            layout,
+        }
+    }
+
+    fn setup() -> (MapGraph, Region, Portal) {
+        let portal = Portal {
+            id: "cave_exit".to_string(),
+            name: "Cave Exit".to_string(),
+            leads_to: "meadow".to_string(),
+            required_level: 0,
+            required_item: None,
+        };
+        let layout = RoomLayout {
+            rows: vec!["#####".to_string(), "#...#".to_string(), "#####".to_string()],
+            start: Position { x: 1, y: 1 },
+            entrances: vec![Entrance { portal_id: portal.id.clone(), x: 3, y: 1 }],
+        };
+        let cave = region("cave", vec![portal.clone()], Some(layout));
+        let map = MapGraph::new(vec![cave.clone(), region("meadow", Vec::new(), None)]);
+        (map, cave, portal)
+    }
+
+    fn traveler(room: Option<Position>) -> Traveler {
+        Traveler {
+            level: 1,
+            items: HashSet::new(),
+            unlocked_portals: HashSet::new(),
+            item_names: HashMap::new(),
+            room,
+        }
+    }
+
+    #[test]
+    fn portals_are_taken_from_their_entrance() {
+        let (map, cave, portal) = setup();
+        let block = portal_block(&map, &cave, &portal, &traveler(Some(Position { x: 1, y: 1 })));
+        assert!(matches!(block, Some(TravelBlock::NotAtEntrance { entrance: Position { x: 3, y: 1 }, .. })));
+        assert!(portal_block(&map, &cave, &portal, &traveler(Some(Position { x: 3, y: 1 }))).is_none());
+    }
+
+    #[test]
+    fn other_gates_are_checked_before_the_room() {
+        let (map, cave, mut portal) = setup();
+        portal.required_level = 5;
+        let block = portal_block(&map, &cave, &portal, &traveler(Some(Position { x: 1, y: 1 })));
+        assert!(matches!(block, Some(TravelBlock::LevelTooLow { required_level: 5, level: 1, .. })));
+    }
+}
@@ -1,59 +1,184 @@
 use rand::seq::SliceRandom;
 use rand::Rng;
-use std::fs;
-use std::path::Path;
-use std::io::Write;
-use crate::models::{DungeonRegion, EnvironmentType, Portal};
-use anyhow::Result;
-
-const ENV_TYPES: &[EnvironmentType] = &[
-    EnvironmentType::Fantasy,
-    EnvironmentType::Technology,
-    EnvironmentType::RealLife,
-    EnvironmentType::Hybrid,
-];
-
-/// Generate a random environment type
-fn random_env() -> EnvironmentType {
-    ENV_TYPES.choose(&mut rand::thread_rng()).unwrap().clone()
+use sqlx::PgPool;
+use crate::db::regions::{add_portal, upsert_region, RegionOrigin, RegionSaveError};
+use crate::models::{EnvironmentType, GenerationInfo, Portal, Region};
+use crate::procedural::layout::{generate_layout, LayoutConfig};
+use crate::procedural::text::TextGenerator;
+use crate::procedural::{seeded_rng, text_rng, unique_id, GENERATOR_VERSION};
+
+pub const GENERATOR_NAME: &str = "worldgen::generate_region";
+
+/// A new region plus the portals existing regions need so players can get into it
+#[derive(Debug, Clone)]
+pub struct GeneratedRegion {
+    pub region: Region,
+    /// (existing region ID, portal to add to it)
+    pub reverse_portals: Vec<(String, Portal)>,
+}
+
+impl GeneratedRegion {
+    /// Add the reverse portals to the existing regions they belong to
+    pub fn link_into(&self, regions: &mut [Region]) {
+        for (region_id, portal) in &self.reverse_portals {
+            if let Some(region) = regions.iter_mut().find(|r| &r.id == region_id) {
+                if !region.portals.iter().any(|p| p.id == portal.id) {
+                    region.portals.push(portal.clone());
+                }
+            }
+        }
+    }
 }
 
-/// Generate a new region with a unique name and portals
-pub fn generate_region(existing_region_ids: &[String]) -> DungeonRegion {
-    let mut rng = rand::thread_rng();
-    let id = format!("region_{}", rng.gen_range(1000..9999));
-    let name = format!("Mystery Zone {}", rng.gen_range(1..100));
-    let description = format!("A procedurally generated zone of strangeness and wonder.");
-    let environment = random_env();
+/// Generate a new region connected to one or two existing regions, named and described by
+/// `text` for its environment. The ID is derived from the seed and never collides with an
+/// existing region; to regenerate a region exactly, pass its recorded seed and the regions that
+/// existed before it. With a `layout` config the region gets rooms to explore.
+pub fn generate_region(
+    existing: &[Region],
+    seed: u64,
+    text: &TextGenerator,
+    layout: Option<&LayoutConfig>,
+) -> GeneratedRegion {
+    let mut rng = seeded_rng(seed);
+    let mut text_rng = text_rng(seed);
+
+    let existing_ids: Vec<String> = existing.iter().map(|r| r.id.clone()).collect();
+    let id = unique_id(format!("region_{:016x}", seed), &existing_ids);
+    let environment = *EnvironmentType::ALL.choose(&mut rng).unwrap();
+    let name = text.region_name(environment, &mut text_rng);
+    let description = text.region_description(environment, &name, &mut text_rng);
+
+    // Sorted so the same seed picks the same neighbours whatever order the regions came in
+    let mut candidates: Vec<&Region> = existing.iter().collect();
+    candidates.sort_by(|a, b| a.id.cmp(&b.id));
+    candidates.dedup_by(|a, b| a.id == b.id);
 
     let mut portals = vec![];
+    let mut reverse_portals = vec![];
 
     // Connect to one or two existing regions at random
-    let connections = existing_region_ids.choose_multiple(&mut rng, 2);
-    for conn in connections {
+    let count = rng.gen_range(1..=2);
+    for conn in candidates.choose_multiple(&mut rng, count) {
+        // Getting in is gated; the way back out is always open
+        let required_level = rng.gen_range(1..10);
         portals.push(Portal {
-            id: format!("portal_to_{}", conn),
-            name: format!("Portal to {}", conn),
-            leads_to: conn.clone(),
-            required_level: rng.gen_range(1..10),
+            id: format!("portal_{}_to_{}", id, conn.id),
+            name: text.portal_name(environment, &conn.name, &mut text_rng),
+            leads_to: conn.id.clone(),
+            required_level: 0,
+            required_item: None,
         });
+        reverse_portals.push((
+            conn.id.clone(),
+            Portal {
+                id: format!("portal_{}_to_{}", conn.id, id),
+                name: text.portal_name(conn.environment, &name, &mut text_rng),
+                leads_to: id.clone(),
+                required_level,
+                required_item: None,
+            },
+        ));
     }
 
-    DungeonRegion {
-        id,
-        name,
-        description,
-        environment,
-        portals,
-        anchor_point: None,
+    let layout = layout.map(|config| generate_layout(config, rng.gen(), &portals));
+    GeneratedRegion {
+        region: Region {
+            id,
+            name,
+            description,
+            environment,
+            portals,
+            anchor_point: None,
+            generated: Some(GenerationInfo {
+                generator: GENERATOR_NAME.to_string(),
+                seed,
+                version: GENERATOR_VERSION,
+            }),
+            layout,
+        },
+        reverse_portals,
     }
 }
 
-/// Save the new region as a TOML file
-pub fn save_region(region: &DungeonRegion, dir: &str) -> Result<()> {
-    let toml_str = toml::to_string_pretty(&region)?;
-    let file_path = Path::new(dir).join(format!("{}.toml", region.id));
-    let mut file = fs::File::create(file_path)?;
-    file.write_all(toml_str.as_bytes())?;
+/// Store the new region and the portals it adds to the regions it connects to, all or nothing.
+/// The content watcher notices and puts it on the running map.
+pub async fn save_region(pool: &PgPool, generated: &GeneratedRegion) -> Result<(), RegionSaveError> {
+    let mut tx = pool.begin().await?;
+    upsert_region(&mut tx, &generated.region, RegionOrigin::Generated).await?;
+    for (region_id, portal) in &generated.reverse_portals {
+        add_portal(&mut tx, region_id, portal, &generated.region.id).await?;
+    }
+    tx.commit().await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(id: &str) -> Region {
+        Region {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            environment: EnvironmentType::Fantasy,
+            portals: Vec::new(),
+            anchor_point: None,
+            generated: None,
+            layout: None,
+        }
+    }
+
+    fn existing() -> Vec<Region> {
+        vec![region("nexus"), region("forest"), region("lab")]
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_region() {
+        let text = TextGenerator::builtin();
+        let first = generate_region(&existing(), 99, &text, Some(&LayoutConfig::default()));
+        let second = generate_region(&existing(), 99, &text, Some(&LayoutConfig::default()));
+        assert_eq!(format!("{:?}", first), format!("{:?}", second));
+        assert_eq!(first.region.generated.as_ref().map(|g| g.seed), Some(99));
+    }
+
+    #[test]
+    fn neighbours_dont_depend_on_the_order_of_existing_regions() {
+        let text = TextGenerator::builtin();
+        let mut reversed = existing();
+        reversed.reverse();
+        let first = generate_region(&existing(), 5, &text, None);
+        let second = generate_region(&reversed, 5, &text, None);
+        assert_eq!(format!("{:?}", first), format!("{:?}", second));
+    }
+
+    #[test]
+    fn id_never_collides_with_an_existing_region() {
+        let text = TextGenerator::builtin();
+        let mut regions = existing();
+        regions.push(region(&format!("region_{:016x}", 3)));
+        let generated = generate_region(&regions, 3, &text, None);
+        assert_eq!(generated.region.id, format!("region_{:016x}_2", 3));
+    }
+
+    #[test]
+    fn reverse_portals_lead_into_the_new_region() {
+        let text = TextGenerator::builtin();
+        let mut regions = existing();
+        let generated = generate_region(&regions, 11, &text, None);
+        assert_eq!(generated.region.portals.len(), generated.reverse_portals.len());
+        for portal in &generated.region.portals {
+            assert_eq!(portal.required_level, 0, "the way out is always open");
+        }
+
+        generated.link_into(&mut regions);
+        generated.link_into(&mut regions);
+        for (region_id, portal) in &generated.reverse_portals {
+            let linked = regions.iter().find(|r| &r.id == region_id).unwrap();
+            let copies = linked.portals.iter().filter(|p| p.id == portal.id).count();
+            assert_eq!(copies, 1, "linking twice adds the portal once");
+            assert_eq!(portal.leads_to, generated.region.id);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod api;
+pub mod db;
+pub mod engine;
+pub mod loader;
+pub mod models;
+pub mod procedural;
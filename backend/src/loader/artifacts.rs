@@ -1,22 +1,121 @@
+use crate::loader::diagnostics::{into_warnings, line_at, toml_error, Diagnostic};
 use crate::models::Artifact;
+use serde::Deserialize;
 use std::fs;
-use std::path::Path;
-use anyhow::Result;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use toml::Spanned;
 
 pub fn load_artifacts_from_dir(dir_path: &str) -> Result<Vec<Artifact>> {
-    let mut artifacts = Vec::new();
-    let entries = fs::read_dir(dir_path)?;
+    let (artifacts, diagnostics) = read_artifacts(Path::new(dir_path))?;
+    into_warnings(diagnostics)?;
+    Ok(artifacts.into_iter().map(|source| source.artifact).collect())
+}
+
+/// An artifact, the file it came from and the line its ID is on
+#[derive(Debug, Clone)]
+pub struct ArtifactSource {
+    pub path: PathBuf,
+    pub artifact: Artifact,
+    pub id_line: usize,
+}
+
+/// Just the ID of an artifact file, with where it is written
+#[derive(Deserialize)]
+struct ArtifactId {
+    #[serde(alias = "artifact_id")]
+    id: Spanned<String>,
+}
+
+/// Keys older artifact files used, which `Artifact` still accepts
+#[derive(Deserialize)]
+struct LegacyKeys {
+    artifact_id: Option<Spanned<toml::Value>>,
+    affinity: Option<Spanned<toml::Value>>,
+}
+
+/// Read every artifact file in a directory, collecting parse errors and duplicate IDs
+pub fn read_artifacts(dir: &Path) -> Result<(Vec<ArtifactSource>, Vec<Diagnostic>)> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "toml") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut artifacts: Vec<ArtifactSource> = Vec::new();
+    let mut diagnostics = Vec::new();
+    for path in paths {
+        let content = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        let (artifact, id) = match (toml::from_str::<Artifact>(&content), toml::from_str::<ArtifactId>(&content)) {
+            (Ok(artifact), Ok(id)) => (artifact, id),
+            (Err(e), _) | (_, Err(e)) => {
+                diagnostics.push(toml_error(&path, &content, &e));
+                continue;
+            }
+        };
+        let id_line = line_at(&content, id.id.span().start);
+        if let Ok(legacy) = toml::from_str::<LegacyKeys>(&content) {
+            let renamed = [(legacy.artifact_id, "artifact_id", "id"), (legacy.affinity, "affinity", "magic_affinity")];
+            for (value, old, new) in renamed {
+                if let Some(value) = value {
+                    diagnostics.push(Diagnostic::warning(
+                        &path,
+                        Some(line_at(&content, value.span().start)),
+                        format!("`{}` is deprecated, use `{}`", old, new),
+                    ));
+                }
+            }
+        }
+
+        let problems = validate_artifact(&artifact);
+        if !problems.is_empty() {
+            diagnostics.extend(problems.into_iter().map(|problem| Diagnostic::error(&path, Some(id_line), problem)));
+            continue;
+        }
 
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
+        if let Some(first) = artifacts.iter().find(|other| other.artifact.id == artifact.id) {
+            diagnostics.push(Diagnostic::error(
+                &path,
+                Some(id_line),
+                format!(
+                    "artifact id '{}' is already defined at {}:{}",
+                    artifact.id,
+                    first.path.display(),
+                    first.id_line
+                ),
+            ));
+            continue;
+        }
+        artifacts.push(ArtifactSource { path, artifact, id_line });
+    }
 
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "toml") {
-            let content = fs::read_to_string(&path)?;
-            let artifact: Artifact = toml::from_str(&content)?;
-            artifacts.push(artifact);
+    Ok((artifacts, diagnostics))
+}
+
+/// Everything wrong with an artifact that the type system doesn't already rule out. IDs may be
+/// old-style UUIDs, so hyphens are allowed.
+pub fn validate_artifact(artifact: &Artifact) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let valid_id = !artifact.id.is_empty()
+        && artifact.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_id {
+        problems.push(format!("id '{}' must be non-empty letters, digits, underscores and hyphens", artifact.id));
+    }
+    if artifact.name.trim().is_empty() {
+        problems.push("name must not be empty".to_string());
+    }
+    if artifact.power < 0 {
+        problems.push(format!("power must not be negative, got {}", artifact.power));
+    }
+    if let Some(affinity) = artifact.magic_affinity {
+        if affinity < 0 {
+            problems.push(format!("magic_affinity must not be negative when set, got {}", affinity));
         }
     }
 
-    Ok(artifacts)
+    problems
 }
@@ -0,0 +1,241 @@
+use crate::engine::map_graph::{MapGraph, NEXUS_ID};
+use crate::loader::artifacts::read_artifacts;
+use crate::loader::diagnostics::Diagnostic;
+use crate::loader::dungeons::{read_regions, RegionSource};
+use crate::loader::environments::{read_environments, EnvironmentSource};
+use crate::loader::grammars::{read_builtin_grammars, read_grammars};
+use crate::loader::items::read_items;
+use crate::models::{ItemEffect, Region};
+use crate::procedural::text::TextGenerator;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Check a whole content pack: every file on its own, then the links between them. Regions are
+/// required; artifacts, items, grammars and environments are checked when they exist.
+pub fn check_content(content_dir: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    // Grammars come first since procedural regions are named with them. The built-in ones are
+    // checked too, since content leans on them for every environment it doesn't theme.
+    diagnostics.extend(read_builtin_grammars().1);
+    let mut text = TextGenerator::builtin();
+    let grammars_dir = content_dir.join("grammars");
+    if grammars_dir.is_dir() {
+        match read_grammars(&grammars_dir) {
+            Ok((grammars, found)) => {
+                diagnostics.extend(found);
+                for source in grammars {
+                    text.set_grammar(source.environment, source.grammar);
+                }
+            }
+            Err(e) => diagnostics.push(Diagnostic::error(&grammars_dir, None, format!("{:#}", e))),
+        }
+    }
+
+    let regions_dir = content_dir.join("regions");
+    let regions = match read_regions(&regions_dir, &text) {
+        Ok((regions, found)) => {
+            diagnostics.extend(found);
+            regions
+        }
+        Err(e) => {
+            diagnostics.push(Diagnostic::error(&regions_dir, None, format!("{:#}", e)));
+            Vec::new()
+        }
+    };
+    check_region_links(&regions_dir, &regions, &mut diagnostics);
+
+    let artifacts_dir = content_dir.join("artifacts");
+    if artifacts_dir.is_dir() {
+        match read_artifacts(&artifacts_dir) {
+            Ok((_, found)) => diagnostics.extend(found),
+            Err(e) => diagnostics.push(Diagnostic::error(&artifacts_dir, None, format!("{:#}", e))),
+        }
+    }
+
+    let environments_path = content_dir.join("environments.toml");
+    let environments = if environments_path.is_file() {
+        match read_environments(&environments_path) {
+            Ok((environments, found)) => {
+                diagnostics.extend(found);
+                environments
+            }
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(&environments_path, None, format!("{:#}", e)));
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    // Without an items directory no item exists, so anything asking for one is broken
+    let items_dir = content_dir.join("items");
+    let mut item_ids = Some(HashSet::new());
+    if items_dir.is_dir() {
+        match read_items(&items_dir) {
+            Ok((items, found)) => {
+                diagnostics.extend(found);
+                item_ids = Some(items.iter().map(|source| source.item.id.clone()).collect());
+                let portal_ids: HashSet<&str> = regions
+                    .iter()
+                    .flat_map(|s| &s.region.portals)
+                    .map(|p| p.id.as_str())
+                    .collect();
+                for source in &items {
+                    for (index, effect) in source.item.effects.iter().enumerate() {
+                        if let ItemEffect::UnlockPortal { portal_id } = effect {
+                            if !portal_ids.contains(portal_id.as_str()) {
+                                diagnostics.push(Diagnostic::error(
+                                    &source.path,
+                                    source.lines.effect(index),
+                                    format!("item '{}' unlocks unknown portal '{}'", source.item.id, portal_id),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(&items_dir, None, format!("{:#}", e)));
+                item_ids = None;
+            }
+        }
+    }
+    if let Some(item_ids) = item_ids {
+        check_required_items(&regions, &environments, &item_ids, &mut diagnostics);
+    }
+
+    diagnostics.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    diagnostics
+}
+
+/// Portals and environments asking for items that don't exist
+fn check_required_items(
+    regions: &[RegionSource],
+    environments: &[EnvironmentSource],
+    item_ids: &HashSet<String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for source in regions {
+        for (portal, lines) in source.region.portals.iter().zip(&source.portal_lines) {
+            let Some(item) = &portal.required_item else {
+                continue;
+            };
+            if !item_ids.contains(item) {
+                diagnostics.push(Diagnostic::error(
+                    &source.path,
+                    Some(lines.required_item),
+                    format!("portal '{}' requires unknown item '{}'", portal.id, item),
+                ));
+            }
+        }
+    }
+    for source in environments {
+        if !item_ids.contains(&source.required_item) {
+            diagnostics.push(Diagnostic::error(
+                &source.path,
+                Some(source.required_item_line),
+                format!("environment '{}' requires unknown item '{}'", source.environment, source.required_item),
+            ));
+        }
+    }
+}
+
+/// Broken portals, regions the nexus can't reach and anchors that don't match a nexus portal
+fn check_region_links(regions_dir: &Path, sources: &[RegionSource], diagnostics: &mut Vec<Diagnostic>) {
+    let map = MapGraph::new(sources.iter().map(|s| s.region.clone()).collect());
+    let Some(nexus) = map.get_region(NEXUS_ID) else {
+        diagnostics.push(Diagnostic::error(regions_dir, None, format!("there is no '{}' region", NEXUS_ID)));
+        return;
+    };
+
+    for source in sources {
+        for (portal, lines) in source.region.portals.iter().zip(&source.portal_lines) {
+            if map.get_region(&portal.leads_to).is_none() {
+                diagnostics.push(Diagnostic::error(
+                    &source.path,
+                    Some(lines.leads_to),
+                    format!("portal '{}' leads to unknown region '{}'", portal.id, portal.leads_to),
+                ));
+            }
+        }
+    }
+
+    // Levels only delay access, so anything missing here can't be reached at all
+    let reachable: HashSet<String> = map.reachable_from_nexus(i32::MAX).into_iter().collect();
+    let orphans: HashSet<String> = map.orphan_regions().into_iter().collect();
+    for source in sources {
+        let id = &source.region.id;
+        if !reachable.contains(id) {
+            let reason = if orphans.contains(id) {
+                "no portal leads into it"
+            } else {
+                "only regions cut off from the nexus lead into it"
+            };
+            diagnostics.push(Diagnostic::error(
+                &source.path,
+                Some(source.id_line),
+                format!("region '{}' can't be reached from the nexus: {}", id, reason),
+            ));
+        }
+
+        let Some(anchor) = &source.region.anchor_point else {
+            continue;
+        };
+        match nexus.portals.iter().find(|p| &p.id == anchor) {
+            None => diagnostics.push(Diagnostic::error(
+                &source.path,
+                source.anchor_line,
+                format!("anchor point '{}' is not a portal in the nexus", anchor),
+            )),
+            Some(portal) if &portal.leads_to != id => diagnostics.push(Diagnostic::error(
+                &source.path,
+                source.anchor_line,
+                format!("anchor point '{}' leads to '{}', not to this region", anchor, portal.leads_to),
+            )),
+            Some(_) => {}
+        }
+    }
+}
+
+/// The same link and item checks for a map built from the stored regions, which have no files
+/// to point at. Empty if the map is fine to go live.
+pub fn check_map(map: &MapGraph, item_ids: &HashSet<String>) -> Vec<String> {
+    let mut problems = Vec::new();
+    if map.get_region(NEXUS_ID).is_none() {
+        problems.push(format!("there is no '{}' region", NEXUS_ID));
+    }
+
+    let reachable: HashSet<String> = map.reachable_from_nexus(i32::MAX).into_iter().collect();
+    let mut regions: Vec<&Region> = map.regions.values().collect();
+    regions.sort_by(|a, b| a.id.cmp(&b.id));
+    for region in regions {
+        if !reachable.contains(&region.id) {
+            problems.push(format!("region '{}' can't be reached from the nexus", region.id));
+        }
+        for portal in &region.portals {
+            if map.get_region(&portal.leads_to).is_none() {
+                problems.push(format!(
+                    "region '{}': portal '{}' leads to unknown region '{}'",
+                    region.id, portal.id, portal.leads_to
+                ));
+            }
+            if let Some(item) = portal.required_item.as_ref().filter(|item| !item_ids.contains(*item)) {
+                problems.push(format!(
+                    "region '{}': portal '{}' requires unknown item '{}'",
+                    region.id, portal.id, item
+                ));
+            }
+        }
+    }
+
+    let mut environments: Vec<_> = map.environment_items.iter().collect();
+    environments.sort_by_key(|(environment, _)| environment.as_str());
+    for (environment, item) in environments {
+        if !item_ids.contains(item) {
+            problems.push(format!("environment '{}' requires unknown item '{}'", environment, item));
+        }
+    }
+    problems
+}
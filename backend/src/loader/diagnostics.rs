@@ -0,0 +1,66 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in a content file, pointing at the line it is on when known
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(path: &Path, line: Option<usize>, message: impl Into<String>) -> Self {
+        Diagnostic { severity: Severity::Error, path: path.to_path_buf(), line, message: message.into() }
+    }
+
+    pub fn warning(path: &Path, line: Option<usize>, message: impl Into<String>) -> Self {
+        Diagnostic { severity: Severity::Warning, path: path.to_path_buf(), line, message: message.into() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}: {}", self.path.display(), line, severity, self.message),
+            None => write!(f, "{}: {}: {}", self.path.display(), severity, self.message),
+        }
+    }
+}
+
+/// The 1-based line a byte offset falls on
+pub fn line_at(source: &str, offset: usize) -> usize {
+    let offset = offset.min(source.len());
+    source.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+/// Turn a TOML parse error into a diagnostic on the line it points at
+pub fn toml_error(path: &Path, source: &str, err: &toml::de::Error) -> Diagnostic {
+    let line = err.span().map(|span| line_at(source, span.start));
+    Diagnostic::error(path, line, err.message().trim())
+}
+
+/// Fail with every error found, or hand back the warnings when there were none
+pub fn into_warnings(diagnostics: Vec<Diagnostic>) -> Result<Vec<Diagnostic>> {
+    let errors: Vec<String> = diagnostics.iter().filter(|d| d.is_error()).map(|d| d.to_string()).collect();
+    if !errors.is_empty() {
+        return Err(anyhow!(errors.join("\n")));
+    }
+    Ok(diagnostics)
+}
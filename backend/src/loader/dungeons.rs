@@ -1,22 +1,407 @@
-use crate::models::DungeonRegion;
+use crate::loader::diagnostics::{into_warnings, line_at, toml_error, Diagnostic};
+use crate::models::{EnvironmentType, GenerationInfo, Portal, Region, RoomLayout};
+use crate::procedural::stub::{expand_stub, ProceduralSpec, RegionStub};
+use crate::procedural::text::TextGenerator;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use anyhow::Result;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use toml::Spanned;
 
-pub fn load_regions_from_dir(dir_path: &str) -> Result<Vec<DungeonRegion>> {
-    let mut regions = Vec::new();
-    let entries = fs::read_dir(dir_path)?;
+/// A region file as written. Besides the current format this accepts what the older region and
+/// portal models wrote: `from_region` on portals, portals without an `id`, and the `Realistic`
+/// environment. Files with a `[procedural]` table are stubs; anything they leave out is
+/// generated.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegionFile {
+    id: Spanned<String>,
+    name: Option<String>,
+    description: Option<String>,
+    environment: Option<Spanned<String>>,
+    #[serde(default)]
+    portals: Vec<PortalFile>,
+    anchor_point: Option<Spanned<String>>,
+    generated: Option<GenerationInfo>,
+    layout: Option<Spanned<RoomLayout>>,
+    procedural: Option<Spanned<ProceduralSpec>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PortalFile {
+    id: Option<Spanned<String>>,
+    name: Spanned<String>,
+    from_region: Option<Spanned<String>>,
+    leads_to: Spanned<String>,
+    required_level: Spanned<i32>,
+    required_item: Option<Spanned<String>>,
+}
+
+/// Where a portal's fields sit in its region file
+#[derive(Debug, Clone)]
+pub struct PortalLines {
+    pub id: usize,
+    pub leads_to: usize,
+    /// The `id` line when the portal asks for no item
+    pub required_item: usize,
+}
+
+/// A region along with the file and lines it was defined on, so problems found across regions
+/// can point at the right place. Generated regions point at the `[procedural]` table they came
+/// from.
+#[derive(Debug, Clone)]
+pub struct RegionSource {
+    pub path: PathBuf,
+    pub region: Region,
+    pub id_line: usize,
+    pub anchor_line: Option<usize>,
+    pub portal_lines: Vec<PortalLines>, // same order as `region.portals`
+}
 
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
+/// A region file with a `[procedural]` table, before it is expanded
+#[derive(Debug, Clone)]
+pub struct StubSource {
+    pub path: PathBuf,
+    pub stub: RegionStub,
+    pub id_line: usize,
+    pub anchor_line: Option<usize>,
+    pub portal_lines: Vec<PortalLines>, // same order as `stub.portals`
+    pub procedural_line: usize,
+}
 
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "toml") {
-            let content = fs::read_to_string(&path)?;
-            let region: DungeonRegion = toml::from_str(&content)?;
-            regions.push(region);
+/// What a region file holds
+#[derive(Debug, Clone)]
+pub enum RegionFileContents {
+    Region(RegionSource),
+    Stub(StubSource),
+}
+
+impl StubSource {
+    /// Generate the stub's regions. Everything generated points at the `[procedural]` table.
+    pub fn expand(
+        self,
+        existing_region_ids: &[String],
+        text: &TextGenerator,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<RegionSource> {
+        let regions = match expand_stub(&self.stub, existing_region_ids, text) {
+            Ok(regions) => regions,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(&self.path, Some(self.procedural_line), e.to_string()));
+                return Vec::new();
+            }
+        };
+        let generated_lines = |count: usize, written: &[PortalLines]| -> Vec<PortalLines> {
+            let mut lines = written.to_vec();
+            let procedural = self.procedural_line;
+            lines.resize(count, PortalLines { id: procedural, leads_to: procedural, required_item: procedural });
+            lines
+        };
+
+        let mut regions = regions.into_iter();
+        let mut sources = Vec::new();
+        if let Some(region) = regions.next() {
+            sources.push(RegionSource {
+                path: self.path.clone(),
+                id_line: self.id_line,
+                anchor_line: self.anchor_line,
+                portal_lines: generated_lines(region.portals.len(), &self.portal_lines),
+                region,
+            });
+        }
+        for region in regions {
+            sources.push(RegionSource {
+                path: self.path.clone(),
+                id_line: self.procedural_line,
+                anchor_line: None,
+                portal_lines: generated_lines(region.portals.len(), &[]),
+                region,
+            });
         }
+        sources
     }
+}
 
+/// Load every region, printing a deprecation warning for each outdated file
+pub fn load_regions_from_dir(dir_path: &str, text: &TextGenerator) -> Result<Vec<Region>> {
+    let (regions, warnings) = load_regions_with_warnings(dir_path, text)?;
+    for warning in warnings {
+        eprintln!("⚠️ Deprecated region content in {}", warning);
+    }
     Ok(regions)
 }
+
+/// Load every region along with the deprecated constructs the files still use. Fails with
+/// every problem found if any file is invalid.
+pub fn load_regions_with_warnings(dir_path: &str, text: &TextGenerator) -> Result<(Vec<Region>, Vec<Diagnostic>)> {
+    let (sources, diagnostics) = read_regions(Path::new(dir_path), text)?;
+    let warnings = into_warnings(diagnostics)?;
+    Ok((sources.into_iter().map(|s| s.region).collect(), warnings))
+}
+
+/// Write each region to `<dir>/<id>.toml` in the format the loader reads, creating `dir` if
+/// needed. Existing files for the same regions are overwritten.
+pub fn write_region_files(regions: &[Region], dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    for region in regions {
+        let toml_str = toml::to_string_pretty(region).with_context(|| format!("serializing region '{}'", region.id))?;
+        let path = dir.join(format!("{}.toml", region.id));
+        fs::write(&path, toml_str).with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Read and check every region file in a directory, collecting problems instead of stopping at
+/// the first. Files that can't be turned into a region are left out of the result. Stubs are
+/// expanded with `text` once every written region is known, in file name order, so the IDs
+/// they generate only depend on the content.
+pub fn read_regions(dir: &Path, text: &TextGenerator) -> Result<(Vec<RegionSource>, Vec<Diagnostic>)> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "toml") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut written = Vec::new();
+    let mut stubs = Vec::new();
+    let mut diagnostics = Vec::new();
+    for path in paths {
+        match read_region_file(&path, &mut diagnostics)? {
+            Some(RegionFileContents::Region(source)) => written.push(source),
+            Some(RegionFileContents::Stub(stub)) => stubs.push(stub),
+            None => {}
+        }
+    }
+
+    // Every ID a file claims is taken before anything is generated
+    let mut taken: Vec<String> = written.iter().map(|s| s.region.id.clone()).collect();
+    taken.extend(stubs.iter().map(|s| s.stub.id.clone()));
+    let mut candidates = written;
+    for stub in stubs {
+        let generated = stub.expand(&taken, text, &mut diagnostics);
+        taken.extend(generated.iter().skip(1).map(|s| s.region.id.clone()));
+        candidates.extend(generated);
+    }
+
+    let mut sources: Vec<RegionSource> = Vec::new();
+    for source in candidates {
+        if let Some(first) = sources.iter().find(|s| s.region.id == source.region.id) {
+            diagnostics.push(Diagnostic::error(
+                &source.path,
+                Some(source.id_line),
+                format!(
+                    "region id '{}' is already defined at {}:{}",
+                    source.region.id,
+                    first.path.display(),
+                    first.id_line
+                ),
+            ));
+            continue;
+        }
+        sources.push(source);
+    }
+
+    // Portal IDs are referenced from anchors and items, so they must be unique across regions
+    let mut portal_ids: HashMap<&str, (&Path, usize)> = HashMap::new();
+    for source in &sources {
+        for (portal, lines) in source.region.portals.iter().zip(&source.portal_lines) {
+            if let Some((first_path, first_line)) = portal_ids.insert(&portal.id, (&source.path, lines.id)) {
+                diagnostics.push(Diagnostic::error(
+                    &source.path,
+                    Some(lines.id),
+                    format!("portal id '{}' is already defined at {}:{}", portal.id, first_path.display(), first_line),
+                ));
+            }
+        }
+    }
+
+    Ok((sources, diagnostics))
+}
+
+/// Parse one region file into the canonical model, or a stub for `read_regions` to expand.
+/// Returns `None` when the file has errors, which are added to `diagnostics` along with any
+/// deprecation warnings.
+pub fn read_region_file(path: &Path, diagnostics: &mut Vec<Diagnostic>) -> Result<Option<RegionFileContents>> {
+    let content = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let file: RegionFile = match toml::from_str(&content) {
+        Ok(file) => file,
+        Err(e) => {
+            diagnostics.push(toml_error(path, &content, &e));
+            return Ok(None);
+        }
+    };
+    let line = |span: std::ops::Range<usize>| line_at(&content, span.start);
+    let errors_before = diagnostics.iter().filter(|d| d.is_error()).count();
+    let region_id = file.id.get_ref().clone();
+    let id_line = line(file.id.span());
+
+    let environment = match (file.environment, &file.procedural) {
+        (Some(environment), Some(_)) => {
+            diagnostics.push(Diagnostic::error(
+                path,
+                Some(line(environment.span())),
+                "procedural regions take their environment from `theme` in [procedural]",
+            ));
+            None
+        }
+        (Some(environment), None) => {
+            let environment_line = line(environment.span());
+            match environment.into_inner() {
+                legacy if legacy == "Realistic" => {
+                    diagnostics.push(Diagnostic::warning(
+                        path,
+                        Some(environment_line),
+                        "environment \"Realistic\" is deprecated, use \"RealLife\"",
+                    ));
+                    Some(EnvironmentType::RealLife)
+                }
+                other => match EnvironmentType::try_from(other) {
+                    Ok(environment) => Some(environment),
+                    Err(e) => {
+                        diagnostics.push(Diagnostic::error(path, Some(environment_line), e.to_string()));
+                        None
+                    }
+                },
+            }
+        }
+        (None, Some(procedural)) => Some(procedural.get_ref().theme),
+        (None, None) => {
+            diagnostics.push(Diagnostic::error(path, Some(id_line), "region has no environment"));
+            None
+        }
+    };
+    if file.name.is_none() && file.procedural.is_none() {
+        diagnostics.push(Diagnostic::error(path, Some(id_line), "region has no name"));
+    }
+
+    let mut portals = Vec::new();
+    let mut portal_lines = Vec::new();
+    for portal in file.portals {
+        let name_line = line(portal.name.span());
+        let name = portal.name.into_inner();
+        if let Some(from_region) = &portal.from_region {
+            let from_line = Some(line(from_region.span()));
+            if *from_region.get_ref() != region_id {
+                diagnostics.push(Diagnostic::error(
+                    path,
+                    from_line,
+                    format!(
+                        "portal '{}' says it is from '{}' but is listed under region '{}'",
+                        name,
+                        from_region.get_ref(),
+                        region_id
+                    ),
+                ));
+            } else {
+                diagnostics.push(Diagnostic::warning(
+                    path,
+                    from_line,
+                    format!(
+                        "portal '{}' sets from_region, which is deprecated; portals belong to the region they're listed under",
+                        name
+                    ),
+                ));
+            }
+        }
+        let (id, id_line) = match portal.id {
+            Some(id) => (id.get_ref().clone(), line(id.span())),
+            None => {
+                let id = format!("portal_{}_to_{}", region_id, portal.leads_to.get_ref());
+                diagnostics.push(Diagnostic::warning(
+                    path,
+                    Some(name_line),
+                    format!("portal '{}' has no id and was given '{}'; add it to the file", name, id),
+                ));
+                (id, name_line)
+            }
+        };
+        if *portal.required_level.get_ref() < 0 {
+            diagnostics.push(Diagnostic::error(
+                path,
+                Some(line(portal.required_level.span())),
+                format!("portal '{}' has a negative required_level", id),
+            ));
+        }
+
+        let required_item_line = portal.required_item.as_ref().map_or(id_line, |item| line(item.span()));
+        portal_lines.push(PortalLines { id: id_line, leads_to: line(portal.leads_to.span()), required_item: required_item_line });
+        portals.push(Portal {
+            id,
+            name,
+            leads_to: portal.leads_to.into_inner(),
+            required_level: portal.required_level.into_inner(),
+            required_item: portal.required_item.map(Spanned::into_inner),
+        });
+    }
+
+    let layout = file.layout.map(|layout| {
+        let layout_line = line(layout.span());
+        let layout = layout.into_inner();
+        if file.procedural.is_some() {
+            diagnostics.push(Diagnostic::error(
+                path,
+                Some(layout_line),
+                "procedural regions generate their layout; set `rooms` and `layout` in [procedural] instead",
+            ));
+        }
+        for problem in layout.problems(&portals) {
+            diagnostics.push(Diagnostic::error(path, Some(layout_line), problem));
+        }
+        layout
+    });
+    if let Some(procedural) = &file.procedural {
+        for problem in procedural.get_ref().problems() {
+            diagnostics.push(Diagnostic::error(path, Some(line(procedural.span())), problem));
+        }
+    }
+
+    let errors_after = diagnostics.iter().filter(|d| d.is_error()).count();
+    let Some(environment) = environment.filter(|_| errors_after == errors_before) else {
+        return Ok(None);
+    };
+
+    // The nexus writes an empty anchor since it is the anchor
+    let anchor = file.anchor_point.filter(|anchor| !anchor.get_ref().is_empty());
+    let anchor_line = anchor.as_ref().map(|anchor| line(anchor.span()));
+    let anchor_point = anchor.map(Spanned::into_inner);
+
+    if let Some(procedural) = file.procedural {
+        return Ok(Some(RegionFileContents::Stub(StubSource {
+            path: path.to_path_buf(),
+            id_line,
+            anchor_line,
+            portal_lines,
+            procedural_line: line(procedural.span()),
+            stub: RegionStub {
+                id: region_id,
+                name: file.name,
+                description: file.description,
+                portals,
+                anchor_point,
+                spec: procedural.into_inner(),
+            },
+        })));
+    }
+
+    Ok(Some(RegionFileContents::Region(RegionSource {
+        path: path.to_path_buf(),
+        id_line,
+        anchor_line,
+        portal_lines,
+        region: Region {
+            id: region_id,
+            name: file.name.unwrap_or_default(),
+            description: file.description.unwrap_or_default(),
+            environment,
+            portals,
+            anchor_point,
+            generated: file.generated,
+            layout,
+        },
+    })))
+}
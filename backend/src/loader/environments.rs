@@ -0,0 +1,80 @@
+use crate::loader::diagnostics::{into_warnings, line_at, toml_error, Diagnostic};
+use crate::models::EnvironmentType;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use toml::Spanned;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentsFile {
+    #[serde(default)]
+    environments: Vec<EnvironmentEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentEntry {
+    environment: Spanned<String>,
+    required_item: Spanned<String>,
+}
+
+/// The item a kind of world asks travellers to carry, along with where it was set
+#[derive(Debug, Clone)]
+pub struct EnvironmentSource {
+    pub path: PathBuf,
+    pub environment: EnvironmentType,
+    /// Content ID of the item
+    pub required_item: String,
+    pub environment_line: usize,
+    pub required_item_line: usize,
+}
+
+/// The item each environment asks for, by environment
+pub fn load_environment_items(path: &Path) -> Result<HashMap<EnvironmentType, String>> {
+    let (environments, diagnostics) = read_environments(path)?;
+    into_warnings(diagnostics)?;
+    Ok(environments.into_iter().map(|source| (source.environment, source.required_item)).collect())
+}
+
+/// Read and check the environments file. Each environment may be listed once.
+pub fn read_environments(path: &Path) -> Result<(Vec<EnvironmentSource>, Vec<Diagnostic>)> {
+    let content = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut diagnostics = Vec::new();
+    let file: EnvironmentsFile = match toml::from_str(&content) {
+        Ok(file) => file,
+        Err(e) => return Ok((Vec::new(), vec![toml_error(path, &content, &e)])),
+    };
+
+    let mut environments: Vec<EnvironmentSource> = Vec::new();
+    for entry in file.environments {
+        let environment_line = line_at(&content, entry.environment.span().start);
+        let required_item_line = line_at(&content, entry.required_item.span().start);
+        let environment = match EnvironmentType::try_from(entry.environment.into_inner()) {
+            Ok(environment) => environment,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(path, Some(environment_line), e.to_string()));
+                continue;
+            }
+        };
+        if let Some(first) = environments.iter().find(|e| e.environment == environment) {
+            diagnostics.push(Diagnostic::error(
+                path,
+                Some(environment_line),
+                format!("environment '{}' is already listed on line {}", environment, first.environment_line),
+            ));
+            continue;
+        }
+        environments.push(EnvironmentSource {
+            path: path.to_path_buf(),
+            environment,
+            required_item: entry.required_item.into_inner(),
+            environment_line,
+            required_item_line,
+        });
+    }
+
+    Ok((environments, diagnostics))
+}
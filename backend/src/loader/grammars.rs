@@ -0,0 +1,149 @@
+use crate::loader::diagnostics::{into_warnings, line_at, toml_error, Diagnostic};
+use crate::models::EnvironmentType;
+use crate::procedural::grammar::Grammar;
+use crate::procedural::text::{TextGenerator, REQUIRED_RULES, VARIABLES};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use toml::Spanned;
+
+/// The grammars shipped with the game, used for any environment the content doesn't override
+const BUILTIN_GRAMMARS: &[(&str, &str)] = &[
+    ("fantasy.toml", include_str!("../../content/grammars/fantasy.toml")),
+    ("technology.toml", include_str!("../../content/grammars/technology.toml")),
+    ("real_life.toml", include_str!("../../content/grammars/real_life.toml")),
+    ("hybrid.toml", include_str!("../../content/grammars/hybrid.toml")),
+];
+
+/// A grammar file: the environment it themes and its rules
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GrammarFile {
+    environment: Spanned<String>,
+    rules: HashMap<String, Spanned<Vec<String>>>,
+}
+
+/// A grammar along with the file it came from
+#[derive(Debug, Clone)]
+pub struct GrammarSource {
+    pub path: PathBuf,
+    pub environment: EnvironmentType,
+    pub grammar: Grammar,
+    pub environment_line: usize,
+}
+
+/// The built-in text generator with any grammars in `dir` replacing the built-in ones for their
+/// environment
+pub fn load_text_generator(dir_path: &str) -> Result<TextGenerator> {
+    let mut text = TextGenerator::builtin();
+    let (grammars, diagnostics) = read_grammars(Path::new(dir_path))?;
+    into_warnings(diagnostics)?;
+    for source in grammars {
+        text.set_grammar(source.environment, source.grammar);
+    }
+    Ok(text)
+}
+
+/// Read and check every grammar file in a directory. At most one file may theme each environment.
+pub fn read_grammars(dir: &Path) -> Result<(Vec<GrammarSource>, Vec<Diagnostic>)> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "toml") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut grammars: Vec<GrammarSource> = Vec::new();
+    let mut diagnostics = Vec::new();
+    for path in paths {
+        let Some(source) = read_grammar_file(&path, &mut diagnostics)? else {
+            continue;
+        };
+        if let Some(first) = grammars.iter().find(|g| g.environment == source.environment) {
+            diagnostics.push(Diagnostic::error(
+                &source.path,
+                Some(source.environment_line),
+                format!(
+                    "environment '{}' already has a grammar at {}:{}",
+                    source.environment,
+                    first.path.display(),
+                    first.environment_line
+                ),
+            ));
+            continue;
+        }
+        grammars.push(source);
+    }
+
+    Ok((grammars, diagnostics))
+}
+
+/// The grammars shipped with the game, checked like grammar files. Diagnostics name them
+/// `builtin/<file>`; a grammar with errors is left out.
+pub fn read_builtin_grammars() -> (Vec<GrammarSource>, Vec<Diagnostic>) {
+    let mut grammars = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (name, content) in BUILTIN_GRAMMARS {
+        let path = Path::new("builtin").join(name);
+        if let Some(source) = parse_grammar(&path, content, &mut diagnostics) {
+            grammars.push(source);
+        }
+    }
+    (grammars, diagnostics)
+}
+
+/// Parse one grammar file. Returns `None` when it has errors, which are added to `diagnostics`.
+pub fn read_grammar_file(path: &Path, diagnostics: &mut Vec<Diagnostic>) -> Result<Option<GrammarSource>> {
+    let content = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    Ok(parse_grammar(path, &content, diagnostics))
+}
+
+fn parse_grammar(path: &Path, content: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<GrammarSource> {
+    let file: GrammarFile = match toml::from_str(content) {
+        Ok(file) => file,
+        Err(e) => {
+            diagnostics.push(toml_error(path, content, &e));
+            return None;
+        }
+    };
+
+    let environment_line = line_at(content, file.environment.span().start);
+    let environment = match EnvironmentType::try_from(file.environment.into_inner()) {
+        Ok(environment) => Some(environment),
+        Err(e) => {
+            diagnostics.push(Diagnostic::error(path, Some(environment_line), e.to_string()));
+            None
+        }
+    };
+
+    let rule_lines: HashMap<&str, usize> = file
+        .rules
+        .iter()
+        .map(|(name, alternatives)| (name.as_str(), line_at(content, alternatives.span().start)))
+        .collect();
+    let grammar = Grammar::new(
+        file.rules
+            .iter()
+            .map(|(name, alternatives)| (name.clone(), alternatives.get_ref().clone()))
+            .collect(),
+    );
+    let errors = grammar.check(REQUIRED_RULES, VARIABLES);
+    for error in &errors {
+        let line = error.rule().and_then(|rule| rule_lines.get(rule).copied());
+        diagnostics.push(Diagnostic::error(path, line, error.to_string()));
+    }
+
+    match environment {
+        Some(environment) if errors.is_empty() => Some(GrammarSource {
+            path: path.to_path_buf(),
+            environment,
+            grammar,
+            environment_line,
+        }),
+        _ => None,
+    }
+}
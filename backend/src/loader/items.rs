@@ -0,0 +1,318 @@
+use crate::loader::diagnostics::{into_warnings, line_at, toml_error, Diagnostic};
+use crate::models::{ItemDefinition, ItemEffect, ItemType};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use toml::Spanned;
+
+/// Load every item definition under `dir_path`. Files may be TOML, JSON or YAML, one item per
+/// file; anything else is ignored. Content IDs must be unique across all files.
+pub fn load_items_from_dir(dir_path: &str) -> Result<Vec<ItemDefinition>> {
+    let (items, diagnostics) = read_items(Path::new(dir_path))?;
+    into_warnings(diagnostics)?;
+    Ok(items.into_iter().map(|source| source.item).collect())
+}
+
+/// An item definition, the file it came from and where its fields are in it
+#[derive(Debug, Clone)]
+pub struct ItemSource {
+    pub path: PathBuf,
+    pub item: ItemDefinition,
+    pub lines: ItemLines,
+}
+
+/// The lines an item's top-level fields and effects are written on
+#[derive(Debug, Clone, Default)]
+pub struct ItemLines {
+    fields: HashMap<String, usize>,
+    /// Where each effect starts, in order
+    effects: Vec<usize>,
+}
+
+impl ItemLines {
+    pub fn field(&self, key: &str) -> Option<usize> {
+        self.fields.get(key).copied()
+    }
+
+    pub fn effect(&self, index: usize) -> Option<usize> {
+        self.effects.get(index).copied()
+    }
+
+    /// The line a problem with `field` is on
+    fn locate(&self, field: ItemField) -> Option<usize> {
+        match field {
+            ItemField::Key(key) => self.field(key),
+            ItemField::Effect(index) => self.effect(index).or_else(|| self.field("effects")),
+        }
+    }
+}
+
+/// The part of an item a problem is with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemField {
+    Key(&'static str),
+    Effect(usize),
+}
+
+/// Read and check every item file in a directory, collecting problems instead of stopping at
+/// the first. Invalid files are left out of the result.
+pub fn read_items(dir: &Path) -> Result<(Vec<ItemSource>, Vec<Diagnostic>)> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file() && item_format(&path).is_some() {
+            paths.push(path);
+        }
+    }
+    // Sort so errors and sync order don't depend on directory iteration order
+    paths.sort();
+
+    let mut items: Vec<ItemSource> = Vec::new();
+    let mut diagnostics = Vec::new();
+    for path in paths {
+        let Some((item, lines)) = read_item_file(&path, &mut diagnostics)? else {
+            continue;
+        };
+        if let Some(first) = items.iter().find(|other| other.item.id == item.id) {
+            let at = match first.lines.field("id") {
+                Some(line) => format!("{}:{}", first.path.display(), line),
+                None => first.path.display().to_string(),
+            };
+            diagnostics.push(Diagnostic::error(
+                &path,
+                lines.field("id"),
+                format!("item id '{}' is already defined at {}", item.id, at),
+            ));
+            continue;
+        }
+        items.push(ItemSource { path, item, lines });
+    }
+
+    Ok((items, diagnostics))
+}
+
+#[derive(Clone, Copy)]
+enum ItemFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+fn item_format(path: &Path) -> Option<ItemFormat> {
+    match path.extension()?.to_str()? {
+        "toml" => Some(ItemFormat::Toml),
+        "json" => Some(ItemFormat::Json),
+        "yaml" | "yml" => Some(ItemFormat::Yaml),
+        _ => None,
+    }
+}
+
+/// An item as the file at `path` would hold it, in the format its extension calls for
+pub fn item_file_contents(path: &Path, item: &ItemDefinition) -> Result<String> {
+    let contents = match item_format(path) {
+        Some(ItemFormat::Toml) => toml::to_string_pretty(item)?,
+        Some(ItemFormat::Json) => serde_json::to_string_pretty(item)? + "\n",
+        Some(ItemFormat::Yaml) => serde_yaml::to_string(item)?,
+        None => anyhow::bail!("{} is not a TOML, JSON or YAML file", path.display()),
+    };
+    Ok(contents)
+}
+
+/// Parse and check a single item file. Returns `None` when it has errors, which are added to
+/// `diagnostics`.
+pub fn read_item_file(
+    path: &Path,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Option<(ItemDefinition, ItemLines)>> {
+    let content = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let parsed = match item_format(path) {
+        Some(ItemFormat::Toml) => toml::from_str(&content).map_err(|e| toml_error(path, &content, &e)),
+        Some(ItemFormat::Json) => serde_json::from_str(&content)
+            .map_err(|e| Diagnostic::error(path, Some(e.line()), e.to_string())),
+        Some(ItemFormat::Yaml) => serde_yaml::from_str(&content)
+            .map_err(|e| Diagnostic::error(path, e.location().map(|l| l.line()), e.to_string())),
+        None => Err(Diagnostic::error(path, None, "not a TOML, JSON or YAML file")),
+    };
+    let item: ItemDefinition = match parsed {
+        Ok(item) => item,
+        Err(diagnostic) => {
+            diagnostics.push(diagnostic);
+            return Ok(None);
+        }
+    };
+
+    let lines = match item_format(path) {
+        Some(ItemFormat::Toml) => toml_lines(&content),
+        Some(ItemFormat::Json) => json_lines(&content),
+        Some(ItemFormat::Yaml) | None => yaml_lines(&content),
+    };
+
+    let problems = item_problems(&item);
+    if !problems.is_empty() {
+        diagnostics.extend(
+            problems.into_iter().map(|(field, problem)| Diagnostic::error(path, lines.locate(field), problem)),
+        );
+        return Ok(None);
+    }
+    Ok(Some((item, lines)))
+}
+
+/// Everything wrong with a definition that the type system doesn't already rule out
+pub fn validate_item(item: &ItemDefinition) -> Vec<String> {
+    item_problems(item).into_iter().map(|(_, problem)| problem).collect()
+}
+
+/// `validate_item`, with the part of the item each problem is with
+pub fn item_problems(item: &ItemDefinition) -> Vec<(ItemField, String)> {
+    let mut problems = Vec::new();
+    let mut problem = |field, message: String| problems.push((field, message));
+
+    let valid_id = !item.id.is_empty()
+        && item.id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_id {
+        problem(
+            ItemField::Key("id"),
+            format!("id '{}' must be non-empty lowercase letters, digits and underscores", item.id),
+        );
+    }
+    if item.name.trim().is_empty() {
+        problem(ItemField::Key("name"), "name must not be empty".to_string());
+    }
+    if item.value < 0 {
+        problem(ItemField::Key("value"), format!("value must not be negative, got {}", item.value));
+    }
+    if item.power < 0 {
+        problem(ItemField::Key("power"), format!("power must not be negative, got {}", item.power));
+    }
+    if let Some(durability) = item.durability {
+        if durability <= 0 {
+            problem(
+                ItemField::Key("durability"),
+                format!("durability must be positive when set, got {}", durability),
+            );
+        }
+    }
+    if item.item_type == ItemType::Consumable && item.effects.is_empty() {
+        problem(ItemField::Key("item_type"), "consumables need at least one effect".to_string());
+    }
+
+    for (index, effect) in item.effects.iter().enumerate() {
+        let field = ItemField::Effect(index);
+        match effect {
+            ItemEffect::Heal { amount } if *amount <= 0 => {
+                problem(field, format!("heal amount must be positive, got {}", amount));
+            }
+            ItemEffect::GrantSkill { skill } if skill.trim().is_empty() => {
+                problem(field, "grant_skill needs a skill name".to_string());
+            }
+            ItemEffect::UnlockPortal { portal_id } if portal_id.trim().is_empty() => {
+                problem(field, "unlock_portal needs a portal_id".to_string());
+            }
+            ItemEffect::SummonMinion { minion_type, health, .. } => {
+                if minion_type.trim().is_empty() {
+                    problem(field, "summon_minion needs a minion_type".to_string());
+                }
+                if *health <= 0 {
+                    problem(field, format!("summoned minion health must be positive, got {}", health));
+                }
+            }
+            ItemEffect::ApplyEffect { magnitude, seconds, .. } => {
+                if *magnitude <= 0 {
+                    problem(field, format!("apply_effect magnitude must be positive, got {}", magnitude));
+                }
+                if *seconds == 0 {
+                    problem(field, "apply_effect needs a duration of at least one second".to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    problems
+}
+
+/// Just the effects of a TOML item file, with where each one is written
+#[derive(Deserialize)]
+struct TomlEffects {
+    #[serde(default)]
+    effects: Vec<Spanned<toml::Value>>,
+}
+
+fn toml_lines(content: &str) -> ItemLines {
+    let mut lines = ItemLines::default();
+    if let Ok(fields) = toml::from_str::<BTreeMap<String, Spanned<toml::Value>>>(content) {
+        for (key, value) in fields {
+            lines.fields.insert(key, line_at(content, value.span().start));
+        }
+    }
+    if let Ok(file) = toml::from_str::<TomlEffects>(content) {
+        lines.effects = file.effects.iter().map(|effect| line_at(content, effect.span().start)).collect();
+    }
+    lines
+}
+
+/// JSON parsers don't keep positions, so keys are found in the text: top-level ones at the
+/// first level of braces, effects by their `"effect"` keys
+fn json_lines(content: &str) -> ItemLines {
+    let mut lines = ItemLines::default();
+    let mut depth = 0usize;
+    let mut line = 1;
+    let mut chars = content.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth = depth.saturating_sub(1),
+            '"' => {
+                let mut end = start + 1;
+                let mut escaped = false;
+                for (at, c) in chars.by_ref() {
+                    end = at;
+                    match c {
+                        '\n' => line += 1,
+                        '\\' if !escaped => {
+                            escaped = true;
+                            continue;
+                        }
+                        '"' if !escaped => break,
+                        _ => {}
+                    }
+                    escaped = false;
+                }
+                let is_key = content[end + 1..].trim_start().starts_with(':');
+                let key = &content[start + 1..end];
+                if is_key && depth == 1 {
+                    lines.fields.entry(key.to_string()).or_insert(line);
+                } else if is_key && key == "effect" {
+                    lines.effects.push(line);
+                }
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+/// YAML positions are only reported for parse errors, so keys are found in the text:
+/// unindented ones are top-level, and each `effect:` starts an effect
+fn yaml_lines(content: &str) -> ItemLines {
+    let mut lines = ItemLines::default();
+    for (index, text) in content.lines().enumerate() {
+        if !text.starts_with([' ', '\t', '-', '#']) {
+            if let Some(key) = yaml_key(text) {
+                lines.fields.entry(key.to_string()).or_insert(index + 1);
+            }
+        }
+        let entry = text.trim_start().trim_start_matches("- ").trim_start_matches(['{', ' ']);
+        if yaml_key(entry) == Some("effect") {
+            lines.effects.push(index + 1);
+        }
+    }
+    lines
+}
+
+fn yaml_key(text: &str) -> Option<&str> {
+    text.split_once(':').map(|(key, _)| key.trim().trim_matches(|c| c == '"' || c == '\''))
+}
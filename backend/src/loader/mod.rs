@@ -1,2 +1,7 @@
 pub mod artifacts;
+pub mod check;
+pub mod diagnostics;
 pub mod dungeons; // placeholder for now
+pub mod environments;
+pub mod grammars;
+pub mod items;
@@ -1,21 +1,30 @@
-mod api;
-mod db;
-mod engine;
-mod loader;
-mod models;
-
-use axum::{Router, Extension};
-use axum::routing::{get};
-use db::{init_db, check_db_health, seed_data};
-use api::auth::login;
+use rpg_framework::{api, db, engine, loader, models};
+
+use axum::{middleware, Router, Extension};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use db::{init_db, check_db_health};
+use engine::combat::CombatSessions;
+use engine::auth::AuthConfig;
+use engine::oidc::{OidcConfig, OidcVerifier};
+use engine::content::{spawn_content_watcher, LiveContent};
+use engine::game_sessions::GameSessions;
+use engine::presence::{LocalBackend, PostgresBackend, Presence, PresenceBackend};
+use api::auth::{authenticate, login, logout, refresh, register, require_role};
 use api::player::{get_player, get_players};
-use api::game::{start_combat, complete_quest_route};
-use api::inventory::{add_item, remove_item}; // Add this line
-use models::item::describe_item; // Adjust the path depending on where describe_item is located
+use api::game::{start_combat, advance_combat};
+use api::inventory::{get_inventory, add_item, remove_item, use_item}; // Add this line
+use api::skills::{list_player_skills, learn_player_skill};
+use api::equipment::{get_player_equipment, equip, unequip, remove_curse};
+use api::map::{get_route, get_reachable, get_orphans, get_layout, get_region_players};
+use api::travel::{get_player_location, travel_through_portal, get_room, move_room};
+use api::command::run_command;
+use api::ws::game_socket;
+use models::Role;
 
 use dotenvy::dotenv;
-use sqlx::{PgPool, migrate::Migrator};
-use std::{env, net::SocketAddr, sync::Arc};
+use sqlx::PgPool;
+use std::{env, net::SocketAddr, sync::Arc, time::Duration};
 
 
 #[tokio::main]
@@ -25,57 +34,182 @@ async fn main() {
     // Initialize database connection pool
     let db = init_db().await;
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let pool = PgPool::connect(&database_url)
-        .await
-        .expect("Failed to connect to the database");
-
-    // Apply migrations automatically on startup
-    let migrator = Migrator::new(std::path::Path::new("migrations")).await.unwrap();
-    migrator.run(&pool).await.unwrap();
+    // `seed [--dry-run]` brings the seed data up to date and exits instead of serving
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("seed") {
+        let dry_run = args.iter().any(|arg| arg == "--dry-run");
+        match db::run_seeds(&db, dry_run).await {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                eprintln!("❌ Seeding failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    println!("Migrations applied successfully");
+    // `regions import DIR` stores the region files in DIR; `regions export DIR` writes every
+    // stored region to DIR as region files
+    if args.first().map(String::as_str) == Some("regions") {
+        let result = match &args[1..] {
+            [command, dir] if command == "import" => import_regions(&db, dir).await,
+            [command, dir] if command == "export" => export_regions(&db, dir).await,
+            _ => Err(anyhow::anyhow!("usage: regions import DIR | regions export DIR")),
+        };
+        if let Err(e) = result {
+            eprintln!("❌ {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    // Continue with the rest of your app initialization...
+    // Debug builds bring the seed data up to date on every start
     if cfg!(debug_assertions) {
-    if let Err(e) = db::seed::seed_items(&db).await {
-        eprintln!("⚠️ Failed to seed items: {}", e);
+        match db::run_seeds(&db, false).await {
+            Ok(report) => print!("{}", report),
+            Err(e) => eprintln!("⚠️ Failed to seed data: {}", e),
+        }
     }
-}
+
+    // Load content, sync the item catalog and keep both up to date as the files change
+    let content = match LiveContent::start("content", &db).await {
+        Ok(content) => Arc::new(content),
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+    for broken in content.map().validate_links() {
+        eprintln!("⚠️ {}", broken);
+    }
+    // Seconds between checks for changed content; 0 turns the watcher off
+    let reload_secs: u64 = env::var("CONTENT_RELOAD_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(2);
+    if reload_secs > 0 {
+        spawn_content_watcher(content.clone(), db.clone(), Duration::from_secs(reload_secs));
+    }
+
+    // Access and refresh tokens are signed with JWT_SECRET. Debug builds make one up if it's
+    // missing, so tokens stop working when the server restarts.
+    let secret = match env::var("JWT_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+        _ if cfg!(debug_assertions) => {
+            eprintln!("⚠️ JWT_SECRET is not set; using a random secret");
+            rand::random::<[u8; 32]>().to_vec()
+        }
+        _ => {
+            eprintln!("❌ JWT_SECRET must be set");
+            std::process::exit(1);
+        }
+    };
+    let access_secs: u64 = env::var("ACCESS_TOKEN_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(15 * 60);
+    let refresh_secs: u64 = env::var("REFRESH_TOKEN_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(30 * 24 * 3600);
+    let mut auth = AuthConfig::new(&secret, Duration::from_secs(access_secs), Duration::from_secs(refresh_secs));
+    // Access tokens from Keycloak are accepted as well once OIDC_ISSUER is set
+    match OidcConfig::from_env() {
+        Ok(Some(config)) => {
+            let oidc = OidcVerifier::start(config).await;
+            println!("🔑 Accepting tokens issued by {}", oidc.issuer());
+            auth = auth.with_oidc(oidc);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("❌ {:#}", e);
+            std::process::exit(1);
+        }
+    }
+    let auth = Arc::new(auth);
+
+    // Who is where and what they do there reaches the other replicas through Postgres
+    // LISTEN/NOTIFY with PRESENCE_BACKEND=postgres; by default it stays in this process
+    let backend: Arc<dyn PresenceBackend> = match env::var("PRESENCE_BACKEND").as_deref() {
+        Ok("postgres") => match PostgresBackend::start(db.as_ref().clone()).await {
+            Ok(backend) => Arc::new(backend),
+            Err(e) => {
+                eprintln!("❌ Failed to listen for region messages: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Ok("local") | Ok("") | Err(_) => Arc::new(LocalBackend::default()),
+        Ok(other) => {
+            eprintln!("❌ Unknown PRESENCE_BACKEND '{}'; use local or postgres", other);
+            std::process::exit(1);
+        }
+    };
+    let presence = Arc::new(Presence::new(backend));
+    presence.start_heartbeat();
+    let game_sessions = GameSessions::start(presence.clone());
 
     // Create Axum app with routes and shared database pool
     let app = Router::new()
         .route("/health", get(health_check))
-        .route("/auth/login", get(login))  // Add login route
-        .route("/player/:id", get(get_player))  // Get player by id route
-        .route("/players", get(get_players))  // Get multiple players route
-        .route("/combat/:player_id/:monster_health", get(start_combat))  // Combat route
-        .route("/quest/:player_id/:quest_id", get(complete_quest_route))  // Complete quest route
-        .route("/inventory/add/:player_id", get(add_item))  // Add item to inventory
-        .route("/inventory/remove/:player_id", get(remove_item))  // Remove item from inventory
-        .layer(Extension(Arc::new(db)));
+        .route("/auth/register", post(register))  // Create a player and log in
+        .route("/auth/login", post(login))  // Log in with a username and password
+        .route("/auth/refresh", post(refresh))  // Trade a refresh token for new tokens
+        .route("/auth/logout", post(logout))  // Revoke a refresh token
+        .route("/player", get(get_player))  // The logged-in player
+        .route("/players", get(get_players).route_layer(middleware::from_fn_with_state(Role::Admin, require_role)))  // Every player; admins only
+        .route("/player/skills", get(list_player_skills))  // Skills the player has learned
+        .route("/player/skills/:skill_id", post(learn_player_skill))  // Learn a skill
+        .route("/player/location", get(get_player_location))  // Where the player is and the ways out
+        .route("/player/travel", post(travel_through_portal))  // Take a portal
+        .route("/player/room", get(get_room))  // The room the player stands in
+        .route("/player/move", post(move_room))  // Walk to the next room
+        .route("/command", post(run_command))  // Run a line typed into the terminal
+        .route("/ws", get(game_socket))  // Game session over WebSocket: commands in, events out
+        .route("/combat", post(start_combat))  // Start a combat encounter against a wild monster
+        .route("/combat/encounter/:encounter_id", post(advance_combat))  // Play the next combat round
+        .route("/inventory", get(get_inventory))  // List the player's inventory
+        .route("/inventory/add", post(add_item).route_layer(middleware::from_fn_with_state(Role::GameMaster, require_role)))  // Give a player items; game masters only
+        .route("/inventory/remove", post(remove_item))  // Remove item from inventory
+        .route("/inventory/use", post(use_item))  // Use an item and run its effects
+        .route("/equipment", get(get_player_equipment))  // What the player is wearing
+        .route("/equipment/equip", post(equip))  // Equip an inventory item
+        .route("/equipment/unequip", post(unequip))  // Empty an equipment slot
+        .route("/equipment/lift-curse", post(remove_curse).route_layer(middleware::from_fn_with_state(Role::GameMaster, require_role)))  // Lift the curse on a player's equipped item; game masters only
+        .route("/map/route", get(get_route))  // Route between two regions at a player level
+        .route("/map/reachable", get(get_reachable))  // Regions reachable at a player level
+        .route("/map/orphans", get(get_orphans))  // Regions no portal leads into
+        .route("/map/regions/:region_id/layout", get(get_layout))  // Rooms inside a region
+        .route("/map/regions/:region_id/players", get(get_region_players))  // Players connected in a region
+        .nest("/admin", api::admin::router())  // Content, skills, classes and the audit log; admins only
+        .layer(middleware::from_fn(authenticate))
+        .layer(Extension(db))
+        .layer(Extension(CombatSessions::default()))
+        .layer(Extension(game_sessions))
+        .layer(Extension(presence))
+        .layer(Extension(content))
+        .layer(Extension(auth));
 
     // Launch the server
     let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
+    let listener = tokio::net::TcpListener::bind(addr).await.expect("Failed to bind the server address");
     println!("🚀 Server running at http://{}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
+
+async fn import_regions(db: &PgPool, dir: &str) -> anyhow::Result<()> {
+    let text = loader::grammars::load_text_generator("content/grammars")?;
+    let regions = loader::dungeons::load_regions_from_dir(dir, &text)?;
+    let report = db::regions::import_regions(db, &regions).await?;
+    println!(
+        "Imported regions from {}: {} new, {} updated, {} unchanged",
+        dir, report.inserted, report.updated, report.unchanged
+    );
+    Ok(())
 }
-let item = Item {
-    id: 1,
-    name: "Ancient Sword".to_string(),
-    description: "A sword from a forgotten era.".to_string(),
-    item_type: "Weapon".to_string(),
-    value: 100,
-    durability: Some(50),
-    is_magical: true,
-    is_cursed: true,
-};
-
-describe_item(&item);
+
+async fn export_regions(db: &PgPool, dir: &str) -> anyhow::Result<()> {
+    let regions = db::regions::load_regions(db).await?;
+    loader::dungeons::write_region_files(&regions, std::path::Path::new(dir))?;
+    println!("Exported {} region(s) to {}", regions.len(), dir);
+    Ok(())
+}
+
 // Health check with DB connectivity
-async fn health_check() -> impl axum::response::IntoResponse {
-    StatusCode::OK
+async fn health_check(Extension(db): Extension<db::DbPool>) -> impl axum::response::IntoResponse {
+    if check_db_health(&db).await {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
 }
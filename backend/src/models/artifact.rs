@@ -1,11 +1,20 @@
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A unique item defined in `content/artifacts`. Older files used UUIDs for `id`; those still
+/// load as plain strings, as do the older `artifact_id` and `affinity` keys.
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Artifact {
-    pub id: Uuid,
+    #[serde(alias = "artifact_id")]
+    pub id: String,
     pub name: String,
     pub description: String,
     pub power: i32,
+    #[serde(default = "default_rarity")]
+    pub rarity: String,
+    #[serde(alias = "affinity")]
     pub magic_affinity: Option<i32>,
 }
+
+fn default_rarity() -> String {
+    "common".to_string()
+}
@@ -1,12 +1,40 @@
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
 pub struct CharacterClass {
+    /// Assigned by the database; ignored when a class is created
+    #[serde(default = "Uuid::nil")]
     pub id: Uuid,
     pub name: String,
     pub description: String,
     pub base_health: i32,
     pub base_mana: i32,
-    pub starting_artifacts: Vec<Uuid>,
+    /// IDs of artifacts in `content/artifacts` a new character of this class starts with
+    #[serde(default)]
+    pub starting_artifacts: Vec<String>,
+}
+
+impl CharacterClass {
+    /// Everything wrong with the class on its own. Whether the starting artifacts exist is up
+    /// to whoever knows the content.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.name.trim().is_empty() {
+            problems.push("name must not be empty".to_string());
+        }
+        if self.base_health <= 0 {
+            problems.push(format!("base_health must be positive, got {}", self.base_health));
+        }
+        if self.base_mana < 0 {
+            problems.push(format!("base_mana must not be negative, got {}", self.base_mana));
+        }
+        for (i, artifact) in self.starting_artifacts.iter().enumerate() {
+            if self.starting_artifacts[..i].contains(artifact) {
+                problems.push(format!("starting artifact '{}' is listed twice", artifact));
+            }
+        }
+        problems
+    }
 }
@@ -0,0 +1,179 @@
+use sqlx::FromRow;
+use serde::{Serialize, Deserialize};
+use crate::models::{Item, ItemType, UnknownVariantError};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Armor,
+    Accessory,
+    MountAccessory,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 5] = [
+        EquipmentSlot::MainHand,
+        EquipmentSlot::OffHand,
+        EquipmentSlot::Armor,
+        EquipmentSlot::Accessory,
+        EquipmentSlot::MountAccessory,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EquipmentSlot::MainHand => "MainHand",
+            EquipmentSlot::OffHand => "OffHand",
+            EquipmentSlot::Armor => "Armor",
+            EquipmentSlot::Accessory => "Accessory",
+            EquipmentSlot::MountAccessory => "MountAccessory",
+        }
+    }
+
+    /// Whether an item of the given type may be worn in this slot
+    pub fn accepts(&self, item_type: ItemType) -> bool {
+        match self {
+            EquipmentSlot::MainHand => item_type == ItemType::Weapon,
+            EquipmentSlot::OffHand => matches!(item_type, ItemType::Weapon | ItemType::MagicItem),
+            EquipmentSlot::Armor => item_type == ItemType::Armor,
+            EquipmentSlot::Accessory => item_type == ItemType::Accessory,
+            EquipmentSlot::MountAccessory => item_type == ItemType::MountAccessory,
+        }
+    }
+
+    /// The slot an item goes into when the player doesn't pick one
+    pub fn default_for(item_type: ItemType) -> Option<EquipmentSlot> {
+        EquipmentSlot::ALL.into_iter().find(|slot| slot.accepts(item_type))
+    }
+}
+
+impl TryFrom<String> for EquipmentSlot {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        EquipmentSlot::ALL
+            .into_iter()
+            .find(|slot| slot.as_str() == value)
+            .ok_or(UnknownVariantError {
+                kind: "equipment slot",
+                value,
+                expected: &["MainHand", "OffHand", "Armor", "Accessory", "MountAccessory"],
+            })
+    }
+}
+
+/// An inventory row worn in a slot. `item.durability` and `item.is_cursed` reflect the
+/// player's own copy rather than the item template.
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct EquippedItem {
+    #[sqlx(try_from = "String")]
+    pub slot: EquipmentSlot,
+    pub inventory_id: i32,
+    #[sqlx(flatten)]
+    pub item: Item,
+}
+
+impl EquippedItem {
+    /// What the item adds to its wearer's stats. A curse turns its power against them.
+    pub fn power(&self) -> i32 {
+        if self.item.is_cursed {
+            -self.item.power
+        } else {
+            self.item.power
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Equipment {
+    pub items: Vec<EquippedItem>,
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipmentSlot) -> Option<&EquippedItem> {
+        self.items.iter().find(|e| e.slot == slot)
+    }
+
+    /// Main hand weapons add their full power to attack, off hand items half. Cursed items
+    /// take it away instead.
+    pub fn attack_bonus(&self) -> i32 {
+        self.items
+            .iter()
+            .map(|e| match e.slot {
+                EquipmentSlot::MainHand => e.power(),
+                EquipmentSlot::OffHand => e.power() / 2,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Armor and accessories add their power to defense, or take it away if they are cursed
+    pub fn defense_bonus(&self) -> i32 {
+        self.items
+            .iter()
+            .filter(|e| matches!(e.slot, EquipmentSlot::Armor | EquipmentSlot::Accessory))
+            .map(|e| e.power())
+            .sum()
+    }
+
+    pub fn equipped_items(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter().map(|e| &e.item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worn(slot: EquipmentSlot, item_type: ItemType, power: i32, is_cursed: bool) -> EquippedItem {
+        let item = Item {
+            id: 1,
+            name: "Gear".to_string(),
+            description: String::new(),
+            item_type,
+            value: 0,
+            power,
+            durability: None,
+            is_magical: false,
+            is_cursed,
+            effects: Vec::new(),
+        };
+        EquippedItem { slot, inventory_id: 1, item }
+    }
+
+    #[test]
+    fn slots_accept_their_item_types() {
+        assert!(EquipmentSlot::MainHand.accepts(ItemType::Weapon));
+        assert!(!EquipmentSlot::MainHand.accepts(ItemType::MagicItem));
+        assert!(EquipmentSlot::OffHand.accepts(ItemType::Weapon));
+        assert!(EquipmentSlot::OffHand.accepts(ItemType::MagicItem));
+        assert!(!EquipmentSlot::Armor.accepts(ItemType::Accessory));
+        assert!(EquipmentSlot::MountAccessory.accepts(ItemType::MountAccessory));
+        for item_type in [ItemType::Consumable, ItemType::TechKey, ItemType::Tool] {
+            assert!(EquipmentSlot::ALL.iter().all(|slot| !slot.accepts(item_type)));
+        }
+    }
+
+    #[test]
+    fn items_default_to_the_first_slot_that_takes_them() {
+        assert_eq!(EquipmentSlot::default_for(ItemType::Weapon), Some(EquipmentSlot::MainHand));
+        assert_eq!(EquipmentSlot::default_for(ItemType::MagicItem), Some(EquipmentSlot::OffHand));
+        assert_eq!(EquipmentSlot::default_for(ItemType::Consumable), None);
+        assert_eq!(EquipmentSlot::try_from("OffHand".to_string()), Ok(EquipmentSlot::OffHand));
+        assert!(EquipmentSlot::try_from("Head".to_string()).is_err());
+    }
+
+    #[test]
+    fn curses_turn_an_items_power_against_its_wearer() {
+        let equipment = Equipment {
+            items: vec![
+                worn(EquipmentSlot::MainHand, ItemType::Weapon, 8, false),
+                worn(EquipmentSlot::OffHand, ItemType::Weapon, 6, true),
+                worn(EquipmentSlot::Armor, ItemType::Armor, 5, false),
+                worn(EquipmentSlot::Accessory, ItemType::Accessory, 2, true),
+            ],
+        };
+        assert_eq!(equipment.attack_bonus(), 8 - 3);
+        assert_eq!(equipment.defense_bonus(), 5 - 2);
+    }
+}
@@ -0,0 +1,23 @@
+use std::fmt;
+
+/// Returned when a string read from the DB or a content file doesn't name a known enum variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownVariantError {
+    pub kind: &'static str,
+    pub value: String,
+    pub expected: &'static [&'static str],
+}
+
+impl fmt::Display for UnknownVariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown {} '{}' (expected one of: {})",
+            self.kind,
+            self.value,
+            self.expected.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownVariantError {}
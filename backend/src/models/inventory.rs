@@ -3,10 +3,13 @@ use serde::{Serialize, Deserialize};
 
 #[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
 pub struct Inventory {
+    pub id: i32,
     pub player_id: i32,
     pub item_id: i32,
     pub quantity: i32,
     pub durability: Option<i32>, // Durability for items like weapons and armor
+    pub is_cursed: bool, // Per-instance curse, may differ from the item template
+    pub stacked: bool, // One row holding every plain copy; otherwise a single copy with its own durability and curse
 }
 
 
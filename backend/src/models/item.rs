@@ -1,19 +1,159 @@
 use sqlx::FromRow;
 use serde::{Serialize, Deserialize};
-use crate::models::Item; // Assuming Item is in the models module
+use std::fmt;
+use crate::models::{EffectDuration, EffectKind, Player, StackingRule, StatusEffect, UnknownVariantError};
 
+/// Accuracy penalty every cursed item applies before its power is factored in
+const CURSE_BASE_MAGNITUDE: i32 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemType {
+    Weapon,
+    Armor,
+    Consumable,
+    Accessory,
+    MountAccessory,
+    TechKey,
+    MagicItem,
+    Tool,
+}
+
+impl ItemType {
+    pub const ALL: [ItemType; 8] = [
+        ItemType::Weapon,
+        ItemType::Armor,
+        ItemType::Consumable,
+        ItemType::Accessory,
+        ItemType::MountAccessory,
+        ItemType::TechKey,
+        ItemType::MagicItem,
+        ItemType::Tool,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemType::Weapon => "Weapon",
+            ItemType::Armor => "Armor",
+            ItemType::Consumable => "Consumable",
+            ItemType::Accessory => "Accessory",
+            ItemType::MountAccessory => "MountAccessory",
+            ItemType::TechKey => "TechKey",
+            ItemType::MagicItem => "MagicItem",
+            ItemType::Tool => "Tool",
+        }
+    }
+}
+
+impl fmt::Display for ItemType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TryFrom<String> for ItemType {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        ItemType::ALL
+            .into_iter()
+            .find(|t| t.as_str() == value)
+            .ok_or(UnknownVariantError {
+                kind: "item type",
+                value,
+                expected: &[
+                    "Weapon", "Armor", "Consumable", "Accessory", "MountAccessory", "TechKey",
+                    "MagicItem", "Tool",
+                ],
+            })
+    }
+}
+
+/// What happens when an item is used. Stored as a JSON list on the item.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "effect", rename_all = "snake_case")]
+pub enum ItemEffect {
+    Heal { amount: i32 },
+    GrantSkill { skill: String },
+    UnlockPortal { portal_id: String },
+    SummonMinion { minion_type: String, health: i32, power: i32 },
+    /// A status effect on the user that wears off after `seconds` of real time
+    ApplyEffect { kind: EffectKind, magnitude: i32, seconds: u32 },
+}
 
 #[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
 pub struct Item {
     pub id: i32,
     pub name: String,
     pub description: String,
-    pub item_type: String, // "Weapon", "Potion", etc.
+    #[sqlx(try_from = "String")]
+    pub item_type: ItemType,
     pub value: i32,
+    pub power: i32, // Attack bonus for weapons, defense bonus for armor
     pub durability: Option<i32>, // Nullable for durability (for items like weapons and armor)
     pub is_magical: bool, // Flag for magical items
     pub is_cursed: bool,  // Flag for cursed items
+    #[sqlx(json)]
+    #[serde(default)]
+    pub effects: Vec<ItemEffect>,
+}
+
+/// An item as written by content authors in `content/items`. `id` is the stable content ID the
+/// definition is synced to the `items` table by, so renaming an item doesn't duplicate it.
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ItemDefinition {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[sqlx(try_from = "String")]
+    pub item_type: ItemType,
+    #[serde(default)]
+    pub value: i32,
+    #[serde(default)]
+    pub power: i32,
+    pub durability: Option<i32>,
+    #[serde(default)]
+    pub is_magical: bool,
+    #[serde(default)]
+    pub is_cursed: bool,
+    #[sqlx(json)]
+    #[serde(default)]
+    pub effects: Vec<ItemEffect>,
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemUseError {
+    Broken(String),
+    MustEquip(String),
+    NoEffect(String),
+    UnknownSkill(String),
+}
+
+impl fmt::Display for ItemUseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemUseError::Broken(name) => write!(f, "The item {} is broken and can no longer be used.", name),
+            ItemUseError::MustEquip(name) => write!(f, "{} has to be equipped rather than used.", name),
+            ItemUseError::NoEffect(name) => write!(f, "Nothing happens when you use {}.", name),
+            ItemUseError::UnknownSkill(skill) => write!(f, "The item teaches {}, but no such skill exists.", skill),
+        }
+    }
+}
+
+impl std::error::Error for ItemUseError {}
+
+/// The result of using an item on a player
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ItemUse {
+    pub consumed: bool,
+    pub healed: i32,
+    /// Status effects placed on the player, saved along with their stats
+    pub applied: Vec<StatusEffect>,
+    /// Effects that reach beyond the player's stats and must be persisted by the caller
+    pub pending: Vec<ItemEffect>,
+}
+
 // Function to describe an item, including its properties
 pub fn describe_item(item: &Item) {
     println!("Item: {}", item.name);
@@ -34,35 +174,222 @@ pub fn describe_item(item: &Item) {
     }
 }
 impl Item {
-    pub fn use_item(&mut self, player: &mut Player) {
+    /// Whether copies are interchangeable and share one inventory stack. Items that wear down
+    /// or carry a curse are held one row per copy.
+    pub fn stacks(&self) -> bool {
+        self.durability.is_none() && !self.is_cursed
+    }
+
+    /// The persistent debuff a cursed item places on whoever has it equipped
+    pub fn curse_effect(&self) -> Option<StatusEffect> {
+        if !self.is_cursed {
+            return None;
+        }
+        let mut curse = StatusEffect::new(
+            &format!("curse_{}", self.id),
+            &format!("Curse of the {}", self.name),
+            EffectKind::AccuracyDown,
+            CURSE_BASE_MAGNITUDE + self.power / 10,
+            EffectDuration::Permanent,
+            &format!("item:{}", self.id),
+        );
+        curse.stacking = StackingRule::Ignore;
+        Some(curse)
+    }
+
+    /// Use the item on a player. Healing, status effects and durability are applied directly; granting
+    /// skills, unlocking portals and summoning minions are returned in `ItemUse::pending`.
+    pub fn use_item(&mut self, player: &mut Player) -> Result<ItemUse, ItemUseError> {
+        if self.durability.is_some_and(|d| d <= 0) {
+            return Err(ItemUseError::Broken(self.name.clone()));
+        }
+        if self.effects.is_empty() {
+            return match self.item_type {
+                ItemType::Weapon | ItemType::Armor | ItemType::Accessory | ItemType::MountAccessory => {
+                    Err(ItemUseError::MustEquip(self.name.clone()))
+                }
+                _ => Err(ItemUseError::NoEffect(self.name.clone())),
+            };
+        }
+
         if let Some(durability) = self.durability {
-            if durability <= 0 {
-                println!("The item {} is broken and can no longer be used.", self.name);
-                return;
-            } else {
-                self.durability = Some(durability - 1); // Decrease durability
-                println!("Using item: {}. Remaining durability: {}", self.name, self.durability.unwrap());
-            }
+            self.durability = Some(durability - 1); // Decrease durability
         }
 
-        if self.is_magical {
-            // Magical effect, can be expanded
-            println!("The item {} is magical! It grants special abilities!", self.name);
-            if self.item_type == "Potion" {
-                // Magical potion might heal more
-                player.heal(self.value * 2); // Example effect: heals double
+        let mut outcome = ItemUse {
+            consumed: self.item_type == ItemType::Consumable,
+            ..ItemUse::default()
+        };
+        for effect in &self.effects {
+            match effect {
+                ItemEffect::Heal { amount } => {
+                    // Magical items heal double
+                    let amount = if self.is_magical { amount * 2 } else { *amount };
+                    let before = player.health;
+                    player.heal(amount);
+                    outcome.healed += player.health - before;
+                }
+                ItemEffect::ApplyEffect { kind, magnitude, seconds } => {
+                    let effect = StatusEffect::new(
+                        &format!("item_effect_{}", self.id),
+                        &self.name,
+                        *kind,
+                        *magnitude,
+                        EffectDuration::Seconds(*seconds),
+                        &format!("used:{}", self.id),
+                    );
+                    player.status_effects.apply(effect.clone());
+                    outcome.applied.push(effect);
+                }
+                other => outcome.pending.push(other.clone()),
             }
         }
 
-        match self.item_type.as_str() {
-            "Potion" => {
-                println!("Using potion: {}", self.name);
-                player.heal(self.value);
-            },
-            "Weapon" => {
-                println!("Equipping weapon: {}", self.name);
-            },
-            _ => println!("Using item: {}", self.name),
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(item_type: ItemType, durability: Option<i32>, effects: Vec<ItemEffect>) -> Item {
+        Item {
+            id: 7,
+            name: "Trinket".to_string(),
+            description: String::new(),
+            item_type,
+            value: 0,
+            power: 0,
+            durability,
+            is_magical: false,
+            is_cursed: false,
+            effects,
+        }
+    }
+
+    fn player(health: i32) -> Player {
+        Player {
+            id: 1,
+            username: "tester".to_string(),
+            level: 1,
+            health,
+            max_health: 100,
+            mana: 20,
+            max_mana: 20,
+            experience: 0,
+            status_effects: Default::default(),
         }
     }
+
+    #[test]
+    fn item_types_parse_from_their_names_only() {
+        for item_type in ItemType::ALL {
+            assert_eq!(ItemType::try_from(item_type.as_str().to_string()), Ok(item_type));
+        }
+        let err = ItemType::try_from("Potion".to_string()).unwrap_err();
+        assert_eq!(err.value, "Potion");
+    }
+
+    #[test]
+    fn effects_read_from_content_files() {
+        let effects: Vec<ItemEffect> = toml::from_str::<ItemDefinition>(
+            r#"
+            id = "kit"
+            name = "Kit"
+            item_type = "Tool"
+
+            [[effects]]
+            effect = "heal"
+            amount = 5
+
+            [[effects]]
+            effect = "apply_effect"
+            kind = "AttackUp"
+            magnitude = 3
+            seconds = 600
+            "#,
+        )
+        .unwrap()
+        .effects;
+        assert_eq!(
+            effects,
+            vec![
+                ItemEffect::Heal { amount: 5 },
+                ItemEffect::ApplyEffect { kind: EffectKind::AttackUp, magnitude: 3, seconds: 600 },
+            ]
+        );
+        assert!(serde_json::from_str::<ItemEffect>(r#"{"effect":"teleport"}"#).is_err());
+    }
+
+    #[test]
+    fn consumables_heal_and_are_used_up() {
+        let mut potion = item(ItemType::Consumable, None, vec![ItemEffect::Heal { amount: 20 }]);
+        let mut hero = player(50);
+        let used = potion.use_item(&mut hero).unwrap();
+        assert!(used.consumed);
+        assert_eq!(used.healed, 20);
+        assert_eq!(hero.health, 70);
+
+        // Magical items heal double, but never past max health
+        potion.is_magical = true;
+        let used = potion.use_item(&mut hero).unwrap();
+        assert_eq!(used.healed, 30);
+        assert_eq!(hero.health, 100);
+    }
+
+    #[test]
+    fn applied_effects_land_on_the_player() {
+        let mut elixir = item(
+            ItemType::Consumable,
+            None,
+            vec![ItemEffect::ApplyEffect { kind: EffectKind::AttackUp, magnitude: 3, seconds: 600 }],
+        );
+        let mut hero = player(100);
+        let used = elixir.use_item(&mut hero).unwrap();
+        assert_eq!(used.applied.len(), 1);
+        assert_eq!(used.applied[0].duration, EffectDuration::Seconds(600));
+        assert_eq!(hero.status_effects.attack_modifier(), 3);
+    }
+
+    #[test]
+    fn effects_beyond_the_player_are_left_to_the_caller() {
+        let grant = ItemEffect::GrantSkill { skill: "fireball".to_string() };
+        let mut tome = item(ItemType::MagicItem, Some(2), vec![grant.clone()]);
+        let mut hero = player(100);
+        let used = tome.use_item(&mut hero).unwrap();
+        assert!(!used.consumed);
+        assert_eq!(used.pending, vec![grant]);
+        assert_eq!(tome.durability, Some(1));
+
+        tome.use_item(&mut hero).unwrap();
+        assert_eq!(tome.use_item(&mut hero).unwrap_err(), ItemUseError::Broken("Trinket".to_string()));
+        assert_eq!(tome.durability, Some(0));
+    }
+
+    #[test]
+    fn items_without_effects_refuse_to_be_used() {
+        let mut hero = player(100);
+        let mut sword = item(ItemType::Weapon, None, Vec::new());
+        assert_eq!(sword.use_item(&mut hero).unwrap_err(), ItemUseError::MustEquip("Trinket".to_string()));
+        let mut rope = item(ItemType::Tool, None, Vec::new());
+        assert_eq!(rope.use_item(&mut hero).unwrap_err(), ItemUseError::NoEffect("Trinket".to_string()));
+    }
+
+    #[test]
+    fn only_plain_items_stack_and_only_cursed_ones_carry_a_curse() {
+        let mut ring = item(ItemType::Accessory, None, Vec::new());
+        assert!(ring.stacks());
+        assert!(ring.curse_effect().is_none());
+
+        ring.is_cursed = true;
+        ring.power = 25;
+        assert!(!ring.stacks());
+        let curse = ring.curse_effect().unwrap();
+        assert_eq!(curse.kind, EffectKind::AccuracyDown);
+        assert_eq!(curse.magnitude, CURSE_BASE_MAGNITUDE + 2);
+        assert_eq!(curse.duration, EffectDuration::Permanent);
+
+        assert!(!item(ItemType::Weapon, Some(10), Vec::new()).stacks());
+    }
 }
@@ -0,0 +1,317 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use crate::models::{Portal, UnknownVariantError};
+
+pub const ROCK: char = '#';
+pub const ROOM: char = '.';
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::North, Direction::South, Direction::East, Direction::West];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::South => "south",
+            Direction::East => "east",
+            Direction::West => "west",
+        }
+    }
+
+    /// Column and row offset of one step; north is up, towards row 0
+    pub fn offset(&self) -> (isize, isize) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::South => (0, 1),
+            Direction::East => (1, 0),
+            Direction::West => (-1, 0),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TryFrom<String> for Direction {
+    type Error = UnknownVariantError;
+
+    /// Accepts the full name or its first letter, in any case, as typed in text mode
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let lower = value.to_lowercase();
+        Direction::ALL
+            .into_iter()
+            .find(|dir| dir.as_str() == lower || dir.as_str()[..1] == lower)
+            .ok_or(UnknownVariantError {
+                kind: "direction",
+                value,
+                expected: &["north", "south", "east", "west"],
+            })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+}
+
+/// Where a portal out of the region can be taken
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Entrance {
+    pub portal_id: String,
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Entrance {
+    pub fn position(&self) -> Position {
+        Position { x: self.x, y: self.y }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    NotARoom(Position),
+    Blocked(Direction),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::NotARoom(pos) => write!(f, "There is no room at ({}, {})", pos.x, pos.y),
+            MoveError::Blocked(dir) => write!(f, "You can't go {} from here", dir),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// The rooms inside a region, as a grid. Each `.` is a room and each `#` solid rock; players move
+/// between rooms that share an edge, and take a portal from the room its entrance is in, or from
+/// the start if it has no entrance.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RoomLayout {
+    /// Top row first
+    pub rows: Vec<String>,
+    /// Where players arrive when no entrance leads back the way they came
+    pub start: Position,
+    #[serde(default)]
+    pub entrances: Vec<Entrance>,
+}
+
+impl RoomLayout {
+    pub fn width(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.chars().count())
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_room(&self, pos: Position) -> bool {
+        self.rows.get(pos.y).and_then(|row| row.chars().nth(pos.x)) == Some(ROOM)
+    }
+
+    fn neighbour(&self, pos: Position, dir: Direction) -> Option<Position> {
+        let (dx, dy) = dir.offset();
+        let next = Position { x: pos.x.checked_add_signed(dx)?, y: pos.y.checked_add_signed(dy)? };
+        self.is_room(next).then_some(next)
+    }
+
+    /// Directions leading to another room
+    pub fn exits(&self, pos: Position) -> Vec<Direction> {
+        Direction::ALL.into_iter().filter(|&dir| self.neighbour(pos, dir).is_some()).collect()
+    }
+
+    /// Move one room over
+    pub fn step(&self, pos: Position, dir: Direction) -> Result<Position, MoveError> {
+        if !self.is_room(pos) {
+            return Err(MoveError::NotARoom(pos));
+        }
+        self.neighbour(pos, dir).ok_or(MoveError::Blocked(dir))
+    }
+
+    /// Portals that can be taken from this room
+    pub fn entrances_at(&self, pos: Position) -> impl Iterator<Item = &Entrance> {
+        self.entrances.iter().filter(move |e| e.position() == pos)
+    }
+
+    pub fn entrance_for(&self, portal_id: &str) -> Option<&Entrance> {
+        self.entrances.iter().find(|e| e.portal_id == portal_id)
+    }
+
+    /// The room a portal is taken from
+    pub fn portal_position(&self, portal_id: &str) -> Position {
+        self.entrance_for(portal_id).map_or(self.start, Entrance::position)
+    }
+
+    /// The region's portals that can be taken from this room
+    pub fn portals_at<'a>(&self, portals: &'a [Portal], pos: Position) -> Vec<&'a Portal> {
+        portals.iter().filter(|p| self.portal_position(&p.id) == pos).collect()
+    }
+
+    /// Where a player coming from `from_region` appears: at the entrance of the portal leading
+    /// back there if there is one, otherwise at the start
+    pub fn arrival(&self, portals: &[Portal], from_region: &str) -> Position {
+        portals
+            .iter()
+            .filter(|p| p.leads_to == from_region)
+            .find_map(|p| self.entrance_for(&p.id))
+            .map_or(self.start, Entrance::position)
+    }
+
+    /// Every room, top row first
+    pub fn rooms(&self) -> Vec<Position> {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.chars().enumerate().filter(|&(_, c)| c == ROOM).map(move |(x, _)| Position { x, y })
+            })
+            .collect()
+    }
+
+    /// Rooms that can be walked to from `from`
+    pub fn connected_rooms(&self, from: Position) -> Vec<Position> {
+        if !self.is_room(from) {
+            return Vec::new();
+        }
+        let mut seen = HashSet::from([from]);
+        let mut rooms = vec![from];
+        let mut queue = VecDeque::from([from]);
+        while let Some(pos) = queue.pop_front() {
+            for dir in Direction::ALL {
+                if let Some(next) = self.neighbour(pos, dir) {
+                    if seen.insert(next) {
+                        rooms.push(next);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        rooms
+    }
+
+    /// Everything wrong with the layout for a region with these portals
+    pub fn problems(&self, portals: &[Portal]) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.rows.is_empty() {
+            return vec!["layout has no rows".to_string()];
+        }
+        let width = self.width();
+        for (y, row) in self.rows.iter().enumerate() {
+            if row.chars().count() != width {
+                problems.push(format!("layout row {} is {} wide, expected {}", y, row.chars().count(), width));
+            }
+            if let Some(c) = row.chars().find(|&c| c != ROOM && c != ROCK) {
+                problems.push(format!("layout row {} has '{}', expected '{}' or '{}'", y, c, ROOM, ROCK));
+            }
+        }
+        if !self.is_room(self.start) {
+            problems.push(format!("layout start ({}, {}) is not a room", self.start.x, self.start.y));
+        }
+        for entrance in &self.entrances {
+            if !portals.iter().any(|p| p.id == entrance.portal_id) {
+                problems.push(format!("layout has an entrance for unknown portal '{}'", entrance.portal_id));
+            }
+            if !self.is_room(entrance.position()) {
+                problems.push(format!(
+                    "entrance for portal '{}' at ({}, {}) is not a room",
+                    entrance.portal_id, entrance.x, entrance.y
+                ));
+            }
+        }
+        let mut seen = HashSet::new();
+        for entrance in self.entrances.iter().filter(|e| !seen.insert(e.portal_id.as_str())) {
+            problems.push(format!("portal '{}' has more than one entrance", entrance.portal_id));
+        }
+        if problems.is_empty() {
+            let cut_off = self.rooms().len() - self.connected_rooms(self.start).len();
+            if cut_off > 0 {
+                problems.push(format!("{} rooms can't be reached from the start", cut_off));
+            }
+        }
+        problems
+    }
+
+    /// The grid with `@` at `at` and `*` on rooms with an entrance
+    pub fn render(&self, at: Option<Position>) -> Vec<String> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.chars()
+                    .enumerate()
+                    .map(|(x, c)| {
+                        let pos = Position { x, y };
+                        if Some(pos) == at {
+                            '@'
+                        } else if self.entrances_at(pos).next().is_some() {
+                            '*'
+                        } else {
+                            c
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> RoomLayout {
+        RoomLayout {
+            rows: vec!["#####".to_string(), "#..##".to_string(), "##..#".to_string(), "#####".to_string()],
+            start: Position { x: 1, y: 1 },
+            entrances: vec![Entrance { portal_id: "back".to_string(), x: 3, y: 2 }],
+        }
+    }
+
+    fn portal(id: &str, leads_to: &str) -> Portal {
+        Portal { id: id.to_string(), name: id.to_string(), leads_to: leads_to.to_string(), required_level: 0, required_item: None }
+    }
+
+    #[test]
+    fn steps_only_between_neighbouring_rooms() {
+        let layout = layout();
+        let start = layout.start;
+        assert_eq!(layout.step(start, Direction::East), Ok(Position { x: 2, y: 1 }));
+        assert_eq!(layout.step(start, Direction::South), Err(MoveError::Blocked(Direction::South)));
+        let rock = Position { x: 0, y: 0 };
+        assert_eq!(layout.step(rock, Direction::East), Err(MoveError::NotARoom(rock)));
+    }
+
+    #[test]
+    fn arrives_at_the_entrance_leading_back() {
+        let layout = layout();
+        let portals = [portal("back", "home"), portal("onward", "elsewhere")];
+        assert_eq!(layout.arrival(&portals, "home"), Position { x: 3, y: 2 });
+        assert_eq!(layout.arrival(&portals, "nowhere"), layout.start);
+        assert_eq!(layout.portal_position("onward"), layout.start);
+    }
+
+    #[test]
+    fn problems_include_rooms_cut_off_from_the_start() {
+        let mut layout = layout();
+        layout.rows[2] = "###.#".to_string();
+        layout.entrances.clear();
+        assert_eq!(layout.problems(&[]), vec!["1 rooms can't be reached from the start".to_string()]);
+    }
+}
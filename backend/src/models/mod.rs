@@ -1,18 +1,28 @@
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Artifact {
-    pub id: String,
-    pub name: String,
-    pub description: String,
-    pub power: u32,
-    pub rarity: String,
-}
-
-pub mod dungeon;
-pub use dungeon::*;
 pub mod player;
+pub use player::Player;
+pub mod item;
+pub use item::{Item, ItemDefinition, ItemEffect, ItemType, ItemUse, ItemUseError};
+pub mod inventory;
+pub use inventory::Inventory;
+pub mod equipment;
+pub use equipment::{Equipment, EquipmentSlot, EquippedItem};
+pub mod quest;
+pub use quest::Quest;
+pub mod skill;
+pub use skill::{PlayerSkill, Skill, SkillType, TargetType};
+pub mod status_effect;
+pub use status_effect::{EffectDuration, EffectKind, EffectTick, StackingRule, StatusEffect, StatusEffects};
+pub mod error;
+pub use error::UnknownVariantError;
 pub mod character_class;
+pub use character_class::CharacterClass;
 pub mod region;
+pub use region::{EnvironmentType, GenerationInfo, Region};
 pub mod portal;
+pub use portal::Portal;
+pub mod layout;
+pub use layout::{Direction, Entrance, MoveError, Position, RoomLayout};
 pub mod artifact;
+pub use artifact::Artifact;
+pub mod role;
+pub use role::Role;
@@ -1,5 +1,6 @@
 use sqlx::FromRow;
 use serde::{Serialize, Deserialize};
+use crate::models::{Item, StatusEffects};
 
 #[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
 pub struct Player {
@@ -8,14 +9,40 @@ pub struct Player {
     pub level: i32,
     pub health: i32,
     pub max_health: i32,
+    pub mana: i32,
+    pub max_mana: i32,
     pub experience: i32,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub status_effects: StatusEffects,
 }
 
 impl Player {
+    /// Experience needed to go from `level` to the next
+    pub fn experience_to_level(level: i32) -> i32 {
+        100 * level
+    }
+
+    /// Add experience, levelling up as often as it reaches the next level. Experience past a
+    /// level-up carries over. Returns the number of levels gained.
+    pub fn gain_experience(&mut self, amount: i32) -> i32 {
+        self.experience += amount.max(0);
+        let mut gained = 0;
+        while self.experience >= Self::experience_to_level(self.level) {
+            let carried = self.experience - Self::experience_to_level(self.level);
+            self.level_up();
+            self.experience = carried;
+            gained += 1;
+        }
+        gained
+    }
+
     pub fn level_up(&mut self) {
         self.level += 1;
         self.max_health += 10;
         self.health = self.max_health;
+        self.max_mana += 5;
+        self.mana = self.max_mana;
         self.experience = 0;
     }
 
@@ -27,7 +54,7 @@ impl Player {
     }
 
     pub fn heal(&mut self, amount: i32) {
-        self.health += amount;
+        self.health += self.status_effects.modify_healing(amount);
         if self.health > self.max_health {
             self.health = self.max_health;
         }
@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
 
+/// An exit from the region it is listed under
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Portal {
+    pub id: String,
     pub name: String,
-    pub from_region: String,
-    pub leads_to: String,
+    pub leads_to: String, // region_id
     pub required_level: i32,
+    /// Content ID of an item travelers have to carry, unless they've used an item that unlocks
+    /// the portal for good
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required_item: Option<String>,
 }
@@ -1,17 +1,80 @@
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::fmt;
+use crate::models::{Portal, RoomLayout, UnknownVariantError};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub enum Environment {
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EnvironmentType {
     Fantasy,
     Technology,
-    Realistic,
+    RealLife,
     Hybrid,
 }
 
+impl EnvironmentType {
+    pub const ALL: [EnvironmentType; 4] = [
+        EnvironmentType::Fantasy,
+        EnvironmentType::Technology,
+        EnvironmentType::RealLife,
+        EnvironmentType::Hybrid,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnvironmentType::Fantasy => "Fantasy",
+            EnvironmentType::Technology => "Technology",
+            EnvironmentType::RealLife => "RealLife",
+            EnvironmentType::Hybrid => "Hybrid",
+        }
+    }
+}
+
+impl fmt::Display for EnvironmentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TryFrom<String> for EnvironmentType {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        EnvironmentType::ALL
+            .into_iter()
+            .find(|env| env.as_str() == value)
+            .ok_or(UnknownVariantError {
+                kind: "environment",
+                value,
+                expected: &["Fantasy", "Technology", "RealLife", "Hybrid"],
+            })
+    }
+}
+
+/// A region of the world and the portals leading out of it
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Region {
     pub id: String,
     pub name: String,
-    pub environment: Environment,
     pub description: String,
+    pub environment: EnvironmentType,
+    #[serde(default)]
+    pub portals: Vec<Portal>,
+    pub anchor_point: Option<String>, // anchor ID in the nexus
+    /// Set on procedurally generated regions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<GenerationInfo>,
+    /// Rooms to explore inside the region; without one the region is a single place
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<RoomLayout>,
+}
+
+/// What a generated region was made with, enough to generate it again exactly
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GenerationInfo {
+    pub generator: String,
+    /// Stored as a string since TOML integers can't hold every u64
+    #[serde_as(as = "DisplayFromStr")]
+    pub seed: u64,
+    pub version: u32,
 }
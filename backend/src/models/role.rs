@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use crate::models::UnknownVariantError;
+
+/// What a player may do beyond playing. Each role includes the ones before it, so an admin can
+/// do everything a game master can.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Player,
+    GameMaster,
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Player, Role::GameMaster, Role::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::GameMaster => "game_master",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TryFrom<String> for Role {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == value)
+            .ok_or(UnknownVariantError { kind: "role", value, expected: &["player", "game_master", "admin"] })
+    }
+}
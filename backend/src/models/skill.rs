@@ -1 +1,148 @@
+use sqlx::FromRow;
+use serde::{Serialize, Deserialize};
+use crate::models::{EffectKind, UnknownVariantError};
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SkillType {
+    Magic,
+    Physical,
+    Support,
+    Buff,
+    Debuff,
+}
+
+impl SkillType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkillType::Magic => "Magic",
+            SkillType::Physical => "Physical",
+            SkillType::Support => "Support",
+            SkillType::Buff => "Buff",
+            SkillType::Debuff => "Debuff",
+        }
+    }
+}
+
+impl TryFrom<String> for SkillType {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "Magic" => Ok(SkillType::Magic),
+            "Physical" => Ok(SkillType::Physical),
+            "Support" => Ok(SkillType::Support),
+            "Buff" => Ok(SkillType::Buff),
+            "Debuff" => Ok(SkillType::Debuff),
+            _ => Err(UnknownVariantError {
+                kind: "skill type",
+                value,
+                expected: &["Magic", "Physical", "Support", "Buff", "Debuff"],
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TargetType {
+    Enemy,
+    Ally,
+    #[serde(rename = "Self")]
+    Caster,
+}
+
+impl TargetType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TargetType::Enemy => "Enemy",
+            TargetType::Ally => "Ally",
+            TargetType::Caster => "Self",
+        }
+    }
+}
+
+impl TryFrom<String> for TargetType {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "Enemy" => Ok(TargetType::Enemy),
+            "Ally" => Ok(TargetType::Ally),
+            "Self" => Ok(TargetType::Caster),
+            _ => Err(UnknownVariantError {
+                kind: "target type",
+                value,
+                expected: &["Enemy", "Ally", "Self"],
+            }),
+        }
+    }
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct Skill {
+    /// Assigned by the database; ignored when a skill is created
+    #[serde(default)]
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    #[sqlx(try_from = "String")]
+    pub skill_type: SkillType,
+    pub power: i32,
+    pub cooldown: i32, // in combat rounds
+    pub mana_cost: i32,
+    #[sqlx(try_from = "String")]
+    pub target_type: TargetType,
+    pub status_effect: Option<EffectKind>, // lingering effect applied to each target
+    pub effect_duration: Option<i32>,      // in turns
+    #[serde(default)]
+    pub effect_seconds: Option<i32>, // in real time, for effects that outlive the encounter
+}
+
+impl Skill {
+    /// Everything wrong with the skill that the type system doesn't already rule out
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.name.trim().is_empty() {
+            problems.push("name must not be empty".to_string());
+        }
+        for (field, value) in [("power", self.power), ("cooldown", self.cooldown), ("mana_cost", self.mana_cost)] {
+            if value < 0 {
+                problems.push(format!("{} must not be negative, got {}", field, value));
+            }
+        }
+        match (self.status_effect, self.effect_duration) {
+            (None, Some(_)) => problems.push("effect_duration needs a status_effect".to_string()),
+            (_, Some(turns)) if turns <= 0 => {
+                problems.push(format!("effect_duration must be positive when set, got {}", turns));
+            }
+            _ => {}
+        }
+        match (self.status_effect, self.effect_duration, self.effect_seconds) {
+            (_, _, None) => {}
+            (None, _, Some(_)) => problems.push("effect_seconds needs a status_effect".to_string()),
+            (_, Some(_), Some(_)) => {
+                problems.push("set either effect_duration or effect_seconds, not both".to_string());
+            }
+            (_, None, Some(seconds)) if seconds <= 0 => {
+                problems.push(format!("effect_seconds must be positive when set, got {}", seconds));
+            }
+            _ => {}
+        }
+        problems
+    }
+}
+
+/// A skill as known by a particular player, including its trained level
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerSkill {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub skill: Skill,
+    pub level: i32,
+}
+
+impl PlayerSkill {
+    /// Skill power grows by 10% for every level past the first
+    pub fn effective_power(&self) -> i32 {
+        self.skill.power + self.skill.power * (self.level - 1) / 10
+    }
+}
@@ -0,0 +1,345 @@
+use serde::{Serialize, Deserialize};
+use sqlx::{Decode, Postgres, Type};
+use sqlx::postgres::{PgTypeInfo, PgValueRef};
+use crate::models::UnknownVariantError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum EffectKind {
+    AttackUp,
+    AttackDown,
+    AccuracyUp,
+    AccuracyDown,
+    HealingUp,      // magnitude is a percentage
+    HealingDown,    // magnitude is a percentage
+    Regeneration,   // heals `magnitude` per tick
+    DamageOverTime, // deals `magnitude` per tick
+    Stun,           // skips the affected combatant's next turn
+}
+
+impl EffectKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EffectKind::AttackUp => "AttackUp",
+            EffectKind::AttackDown => "AttackDown",
+            EffectKind::AccuracyUp => "AccuracyUp",
+            EffectKind::AccuracyDown => "AccuracyDown",
+            EffectKind::HealingUp => "HealingUp",
+            EffectKind::HealingDown => "HealingDown",
+            EffectKind::Regeneration => "Regeneration",
+            EffectKind::DamageOverTime => "DamageOverTime",
+            EffectKind::Stun => "Stun",
+        }
+    }
+
+    /// How a second application of the same effect combines with the first
+    pub fn default_stacking(&self) -> StackingRule {
+        match self {
+            EffectKind::DamageOverTime => StackingRule::Stack { max_stacks: 3 },
+            _ => StackingRule::Refresh,
+        }
+    }
+}
+
+impl TryFrom<String> for EffectKind {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "AttackUp" => Ok(EffectKind::AttackUp),
+            "AttackDown" => Ok(EffectKind::AttackDown),
+            "AccuracyUp" => Ok(EffectKind::AccuracyUp),
+            "AccuracyDown" => Ok(EffectKind::AccuracyDown),
+            "HealingUp" => Ok(EffectKind::HealingUp),
+            "HealingDown" => Ok(EffectKind::HealingDown),
+            "Regeneration" => Ok(EffectKind::Regeneration),
+            "DamageOverTime" => Ok(EffectKind::DamageOverTime),
+            "Stun" => Ok(EffectKind::Stun),
+            _ => Err(UnknownVariantError {
+                kind: "status effect",
+                value,
+                expected: &[
+                    "AttackUp", "AttackDown", "AccuracyUp", "AccuracyDown", "HealingUp",
+                    "HealingDown", "Regeneration", "DamageOverTime", "Stun",
+                ],
+            }),
+        }
+    }
+}
+
+// Stored as plain VARCHAR so nullable columns decode straight into `Option<EffectKind>`
+impl Type<Postgres> for EffectKind {
+    fn type_info() -> PgTypeInfo {
+        <String as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for EffectKind {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let raw = <String as Decode<Postgres>>::decode(value)?;
+        Ok(EffectKind::try_from(raw)?)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum StackingRule {
+    /// Reapplying resets the duration and keeps the stronger magnitude
+    Refresh,
+    /// Reapplying adds a stack (up to `max_stacks`) and resets the duration
+    Stack { max_stacks: u32 },
+    /// Reapplying while active has no effect
+    Ignore,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum EffectDuration {
+    /// Counts down at the end of each of the affected combatant's turns
+    Turns(u32),
+    /// Counts down in real time, for effects that outlive an encounter
+    Seconds(u32),
+    /// Lasts until removed, e.g. while a cursed item is equipped
+    Permanent,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StatusEffect {
+    pub id: String, // effects with the same ID stack according to `stacking`
+    pub name: String,
+    pub kind: EffectKind,
+    pub magnitude: i32,
+    pub duration: EffectDuration,
+    pub stacking: StackingRule,
+    pub stacks: u32,
+    pub source: String,
+    /// Landed during its target's own turn, which ends without counting it down
+    #[serde(skip)]
+    pub fresh: bool,
+}
+
+impl StatusEffect {
+    pub fn new(id: &str, name: &str, kind: EffectKind, magnitude: i32, duration: EffectDuration, source: &str) -> Self {
+        StatusEffect {
+            id: id.to_string(),
+            name: name.to_string(),
+            kind,
+            magnitude,
+            duration,
+            stacking: kind.default_stacking(),
+            stacks: 1,
+            source: source.to_string(),
+            fresh: false,
+        }
+    }
+
+    fn total_magnitude(&self) -> i32 {
+        self.magnitude * self.stacks as i32
+    }
+
+    fn is_expired(&self) -> bool {
+        matches!(self.duration, EffectDuration::Turns(0) | EffectDuration::Seconds(0))
+    }
+}
+
+/// What happened to an effect when it ticked
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum EffectTick {
+    Healed { effect: String, amount: i32 },
+    Damaged { effect: String, amount: i32 },
+    Expired { effect: String },
+}
+
+/// The set of effects currently active on a player or combatant
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StatusEffects {
+    pub active: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// Apply an effect, combining it with an active one of the same ID per its stacking rule
+    pub fn apply(&mut self, effect: StatusEffect) {
+        let Some(existing) = self.active.iter_mut().find(|e| e.id == effect.id) else {
+            self.active.push(effect);
+            return;
+        };
+
+        match existing.stacking {
+            StackingRule::Refresh => {
+                existing.duration = effect.duration;
+                existing.magnitude = existing.magnitude.max(effect.magnitude);
+                existing.fresh = effect.fresh;
+            }
+            StackingRule::Stack { max_stacks } => {
+                existing.duration = effect.duration;
+                existing.stacks = (existing.stacks + 1).min(max_stacks);
+                existing.fresh = effect.fresh;
+            }
+            StackingRule::Ignore => {}
+        }
+    }
+
+    /// Resolve periodic effects and count turn-based durations down by one. Fresh effects sit
+    /// this turn out and take part from the next one.
+    pub fn tick_turn(&mut self) -> Vec<EffectTick> {
+        let mut ticks = Vec::new();
+        for effect in &mut self.active {
+            if std::mem::take(&mut effect.fresh) {
+                continue;
+            }
+            match effect.kind {
+                EffectKind::Regeneration => ticks.push(EffectTick::Healed {
+                    effect: effect.name.clone(),
+                    amount: effect.total_magnitude(),
+                }),
+                EffectKind::DamageOverTime => ticks.push(EffectTick::Damaged {
+                    effect: effect.name.clone(),
+                    amount: effect.total_magnitude(),
+                }),
+                _ => {}
+            }
+            if let EffectDuration::Turns(turns) = &mut effect.duration {
+                *turns = turns.saturating_sub(1);
+            }
+        }
+        ticks.extend(self.drain_expired());
+        ticks
+    }
+
+    /// Count real-time durations down by the elapsed number of seconds
+    pub fn tick_seconds(&mut self, elapsed: u32) -> Vec<EffectTick> {
+        for effect in &mut self.active {
+            if let EffectDuration::Seconds(seconds) = &mut effect.duration {
+                *seconds = seconds.saturating_sub(elapsed);
+            }
+        }
+        self.drain_expired()
+    }
+
+    fn drain_expired(&mut self) -> Vec<EffectTick> {
+        let mut expired = Vec::new();
+        self.active.retain(|e| {
+            if e.is_expired() {
+                expired.push(EffectTick::Expired { effect: e.name.clone() });
+                false
+            } else {
+                true
+            }
+        });
+        expired
+    }
+
+    fn sum(&self, up: EffectKind, down: EffectKind) -> i32 {
+        self.active
+            .iter()
+            .map(|e| match e.kind {
+                k if k == up => e.total_magnitude(),
+                k if k == down => -e.total_magnitude(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Flat bonus (or penalty) to damage
+    pub fn attack_modifier(&self) -> i32 {
+        self.sum(EffectKind::AttackUp, EffectKind::AttackDown)
+    }
+
+    /// Flat bonus (or penalty) to attack rolls
+    pub fn accuracy_modifier(&self) -> i32 {
+        self.sum(EffectKind::AccuracyUp, EffectKind::AccuracyDown)
+    }
+
+    /// Scale an incoming heal by healing modifiers, never below zero
+    pub fn modify_healing(&self, amount: i32) -> i32 {
+        let percent = 100 + self.sum(EffectKind::HealingUp, EffectKind::HealingDown);
+        (amount * percent.max(0)) / 100
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.active.iter().any(|e| e.kind == EffectKind::Stun)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(kind: EffectKind, magnitude: i32, duration: EffectDuration) -> StatusEffect {
+        StatusEffect::new(kind.as_str(), kind.as_str(), kind, magnitude, duration, "test")
+    }
+
+    #[test]
+    fn reapplying_follows_the_stacking_rule() {
+        let mut effects = StatusEffects::default();
+        effects.apply(effect(EffectKind::AttackUp, 3, EffectDuration::Turns(1)));
+        effects.apply(effect(EffectKind::AttackUp, 2, EffectDuration::Turns(4)));
+        assert_eq!(effects.attack_modifier(), 3);
+        assert_eq!(effects.active[0].duration, EffectDuration::Turns(4));
+
+        for _ in 0..4 {
+            effects.apply(effect(EffectKind::DamageOverTime, 2, EffectDuration::Turns(2)));
+        }
+        assert_eq!(effects.active[1].stacks, 3);
+
+        let mut curse = effect(EffectKind::AccuracyDown, 2, EffectDuration::Permanent);
+        curse.stacking = StackingRule::Ignore;
+        effects.apply(curse.clone());
+        curse.magnitude = 9;
+        effects.apply(curse);
+        assert_eq!(effects.accuracy_modifier(), -2);
+    }
+
+    #[test]
+    fn turn_effects_tick_and_expire() {
+        let mut effects = StatusEffects::default();
+        effects.apply(effect(EffectKind::DamageOverTime, 4, EffectDuration::Turns(2)));
+        effects.apply(effect(EffectKind::AttackUp, 1, EffectDuration::Permanent));
+
+        let damaged = EffectTick::Damaged { effect: "DamageOverTime".to_string(), amount: 4 };
+        assert_eq!(effects.tick_turn(), vec![damaged.clone()]);
+        assert_eq!(
+            effects.tick_turn(),
+            vec![damaged, EffectTick::Expired { effect: "DamageOverTime".to_string() }]
+        );
+        assert_eq!(effects.active.len(), 1);
+        assert!(effects.tick_turn().is_empty());
+    }
+
+    #[test]
+    fn fresh_effects_sit_out_the_turn_they_landed_in() {
+        let mut effects = StatusEffects::default();
+        let mut regeneration = effect(EffectKind::Regeneration, 5, EffectDuration::Turns(1));
+        regeneration.fresh = true;
+        effects.apply(regeneration);
+
+        assert!(effects.tick_turn().is_empty());
+        assert_eq!(
+            effects.tick_turn(),
+            vec![
+                EffectTick::Healed { effect: "Regeneration".to_string(), amount: 5 },
+                EffectTick::Expired { effect: "Regeneration".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn real_time_effects_expire_with_the_seconds() {
+        let mut effects = StatusEffects::default();
+        effects.apply(effect(EffectKind::AttackUp, 3, EffectDuration::Seconds(60)));
+        assert!(effects.tick_turn().is_empty());
+        assert!(effects.tick_seconds(59).is_empty());
+        assert_eq!(effects.tick_seconds(5), vec![EffectTick::Expired { effect: "AttackUp".to_string() }]);
+    }
+
+    #[test]
+    fn healing_modifiers_scale_by_percent() {
+        let mut effects = StatusEffects::default();
+        effects.apply(effect(EffectKind::HealingDown, 150, EffectDuration::Turns(1)));
+        assert_eq!(effects.modify_healing(20), 0);
+        effects.active.clear();
+        effects.apply(effect(EffectKind::HealingUp, 50, EffectDuration::Turns(1)));
+        assert_eq!(effects.modify_healing(20), 30);
+    }
+}
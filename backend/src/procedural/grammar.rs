@@ -0,0 +1,243 @@
+// src/procedural/grammar.rs
+use rand::Rng;
+use std::collections::HashMap;
+use std::fmt;
+
+/// How deep expansions may nest before a symbol is left as written, so a rule that refers back
+/// to itself can't recurse forever
+const MAX_DEPTH: usize = 16;
+
+const MODIFIERS: &[&str] = &["capitalize", "a", "lower"];
+
+/// A Tracery-style grammar. Each rule maps a symbol to alternatives; `#symbol#` in an alternative
+/// is replaced by a random expansion of that symbol, and `#symbol.capitalize#`, `#symbol.a#` or
+/// `#symbol.lower#` tidy up the result.
+#[derive(Debug, Clone, Default)]
+pub struct Grammar {
+    rules: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrammarError {
+    MissingRule(String),
+    EmptyRule(String),
+    UnknownSymbol { rule: String, symbol: String },
+    UnknownModifier { rule: String, modifier: String },
+    Unclosed(String),
+}
+
+impl GrammarError {
+    /// The rule the problem is in, if it is in one
+    pub fn rule(&self) -> Option<&str> {
+        match self {
+            GrammarError::MissingRule(_) => None,
+            GrammarError::EmptyRule(rule)
+            | GrammarError::UnknownSymbol { rule, .. }
+            | GrammarError::UnknownModifier { rule, .. }
+            | GrammarError::Unclosed(rule) => Some(rule),
+        }
+    }
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrammarError::MissingRule(rule) => write!(f, "grammar has no '{}' rule", rule),
+            GrammarError::EmptyRule(rule) => write!(f, "rule '{}' has no alternatives", rule),
+            GrammarError::UnknownSymbol { rule, symbol } => {
+                write!(f, "rule '{}' refers to unknown symbol '{}'", rule, symbol)
+            }
+            GrammarError::UnknownModifier { rule, modifier } => write!(
+                f,
+                "rule '{}' uses unknown modifier '{}', expected one of {}",
+                rule,
+                modifier,
+                MODIFIERS.join(", ")
+            ),
+            GrammarError::Unclosed(rule) => write!(f, "rule '{}' has a '#' without a closing '#'", rule),
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
+/// A piece of an alternative: plain text, or a symbol to expand
+enum Token<'a> {
+    Text(&'a str),
+    Symbol { name: &'a str, modifiers: Vec<&'a str> },
+}
+
+/// Split an alternative into text and `#symbol.modifier#` references. `None` if a `#` is left open.
+fn tokenize(alternative: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = alternative;
+    while let Some(start) = rest.find('#') {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let after = &rest[start + 1..];
+        let end = after.find('#')?;
+        let mut parts = after[..end].split('.');
+        let name = parts.next().unwrap_or_default();
+        tokens.push(Token::Symbol { name, modifiers: parts.collect() });
+        rest = &after[end + 1..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    Some(tokens)
+}
+
+fn apply_modifier(text: String, modifier: &str) -> String {
+    match modifier {
+        "capitalize" => {
+            let mut chars = text.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => text,
+            }
+        }
+        "a" => {
+            let vowel = text.chars().next().is_some_and(|c| "aeiouAEIOU".contains(c));
+            format!("{} {}", if vowel { "an" } else { "a" }, text)
+        }
+        "lower" => text.to_lowercase(),
+        _ => text,
+    }
+}
+
+impl Grammar {
+    pub fn new(rules: HashMap<String, Vec<String>>) -> Self {
+        Grammar { rules }
+    }
+
+    /// Problems that would make expansions come out wrong: `required` rules that are missing,
+    /// empty rules, references to symbols that are neither rules nor `variables`, unknown
+    /// modifiers and unclosed `#`s. Sorted by rule so the result doesn't depend on map order.
+    pub fn check(&self, required: &[&str], variables: &[&str]) -> Vec<GrammarError> {
+        let mut errors: Vec<GrammarError> = required
+            .iter()
+            .filter(|rule| !self.rules.contains_key(**rule))
+            .map(|rule| GrammarError::MissingRule(rule.to_string()))
+            .collect();
+
+        let mut names: Vec<&String> = self.rules.keys().collect();
+        names.sort();
+        for name in names {
+            let alternatives = &self.rules[name];
+            if alternatives.is_empty() {
+                errors.push(GrammarError::EmptyRule(name.clone()));
+            }
+            for alternative in alternatives {
+                let Some(tokens) = tokenize(alternative) else {
+                    errors.push(GrammarError::Unclosed(name.clone()));
+                    continue;
+                };
+                for token in tokens {
+                    let Token::Symbol { name: symbol, modifiers } = token else {
+                        continue;
+                    };
+                    if !self.rules.contains_key(symbol) && !variables.contains(&symbol) {
+                        errors.push(GrammarError::UnknownSymbol { rule: name.clone(), symbol: symbol.to_string() });
+                    }
+                    for modifier in modifiers.into_iter().filter(|m| !MODIFIERS.contains(m)) {
+                        errors.push(GrammarError::UnknownModifier {
+                            rule: name.clone(),
+                            modifier: modifier.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        errors.dedup();
+        errors
+    }
+
+    /// Expand `symbol`. `variables` fill in symbols the grammar doesn't define, such as the name
+    /// of the region a portal leads to; anything still unknown is left as written.
+    pub fn expand<R: Rng>(&self, symbol: &str, rng: &mut R, variables: &[(&str, &str)]) -> String {
+        self.expand_at(symbol, rng, variables, 0)
+    }
+
+    fn expand_at<R: Rng>(&self, symbol: &str, rng: &mut R, variables: &[(&str, &str)], depth: usize) -> String {
+        if let Some((_, value)) = variables.iter().find(|(name, _)| *name == symbol) {
+            return value.to_string();
+        }
+        let alternatives = match self.rules.get(symbol) {
+            Some(alternatives) if !alternatives.is_empty() && depth < MAX_DEPTH => alternatives,
+            _ => return format!("#{}#", symbol),
+        };
+        let alternative = &alternatives[rng.gen_range(0..alternatives.len())];
+        let Some(tokens) = tokenize(alternative) else {
+            return alternative.clone();
+        };
+
+        let mut text = String::new();
+        for token in tokens {
+            match token {
+                Token::Text(plain) => text.push_str(plain),
+                Token::Symbol { name, modifiers } => {
+                    let expanded = self.expand_at(name, rng, variables, depth + 1);
+                    text.push_str(&modifiers.into_iter().fold(expanded, apply_modifier));
+                }
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procedural::seeded_rng;
+
+    fn grammar(rules: &[(&str, &[&str])]) -> Grammar {
+        Grammar::new(
+            rules
+                .iter()
+                .map(|(name, alternatives)| (name.to_string(), alternatives.iter().map(|a| a.to_string()).collect()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn expands_symbols_variables_and_modifiers() {
+        let grammar = grammar(&[("greeting", &["#thing.a.capitalize# near #target#"]), ("thing", &["owl"])]);
+        let text = grammar.expand("greeting", &mut seeded_rng(1), &[("target", "the Keep")]);
+        assert_eq!(text, "An owl near the Keep");
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_text() {
+        let grammar = grammar(&[("name", &["#a# #b#"]), ("a", &["Red", "Grey", "Old", "Deep"]), ("b", &["Vale", "Spire", "Hollow"])]);
+        let run = |seed| (0..10).map(|_| grammar.expand("name", &mut seeded_rng(seed), &[])).collect::<Vec<_>>();
+        assert_eq!(run(3), run(3));
+    }
+
+    #[test]
+    fn self_reference_stops_at_the_depth_limit() {
+        let grammar = grammar(&[("loop", &["x#loop#"])]);
+        let text = grammar.expand("loop", &mut seeded_rng(0), &[]);
+        assert_eq!(text, format!("{}#loop#", "x".repeat(MAX_DEPTH)));
+    }
+
+    #[test]
+    fn check_reports_every_kind_of_problem() {
+        let grammar = grammar(&[
+            ("bad_modifier", &["#name.shout#"]),
+            ("empty", &[]),
+            ("open", &["#name"]),
+            ("unknown", &["#nowhere#"]),
+        ]);
+        assert_eq!(
+            grammar.check(&["region_name"], &["name"]),
+            vec![
+                GrammarError::MissingRule("region_name".to_string()),
+                GrammarError::UnknownModifier { rule: "bad_modifier".to_string(), modifier: "shout".to_string() },
+                GrammarError::EmptyRule("empty".to_string()),
+                GrammarError::Unclosed("open".to_string()),
+                GrammarError::UnknownSymbol { rule: "unknown".to_string(), symbol: "nowhere".to_string() },
+            ]
+        );
+    }
+}
@@ -0,0 +1,379 @@
+// src/procedural/layout.rs
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use crate::models::{Entrance, Portal, Position, RoomLayout, UnknownVariantError};
+use crate::models::layout::{ROCK, ROOM};
+use crate::procedural::seeded_rng;
+
+/// Smallest grid any algorithm can work with, rock border included
+pub const MIN_SIZE: usize = 5;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutAlgorithm {
+    /// Cellular automata: open, organic caves
+    Caves,
+    /// A drunkard's walk: winding tunnels
+    RandomWalk,
+    /// Binary space partitioning: rectangular rooms joined by corridors
+    Bsp,
+}
+
+impl LayoutAlgorithm {
+    pub const ALL: [LayoutAlgorithm; 3] = [LayoutAlgorithm::Caves, LayoutAlgorithm::RandomWalk, LayoutAlgorithm::Bsp];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LayoutAlgorithm::Caves => "caves",
+            LayoutAlgorithm::RandomWalk => "random_walk",
+            LayoutAlgorithm::Bsp => "bsp",
+        }
+    }
+}
+
+impl fmt::Display for LayoutAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TryFrom<String> for LayoutAlgorithm {
+    type Error = UnknownVariantError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        LayoutAlgorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.as_str() == value)
+            .ok_or(UnknownVariantError { kind: "layout algorithm", value, expected: &["caves", "random_walk", "bsp"] })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutConfig {
+    pub algorithm: LayoutAlgorithm,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig { algorithm: LayoutAlgorithm::Caves, width: 24, height: 12 }
+    }
+}
+
+/// `true` for rooms, indexed `[y][x]`
+type Grid = Vec<Vec<bool>>;
+
+/// Generate the rooms of a region and place an entrance for each of its portals, spread out
+/// from the start and from each other. The same seed always gives the same layout.
+pub fn generate_layout(config: &LayoutConfig, seed: u64, portals: &[Portal]) -> RoomLayout {
+    let mut rng = seeded_rng(seed);
+    let width = config.width.max(MIN_SIZE);
+    let height = config.height.max(MIN_SIZE);
+
+    let mut grid = match config.algorithm {
+        LayoutAlgorithm::Caves => caves(width, height, &mut rng),
+        LayoutAlgorithm::RandomWalk => random_walk(width, height, &mut rng),
+        LayoutAlgorithm::Bsp => bsp(width, height, &mut rng),
+    };
+    let start = keep_largest_area(&mut grid, width, height);
+
+    // Farthest-first: each entrance goes to the room farthest from the start and every entrance
+    // placed so far. Small layouts may end up with several entrances in one room.
+    let mut distance = walk_distances(&grid, start);
+    let mut entrances = Vec::new();
+    for portal in portals {
+        let pos = farthest(&distance, width, height).unwrap_or(start);
+        entrances.push(Entrance { portal_id: portal.id.clone(), x: pos.x, y: pos.y });
+        let from_new = walk_distances(&grid, pos);
+        for (row, new_row) in distance.iter_mut().zip(from_new) {
+            for (d, new) in row.iter_mut().zip(new_row) {
+                *d = (*d).min(new);
+            }
+        }
+    }
+
+    let rows = grid
+        .iter()
+        .map(|row| row.iter().map(|&room| if room { ROOM } else { ROCK }).collect())
+        .collect();
+    RoomLayout { rows, start, entrances }
+}
+
+fn empty_grid(width: usize, height: usize) -> Grid {
+    vec![vec![false; width]; height]
+}
+
+fn centre(width: usize, height: usize) -> Position {
+    Position { x: width / 2, y: height / 2 }
+}
+
+/// Random fill, then a few rounds where each cell becomes rock if most of its neighbours are
+fn caves<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
+    let mut grid = empty_grid(width, height);
+    for row in &mut grid[1..height - 1] {
+        for cell in &mut row[1..width - 1] {
+            *cell = rng.gen_bool(0.5);
+        }
+    }
+    for _ in 0..4 {
+        let mut next = empty_grid(width, height);
+        for (y, row) in next.iter_mut().enumerate().take(height - 1).skip(1) {
+            for (x, cell) in row.iter_mut().enumerate().take(width - 1).skip(1) {
+                let rock_around = (y - 1..=y + 1)
+                    .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                    .filter(|&(nx, ny)| (nx, ny) != (x, y) && !grid[ny][nx])
+                    .count();
+                *cell = rock_around < 5;
+            }
+        }
+        grid = next;
+    }
+    // Caves can close up entirely; leave at least the centre open
+    let mid = centre(width, height);
+    grid[mid.y][mid.x] = true;
+    grid
+}
+
+/// Wander from the centre, digging, until about 40% of the inside is open
+fn random_walk<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
+    let mut grid = empty_grid(width, height);
+    let target = (width - 2) * (height - 2) * 2 / 5;
+    let mut pos = centre(width, height);
+    grid[pos.y][pos.x] = true;
+    let mut open = 1;
+    for _ in 0..target * 20 {
+        if open >= target {
+            break;
+        }
+        match rng.gen_range(0..4) {
+            0 if pos.y > 1 => pos.y -= 1,
+            1 if pos.y < height - 2 => pos.y += 1,
+            2 if pos.x > 1 => pos.x -= 1,
+            3 if pos.x < width - 2 => pos.x += 1,
+            _ => continue,
+        }
+        if !grid[pos.y][pos.x] {
+            grid[pos.y][pos.x] = true;
+            open += 1;
+        }
+    }
+    grid
+}
+
+#[derive(Clone, Copy)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rect {
+    fn centre(&self) -> Position {
+        Position { x: self.x + self.width / 2, y: self.y + self.height / 2 }
+    }
+}
+
+/// Split the inside into leaves, put a room in each and join sibling rooms with corridors
+fn bsp<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
+    let mut grid = empty_grid(width, height);
+    let inside = Rect { x: 1, y: 1, width: width - 2, height: height - 2 };
+    split(inside, &mut grid, rng);
+    grid
+}
+
+/// Leaves smaller than this in both directions get a room instead of splitting again
+const MIN_LEAF: usize = 6;
+
+/// Carve the rooms for `rect` and return the centre of one of them, for corridors to aim at
+fn split<R: Rng>(rect: Rect, grid: &mut Grid, rng: &mut R) -> Position {
+    let can_split_x = rect.width >= MIN_LEAF * 2;
+    let can_split_y = rect.height >= MIN_LEAF * 2;
+    let vertical = match (can_split_x, can_split_y) {
+        (false, false) => return carve_room(rect, grid, rng),
+        (true, false) => true,
+        (false, true) => false,
+        (true, true) => rng.gen_bool(0.5),
+    };
+
+    let (a, b) = if vertical {
+        let at = rng.gen_range(MIN_LEAF..=rect.width - MIN_LEAF);
+        (Rect { width: at, ..rect }, Rect { x: rect.x + at, width: rect.width - at, ..rect })
+    } else {
+        let at = rng.gen_range(MIN_LEAF..=rect.height - MIN_LEAF);
+        (Rect { height: at, ..rect }, Rect { y: rect.y + at, height: rect.height - at, ..rect })
+    };
+    let from = split(a, grid, rng);
+    let to = split(b, grid, rng);
+    carve_corridor(from, to, grid, rng);
+    if rng.gen_bool(0.5) { from } else { to }
+}
+
+/// A room somewhere inside the leaf, leaving a wall of rock on its far sides
+fn carve_room<R: Rng>(leaf: Rect, grid: &mut Grid, rng: &mut R) -> Position {
+    let room_width = rng.gen_range(leaf.width.div_ceil(2)..leaf.width.max(2));
+    let room_height = rng.gen_range(leaf.height.div_ceil(2)..leaf.height.max(2));
+    let room = Rect {
+        x: leaf.x + rng.gen_range(0..leaf.width - room_width),
+        y: leaf.y + rng.gen_range(0..leaf.height - room_height),
+        width: room_width,
+        height: room_height,
+    };
+    for row in &mut grid[room.y..room.y + room.height] {
+        for cell in &mut row[room.x..room.x + room.width] {
+            *cell = true;
+        }
+    }
+    room.centre()
+}
+
+/// An L-shaped corridor, turning at one end or the other
+fn carve_corridor<R: Rng>(from: Position, to: Position, grid: &mut Grid, rng: &mut R) {
+    let corner = if rng.gen_bool(0.5) { Position { x: to.x, y: from.y } } else { Position { x: from.x, y: to.y } };
+    for (a, b) in [(from, corner), (corner, to)] {
+        for row in &mut grid[a.y.min(b.y)..=a.y.max(b.y)] {
+            for cell in &mut row[a.x.min(b.x)..=a.x.max(b.x)] {
+                *cell = true;
+            }
+        }
+    }
+}
+
+/// Steps from `from` to every room; `usize::MAX` for rock and rooms that can't be reached
+fn walk_distances(grid: &Grid, from: Position) -> Vec<Vec<usize>> {
+    let height = grid.len();
+    let width = grid[0].len();
+    let mut distance = vec![vec![usize::MAX; width]; height];
+    distance[from.y][from.x] = 0;
+    let mut queue = VecDeque::from([from]);
+    while let Some(pos) = queue.pop_front() {
+        let next_steps = [
+            (pos.x, pos.y.wrapping_sub(1)),
+            (pos.x, pos.y + 1),
+            (pos.x + 1, pos.y),
+            (pos.x.wrapping_sub(1), pos.y),
+        ];
+        for (x, y) in next_steps {
+            if y < height && x < width && grid[y][x] && distance[y][x] == usize::MAX {
+                distance[y][x] = distance[pos.y][pos.x] + 1;
+                queue.push_back(Position { x, y });
+            }
+        }
+    }
+    distance
+}
+
+/// The reachable room with the largest distance, first in reading order on ties
+fn farthest(distance: &[Vec<usize>], width: usize, height: usize) -> Option<Position> {
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| Position { x, y }))
+        .filter(|pos| distance[pos.y][pos.x] != usize::MAX)
+        .fold(None, |best: Option<Position>, pos| match best {
+            Some(b) if distance[b.y][b.x] >= distance[pos.y][pos.x] => Some(b),
+            _ => Some(pos),
+        })
+}
+
+/// Fill in every room not connected to the largest open area, so all rooms can be walked
+/// between, and return the room of that area nearest the centre as the start
+fn keep_largest_area(grid: &mut Grid, width: usize, height: usize) -> Position {
+    let mut area_of = vec![vec![usize::MAX; width]; height];
+    let mut areas: Vec<Vec<Position>> = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if !grid[y][x] || area_of[y][x] != usize::MAX {
+                continue;
+            }
+            let distance = walk_distances(grid, Position { x, y });
+            let mut area = Vec::new();
+            for (ay, row) in distance.iter().enumerate() {
+                for (ax, &d) in row.iter().enumerate() {
+                    if d != usize::MAX {
+                        area_of[ay][ax] = areas.len();
+                        area.push(Position { x: ax, y: ay });
+                    }
+                }
+            }
+            areas.push(area);
+        }
+    }
+
+    let mid = centre(width, height);
+    // Ties go to the area found first
+    let Some(largest) = (0..areas.len()).max_by_key(|&i| (areas[i].len(), std::cmp::Reverse(i))) else {
+        grid[mid.y][mid.x] = true;
+        return mid;
+    };
+    for (i, area) in areas.iter().enumerate() {
+        if i != largest {
+            for pos in area {
+                grid[pos.y][pos.x] = false;
+            }
+        }
+    }
+    *areas[largest]
+        .iter()
+        .min_by_key(|pos| (pos.x.abs_diff(mid.x) + pos.y.abs_diff(mid.y), pos.y, pos.x))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portals(count: usize) -> Vec<Portal> {
+        (0..count)
+            .map(|i| Portal {
+                id: format!("portal_{}", i),
+                name: format!("Portal {}", i),
+                leads_to: format!("region_{}", i),
+                required_level: 0,
+                required_item: None,
+            })
+            .collect()
+    }
+
+    fn configs() -> Vec<LayoutConfig> {
+        LayoutAlgorithm::ALL
+            .into_iter()
+            .flat_map(|algorithm| {
+                [(24, 12), (MIN_SIZE, MIN_SIZE), (40, 30)]
+                    .map(|(width, height)| LayoutConfig { algorithm, width, height })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_layout() {
+        for config in configs() {
+            let portals = portals(3);
+            assert_eq!(generate_layout(&config, 17, &portals), generate_layout(&config, 17, &portals), "{:?}", config);
+        }
+    }
+
+    #[test]
+    fn every_room_and_entrance_can_be_reached_from_the_start() {
+        let portals = portals(4);
+        for config in configs() {
+            for seed in 0..30 {
+                let layout = generate_layout(&config, seed, &portals);
+                assert_eq!(layout.problems(&portals), Vec::<String>::new(), "{:?} seed {}", config, seed);
+                assert_eq!(layout.entrances.len(), portals.len());
+            }
+        }
+    }
+
+    #[test]
+    fn the_edge_is_always_rock() {
+        for config in configs() {
+            let layout = generate_layout(&config, 4, &[]);
+            let (width, height) = (layout.width(), layout.height());
+            for pos in layout.rooms() {
+                assert!(pos.x > 0 && pos.y > 0 && pos.x < width - 1 && pos.y < height - 1, "{:?} room at {:?}", config, pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+pub mod grammar;
+pub mod layout;
+pub mod stub;
+pub mod text;
+pub mod world_generator;
+
+/// Bump whenever a generator would produce different output for the same seed, so regions
+/// generated by an older version aren't regenerated differently by mistake
+pub const GENERATOR_VERSION: u32 = 2;
+
+/// The RNG every generator draws from, so one seed always means one result
+pub fn seeded_rng(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed)
+}
+
+/// A separate stream for names and descriptions, so editing a grammar never changes the layout
+/// a seed produces
+pub fn text_rng(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed ^ 0x7465_7874_7465_7874)
+}
+
+/// A fresh seed for callers that don't pick one
+pub fn random_seed() -> u64 {
+    rand::random()
+}
+
+/// `base`, or `base` with the first numeric suffix that isn't taken yet
+pub fn unique_id(base: String, existing: &[String]) -> String {
+    if !existing.contains(&base) {
+        return base;
+    }
+    (2..)
+        .map(|suffix| format!("{}_{}", base, suffix))
+        .find(|id| !existing.contains(id))
+        .unwrap()
+}
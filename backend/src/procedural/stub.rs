@@ -0,0 +1,264 @@
+// src/procedural/stub.rs
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use crate::models::{EnvironmentType, GenerationInfo, Portal, Region};
+use crate::procedural::layout::{generate_layout, LayoutAlgorithm, LayoutConfig};
+use crate::procedural::text::TextGenerator;
+use crate::procedural::world_generator::{generate_world, GenerationConfig, GenerationError};
+use crate::procedural::{text_rng, GENERATOR_VERSION};
+
+pub const GENERATOR_NAME: &str = "procedural_stub";
+
+/// Portal levels a stub's generated regions stay within
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LevelBand {
+    pub min: i32,
+    pub max: i32,
+}
+
+/// The `[procedural]` table of a region file: the region is generated when content loads,
+/// along with `children` sub-regions hanging off it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ProceduralSpec {
+    /// A TOML integer, or a string for seeds too large for one
+    #[serde(serialize_with = "seed_as_string", deserialize_with = "seed_from_number_or_string")]
+    pub seed: u64,
+    /// Environment of the region and its children
+    pub theme: EnvironmentType,
+    /// About how many rooms each region gets to explore; none without this
+    #[serde(default)]
+    pub rooms: usize,
+    pub difficulty: LevelBand,
+    #[serde(default)]
+    pub children: usize,
+    /// How rooms are laid out; caves unless set
+    #[serde(default)]
+    pub layout: Option<LayoutAlgorithm>,
+}
+
+fn seed_as_string<S: Serializer>(seed: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(seed)
+}
+
+fn seed_from_number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    struct SeedVisitor;
+
+    impl de::Visitor<'_> for SeedVisitor {
+        type Value = u64;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a seed from 0 to 18446744073709551615, as a number or a string")
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<u64, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<u64, E> {
+            u64::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<u64, E> {
+            value.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+    }
+
+    deserializer.deserialize_any(SeedVisitor)
+}
+
+impl ProceduralSpec {
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.difficulty.min < 0 {
+            problems.push("difficulty min must not be negative".to_string());
+        }
+        if self.difficulty.max < self.difficulty.min {
+            problems.push(format!(
+                "difficulty max {} is below min {}",
+                self.difficulty.max, self.difficulty.min
+            ));
+        }
+        if self.layout.is_some() && self.rooms == 0 {
+            problems.push("layout is set but rooms is 0".to_string());
+        }
+        problems
+    }
+
+    /// A grid big enough for about `rooms` rooms, twice as wide as it is high
+    fn layout_config(&self) -> Option<LayoutConfig> {
+        if self.rooms == 0 {
+            return None;
+        }
+        // Generators open up roughly 40% of the inside of the grid
+        let inside = self.rooms * 5 / 2;
+        let height = ((inside as f64 / 2.0).sqrt().round() as usize).max(3);
+        let width = inside.div_ceil(height).max(3);
+        Some(LayoutConfig {
+            algorithm: self.layout.unwrap_or(LayoutAlgorithm::Caves),
+            width: width + 2,
+            height: height + 2,
+        })
+    }
+}
+
+/// A region file with a `[procedural]` table. Whatever the author wrote is kept; the rest is
+/// generated.
+#[derive(Debug, Clone)]
+pub struct RegionStub {
+    pub id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub portals: Vec<Portal>,
+    pub anchor_point: Option<String>,
+    pub spec: ProceduralSpec,
+}
+
+/// Turn a stub into concrete regions: the stub's own region first, then its children. Child IDs
+/// start with the stub's ID and never reuse `existing_region_ids`.
+pub fn expand_stub(
+    stub: &RegionStub,
+    existing_region_ids: &[String],
+    text: &TextGenerator,
+) -> Result<Vec<Region>, GenerationError> {
+    let spec = &stub.spec;
+    if let Some(problem) = spec.problems().into_iter().next() {
+        return Err(GenerationError::InvalidConfig(problem));
+    }
+
+    let mut text_rng = text_rng(spec.seed);
+    let name = stub.name.clone().unwrap_or_else(|| text.region_name(spec.theme, &mut text_rng));
+    let description = stub
+        .description
+        .clone()
+        .unwrap_or_else(|| text.region_description(spec.theme, &name, &mut text_rng));
+    let layout = spec.layout_config();
+
+    let mut portals = stub.portals.clone();
+    let mut children = Vec::new();
+    if spec.children > 0 {
+        let world = generate_world(
+            GenerationConfig {
+                seed: Some(spec.seed),
+                region_count: spec.children,
+                hub_id: stub.id.clone(),
+                hub_name: name.clone(),
+                hub_environment: spec.theme,
+                existing_region_ids: existing_region_ids.to_vec(),
+                base_level: spec.difficulty.min,
+                max_level: Some(spec.difficulty.max),
+                themes: vec![spec.theme],
+                id_prefix: stub.id.clone(),
+                layout: layout.clone(),
+                ..GenerationConfig::default()
+            },
+            text,
+        )?;
+        portals.extend(world.hub_portals);
+        children = world.regions;
+    }
+
+    let region = Region {
+        id: stub.id.clone(),
+        name,
+        description,
+        environment: spec.theme,
+        layout: layout.map(|config| generate_layout(&config, spec.seed, &portals)),
+        portals,
+        anchor_point: stub.anchor_point.clone(),
+        generated: Some(GenerationInfo {
+            generator: GENERATOR_NAME.to_string(),
+            seed: spec.seed,
+            version: GENERATOR_VERSION,
+        }),
+    };
+    Ok(std::iter::once(region).chain(children).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stub(spec: ProceduralSpec) -> RegionStub {
+        RegionStub {
+            id: "hollow".to_string(),
+            name: Some("The Hollow".to_string()),
+            description: None,
+            portals: vec![Portal {
+                id: "hollow_to_nexus".to_string(),
+                name: "Way Home".to_string(),
+                leads_to: "nexus".to_string(),
+                required_level: 0,
+                required_item: None,
+            }],
+            anchor_point: None,
+            spec,
+        }
+    }
+
+    fn spec() -> ProceduralSpec {
+        ProceduralSpec {
+            seed: 1234,
+            theme: EnvironmentType::Fantasy,
+            rooms: 30,
+            difficulty: LevelBand { min: 2, max: 6 },
+            children: 5,
+            layout: None,
+        }
+    }
+
+    #[test]
+    fn same_stub_expands_to_the_same_regions() {
+        let text = TextGenerator::builtin();
+        let first = expand_stub(&stub(spec()), &[], &text).unwrap();
+        let second = expand_stub(&stub(spec()), &[], &text).unwrap();
+        assert_eq!(format!("{:?}", first), format!("{:?}", second));
+        assert_eq!(first.len(), 6);
+    }
+
+    #[test]
+    fn keeps_what_the_author_wrote_and_generates_the_rest() {
+        let regions = expand_stub(&stub(spec()), &[], &TextGenerator::builtin()).unwrap();
+        let region = &regions[0];
+        assert_eq!(region.name, "The Hollow");
+        assert!(!region.description.is_empty());
+        assert_eq!(region.portals[0].id, "hollow_to_nexus");
+        assert!(region.portals.len() > 1, "children are linked in");
+        for region in &regions {
+            assert_eq!(region.environment, EnvironmentType::Fantasy);
+            let layout = region.layout.as_ref().expect("rooms were asked for");
+            assert_eq!(layout.problems(&region.portals), Vec::<String>::new(), "{}", region.id);
+        }
+    }
+
+    #[test]
+    fn children_stay_in_the_difficulty_band_and_skip_existing_ids() {
+        let existing = vec!["hollow_1".to_string()];
+        let regions = expand_stub(&stub(spec()), &existing, &TextGenerator::builtin()).unwrap();
+        assert_eq!(regions[1].id, "hollow_1_2");
+        for portal in regions.iter().flat_map(|r| &r.portals) {
+            assert!(portal.required_level <= 6, "{} needs level {}", portal.id, portal.required_level);
+            assert!(portal.required_level == 0 || portal.required_level >= 2, "{}", portal.id);
+        }
+    }
+
+    #[test]
+    fn rejects_an_inverted_difficulty_band() {
+        let spec = ProceduralSpec { difficulty: LevelBand { min: 5, max: 1 }, ..spec() };
+        assert!(matches!(
+            expand_stub(&stub(spec), &[], &TextGenerator::builtin()),
+            Err(GenerationError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn seeds_too_large_for_toml_integers_are_written_as_strings() {
+        let spec = ProceduralSpec { seed: u64::MAX, ..spec() };
+        let written = toml::to_string(&spec).unwrap();
+        assert!(written.contains("seed = \"18446744073709551615\""), "{}", written);
+        assert_eq!(toml::from_str::<ProceduralSpec>(&written).unwrap(), spec);
+        let small: ProceduralSpec = toml::from_str(&written.replace("\"18446744073709551615\"", "42")).unwrap();
+        assert_eq!(small.seed, 42);
+    }
+}
@@ -1,52 +1,343 @@
 // src/procedural/world_generator.rs
-use rand::{rngs::StdRng, Rng, SeedableRng};
-use crate::models::dungeon::{DungeonRegion, EnvironmentType, Portal};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::fmt;
+use crate::engine::map_graph::{MapGraph, NEXUS_ID};
+use crate::models::{EnvironmentType, GenerationInfo, Portal, Region};
+use crate::procedural::layout::{generate_layout, LayoutConfig};
+use crate::procedural::text::TextGenerator;
+use crate::procedural::{random_seed, seeded_rng, text_rng, unique_id, GENERATOR_VERSION};
+
+pub const GENERATOR_NAME: &str = "world_generator";
 
 pub struct GenerationConfig {
     pub seed: Option<u64>,
     pub region_count: usize,
+    /// The existing region the world hangs off
+    pub hub_id: String,
+    /// Name and environment of the hub, used for the text of portals leading to and from it
+    pub hub_name: String,
+    pub hub_environment: EnvironmentType,
+    /// Regions that already exist, whose IDs generated regions must not reuse
+    pub existing_region_ids: Vec<String>,
+    /// Chance per region of an extra portal pair on top of the spanning tree, from 0.0 to 1.0
+    pub loop_density: f64,
+    /// Level needed to enter regions next to the hub
+    pub base_level: i32,
+    /// Extra level needed for every further step away from the hub
+    pub level_step: i32,
+    /// Highest level any portal may require
+    pub max_level: Option<i32>,
+    /// Environments generated regions pick from
+    pub themes: Vec<EnvironmentType>,
+    /// Generated regions are called `<id_prefix>_1`, `<id_prefix>_2` and so on
+    pub id_prefix: String,
+    /// Give every generated region rooms to explore
+    pub layout: Option<LayoutConfig>,
 }
 
-pub fn generate_world(config: GenerationConfig) -> Vec<DungeonRegion> {
-    // Initialize RNG with a seed for reproducibility.
-    let seed = config.seed.unwrap_or_else(|| rand::random());
-    let mut rng = StdRng::seed_from_u64(seed);
-    
-    let mut regions = Vec::new();
-
-    for i in 0..config.region_count {
-        let id = format!("proc_region_{}", i);
-        let name = format!("Procedural Region {}", i);
-        let description = "A dynamically generated region.".to_string();
-
-        // Randomly pick an environment type.
-        let environment = match rng.gen_range(0..3) {
-            0 => EnvironmentType::Fantasy,
-            1 => EnvironmentType::Technology,
-            _ => EnvironmentType::Hybrid,
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        GenerationConfig {
+            seed: None,
+            region_count: 8,
+            hub_id: NEXUS_ID.to_string(),
+            hub_name: "Nexus Core".to_string(),
+            hub_environment: EnvironmentType::RealLife,
+            existing_region_ids: Vec::new(),
+            loop_density: 0.25,
+            base_level: 1,
+            level_step: 2,
+            max_level: None,
+            themes: EnvironmentType::ALL.to_vec(),
+            id_prefix: "proc_region".to_string(),
+            layout: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenerationError {
+    InvalidConfig(String),
+    /// The connectivity check failed for these regions
+    Unreachable(Vec<String>),
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerationError::InvalidConfig(reason) => write!(f, "Invalid generation config: {}", reason),
+            GenerationError::Unreachable(ids) => {
+                write!(f, "Generated regions unreachable from the hub: {}", ids.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedWorld {
+    /// The seed actually used, so a random world can be generated again
+    pub seed: u64,
+    pub hub_id: String,
+    pub regions: Vec<Region>,
+    /// Portals to add to the hub region, which isn't part of `regions`
+    pub hub_portals: Vec<Portal>,
+}
+
+impl GeneratedWorld {
+    /// Regions that can't be reached from the hub at any level. Empty for a valid world.
+    pub fn unreachable_regions(&self) -> Vec<String> {
+        let hub = Region {
+            id: self.hub_id.clone(),
+            name: self.hub_id.clone(),
+            description: String::new(),
+            environment: EnvironmentType::RealLife,
+            portals: self.hub_portals.clone(),
+            anchor_point: None,
+            generated: None,
+            layout: None,
         };
+        let mut regions = self.regions.clone();
+        regions.push(hub);
+        let map = MapGraph::new(regions);
+
+        let reachable = map.reachable_from(&self.hub_id, i32::MAX);
+        self.regions
+            .iter()
+            .filter(|r| reachable.binary_search(&r.id).is_err())
+            .map(|r| r.id.clone())
+            .collect()
+    }
+}
+
+/// Generate a world as a graph hanging off the hub: a random spanning tree rooted at the hub
+/// keeps everything connected, extra portal pairs add loops, and portals leading further from
+/// the hub need a higher level. Names and descriptions come from `text`, themed by each region's
+/// environment. The same seed always gives the same world.
+pub fn generate_world(config: GenerationConfig, text: &TextGenerator) -> Result<GeneratedWorld, GenerationError> {
+    if !(0.0..=1.0).contains(&config.loop_density) {
+        return Err(GenerationError::InvalidConfig(format!(
+            "loop_density must be between 0 and 1, got {}",
+            config.loop_density
+        )));
+    }
+    if config.base_level < 0 || config.level_step < 0 {
+        return Err(GenerationError::InvalidConfig("levels must not be negative".to_string()));
+    }
+    if config.max_level.is_some_and(|max| max < config.base_level) {
+        return Err(GenerationError::InvalidConfig("max_level must not be below base_level".to_string()));
+    }
+    if config.themes.is_empty() {
+        return Err(GenerationError::InvalidConfig("themes must not be empty".to_string()));
+    }
+
+    // Initialize RNG with a seed for reproducibility.
+    let seed = config.seed.unwrap_or_else(random_seed);
+    let mut rng = seeded_rng(seed);
+
+    // Node 0 is the hub, nodes 1..=n the generated regions
+    let node_count = config.region_count + 1;
+    let ids: Vec<String> = std::iter::once(config.hub_id.clone())
+        .chain((1..node_count).map(|i| unique_id(format!("{}_{}", config.id_prefix, i), &config.existing_region_ids)))
+        .collect();
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); node_count];
+
+    // Attaching each region to a random earlier one gives a spanning tree rooted at the hub
+    for node in 1..node_count {
+        let parent = rng.gen_range(0..node);
+        adjacency[parent].push(node);
+        adjacency[node].push(parent);
+    }
+
+    // Loops between regions that aren't already linked
+    for node in 1..node_count {
+        if !rng.gen_bool(config.loop_density) {
+            continue;
+        }
+        let candidates: Vec<usize> = (1..node_count)
+            .filter(|&other| other != node && !adjacency[node].contains(&other))
+            .collect();
+        if let Some(&other) = candidates.choose(&mut rng) {
+            adjacency[node].push(other);
+            adjacency[other].push(node);
+        }
+    }
 
-        // Create dummy portals connecting regions (ensure connectivity later).
-        let portal = Portal {
-            id: format!("portal_{}_to_next", i),
-            name: format!("Portal from {} to {}", id, id),
-            leads_to: if i + 1 < config.region_count {
-                format!("proc_region_{}", i + 1)
-            } else {
-                "nexus".to_string()  // last region loops back to nexus
-            },
-            required_level: rng.gen_range(1..10),
+    let distance = hop_distances(&adjacency);
+    let level_for = |hops: usize| {
+        let level = config.base_level + config.level_step * (hops.saturating_sub(1) as i32);
+        config.max_level.map_or(level, |max| level.min(max))
+    };
+
+    let environments: Vec<EnvironmentType> = std::iter::once(config.hub_environment)
+        .chain((1..node_count).map(|_| *config.themes.choose(&mut rng).unwrap()))
+        .collect();
+
+    let mut text_rng = text_rng(seed);
+    let mut names = vec![config.hub_name.clone()];
+    for &environment in &environments[1..] {
+        let name = distinct_name(&names, || text.region_name(environment, &mut text_rng));
+        names.push(name);
+    }
+
+    // Portals deeper into the world are gated by the target's distance from the hub; portals
+    // back toward it are always open, so nobody gets stuck
+    let mut portals_from = |node: usize| -> Vec<Portal> {
+        let mut targets = adjacency[node].clone();
+        targets.sort_unstable();
+        targets
+            .into_iter()
+            .map(|target| Portal {
+                id: format!("portal_{}_to_{}", ids[node], ids[target]),
+                name: text.portal_name(environments[node], &names[target], &mut text_rng),
+                leads_to: ids[target].clone(),
+                required_level: if distance[target] > distance[node] { level_for(distance[target]) } else { 0 },
+                required_item: None,
+            })
+            .collect()
+    };
+
+    let hub_portals = portals_from(0);
+    let region_portals: Vec<Vec<Portal>> = (1..node_count).map(&mut portals_from).collect();
+    // Drawn after everything else, so layouts don't change the rest of the world
+    let layout_seeds: Vec<u64> = (1..node_count).map(|_| rng.gen()).collect();
+    let regions = (1..node_count)
+        .zip(region_portals)
+        .zip(layout_seeds)
+        .map(|((node, portals), layout_seed)| Region {
+            id: ids[node].clone(),
+            name: names[node].clone(),
+            description: text.region_description(environments[node], &names[node], &mut text_rng),
+            environment: environments[node],
+            layout: config.layout.as_ref().map(|layout| generate_layout(layout, layout_seed, &portals)),
+            portals,
+            // Regions right next to the nexus are anchored on the nexus portal leading in
+            anchor_point: hub_portals
+                .iter()
+                .find(|p| config.hub_id == NEXUS_ID && p.leads_to == ids[node])
+                .map(|p| p.id.clone()),
+            generated: Some(GenerationInfo {
+                generator: GENERATOR_NAME.to_string(),
+                seed,
+                version: GENERATOR_VERSION,
+            }),
+        })
+        .collect();
+
+    let world = GeneratedWorld { seed, hub_id: config.hub_id, regions, hub_portals };
+    let unreachable = world.unreachable_regions();
+    if !unreachable.is_empty() {
+        return Err(GenerationError::Unreachable(unreachable));
+    }
+    Ok(world)
+}
+
+/// A name from `generate` that isn't in `taken`. Grammars are small, so after a few clashes the
+/// name is numbered instead.
+pub(crate) fn distinct_name(taken: &[String], mut generate: impl FnMut() -> String) -> String {
+    let mut name = generate();
+    for _ in 0..8 {
+        if !taken.contains(&name) {
+            return name;
+        }
+        name = generate();
+    }
+    (2..)
+        .map(|n| format!("{} {}", name, n))
+        .find(|numbered| !taken.contains(numbered))
+        .unwrap()
+}
+
+/// Hops from the hub (node 0) to every node
+fn hop_distances(adjacency: &[Vec<usize>]) -> Vec<usize> {
+    let mut distance = vec![usize::MAX; adjacency.len()];
+    distance[0] = 0;
+    let mut queue = VecDeque::from([0]);
+    while let Some(node) = queue.pop_front() {
+        for &next in &adjacency[node] {
+            if distance[next] == usize::MAX {
+                distance[next] = distance[node] + 1;
+                queue.push_back(next);
+            }
+        }
+    }
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    fn world(seed: u64) -> GeneratedWorld {
+        let config = GenerationConfig { seed: Some(seed), region_count: 12, loop_density: 0.5, ..Default::default() };
+        generate_world(config, &TextGenerator::builtin()).unwrap()
+    }
+
+    /// Regions reachable from the hub through portals needing at most `level`
+    fn reachable(world: &GeneratedWorld, from: &str, level: i32) -> HashSet<String> {
+        let mut portals: HashMap<&str, &[Portal]> =
+            world.regions.iter().map(|r| (r.id.as_str(), r.portals.as_slice())).collect();
+        portals.insert(&world.hub_id, &world.hub_portals);
+        let mut seen = HashSet::from([from.to_string()]);
+        let mut pending = vec![from.to_string()];
+        while let Some(id) = pending.pop() {
+            for portal in portals[id.as_str()].iter().filter(|p| p.required_level <= level) {
+                if seen.insert(portal.leads_to.clone()) {
+                    pending.push(portal.leads_to.clone());
+                }
+            }
+        }
+        seen
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_world() {
+        assert_eq!(format!("{:?}", world(42)), format!("{:?}", world(42)));
+        assert_ne!(format!("{:?}", world(42).regions), format!("{:?}", world(43).regions));
+    }
+
+    #[test]
+    fn every_region_is_reachable_from_the_hub() {
+        for seed in 0..50 {
+            let world = world(seed);
+            assert_eq!(world.regions.len(), 12);
+            let reachable = reachable(&world, &world.hub_id, i32::MAX);
+            for region in &world.regions {
+                assert!(reachable.contains(&region.id), "seed {}: {} is cut off", seed, region.id);
+            }
+        }
+    }
+
+    #[test]
+    fn the_way_back_to_the_hub_is_always_open() {
+        for seed in 0..50 {
+            let world = world(seed);
+            for region in &world.regions {
+                assert!(reachable(&world, &region.id, 0).contains(&world.hub_id), "seed {}: stuck in {}", seed, region.id);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_ids_skip_existing_regions() {
+        let config = GenerationConfig {
+            seed: Some(7),
+            region_count: 3,
+            existing_region_ids: vec!["proc_region_1".to_string()],
+            ..Default::default()
         };
+        let world = generate_world(config, &TextGenerator::builtin()).unwrap();
+        let ids: Vec<&str> = world.regions.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["proc_region_1_2", "proc_region_2", "proc_region_3"]);
+    }
 
-        regions.push(DungeonRegion {
-            id,
-            name,
-            description,
-            environment,
-            portals: vec![portal],
-            anchor_point: None,
-        });
+    #[test]
+    fn rejects_an_out_of_range_loop_density() {
+        let config = GenerationConfig { loop_density: 1.5, ..Default::default() };
+        assert!(matches!(generate_world(config, &TextGenerator::builtin()), Err(GenerationError::InvalidConfig(_))));
     }
-    
-    regions
-}
\ No newline at end of file
+}